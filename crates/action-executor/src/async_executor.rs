@@ -0,0 +1,237 @@
+// Async view of the executor trait.
+//
+// Protocols like libei are asynchronous at heart, and blocking on them
+// inside `Action::execute` stalls the runtime. Actions run against this
+// trait instead; every synchronous `ActionExecutor` implements it through
+// the blanket impl below, so existing backends work unchanged.
+
+use crate::{
+    ActionExecutor, ExecutorError, InputKind, InputState, Key, LockKey, Monitor, MouseButton,
+};
+use anyhow::Result;
+
+/// Input simulation with async methods, which actions execute against
+///
+/// Futures are not required to be `Send`, matching `Action::execute`.
+#[allow(async_fn_in_trait)]
+pub trait AsyncActionExecutor {
+    /// Simulate a key press or release
+    async fn simulate_key(&self, key: Key, state: InputState) -> Result<(), ExecutorError>;
+
+    /// Simulate a mouse button press or release
+    async fn simulate_mouse(
+        &self,
+        button: MouseButton,
+        state: InputState,
+    ) -> Result<(), ExecutorError>;
+
+    /// Simulate several key and button events back-to-back
+    async fn simulate_batch(&self, events: &[(InputKind, InputState)])
+        -> Result<(), ExecutorError>;
+
+    /// Simulate a raw hardware scan code
+    async fn simulate_scancode(&self, code: u16, state: InputState) -> Result<(), ExecutorError>;
+
+    /// Type a character by code point, bypassing the keyboard layout
+    async fn send_unicode(&self, c: char) -> Result<(), ExecutorError>;
+
+    /// Move mouse cursor to absolute position
+    async fn mouse_move_abs(&self, x: i32, y: i32) -> Result<(), ExecutorError>;
+
+    /// Move mouse cursor by relative offset
+    async fn mouse_move_rel(&self, dx: i32, dy: i32) -> Result<(), ExecutorError>;
+
+    /// Move the cursor to a position relative to a monitor's top-left
+    async fn mouse_move_to_monitor(
+        &self,
+        monitor: usize,
+        x: i32,
+        y: i32,
+    ) -> Result<(), ExecutorError>;
+
+    /// Connected monitors, ordered left to right then top to bottom
+    async fn monitors(&self) -> Result<Vec<Monitor>, ExecutorError>;
+
+    /// Scroll the wheel by whole detents
+    async fn scroll(&self, dx: i32, dy: i32) -> Result<(), ExecutorError>;
+
+    /// Current cursor position in screen coordinates
+    async fn current_position(&self) -> Result<(i32, i32), ExecutorError>;
+
+    /// Titles of all visible top-level windows
+    async fn window_titles(&self) -> Result<Vec<String>, ExecutorError>;
+
+    /// Color of the screen pixel at a position
+    async fn pixel_color(&self, x: i32, y: i32) -> Result<(u8, u8, u8), ExecutorError>;
+
+    /// Keys the OS currently reports as held
+    async fn held_keys(&self) -> Result<Vec<Key>, ExecutorError>;
+
+    /// Whether a lock key is currently toggled on
+    async fn lock_state(&self, lock: LockKey) -> Result<bool, ExecutorError>;
+
+    /// Turn a lock key on or off
+    async fn set_lock(&self, lock: LockKey, on: bool) -> Result<(), ExecutorError>;
+
+    /// Block or unblock physical input; synchronous so a drop guard can
+    /// always undo it
+    fn block_input(&self, blocked: bool) -> Result<(), ExecutorError>;
+}
+
+impl<E: ActionExecutor + ?Sized> AsyncActionExecutor for E {
+    async fn simulate_key(&self, key: Key, state: InputState) -> Result<(), ExecutorError> {
+        ActionExecutor::simulate_key(self, key, state)
+    }
+
+    async fn simulate_mouse(
+        &self,
+        button: MouseButton,
+        state: InputState,
+    ) -> Result<(), ExecutorError> {
+        ActionExecutor::simulate_mouse(self, button, state)
+    }
+
+    async fn simulate_batch(
+        &self,
+        events: &[(InputKind, InputState)],
+    ) -> Result<(), ExecutorError> {
+        ActionExecutor::simulate_batch(self, events)
+    }
+
+    async fn simulate_scancode(&self, code: u16, state: InputState) -> Result<(), ExecutorError> {
+        ActionExecutor::simulate_scancode(self, code, state)
+    }
+
+    async fn send_unicode(&self, c: char) -> Result<(), ExecutorError> {
+        ActionExecutor::send_unicode(self, c)
+    }
+
+    async fn mouse_move_abs(&self, x: i32, y: i32) -> Result<(), ExecutorError> {
+        ActionExecutor::mouse_move_abs(self, x, y)
+    }
+
+    async fn mouse_move_rel(&self, dx: i32, dy: i32) -> Result<(), ExecutorError> {
+        ActionExecutor::mouse_move_rel(self, dx, dy)
+    }
+
+    async fn mouse_move_to_monitor(
+        &self,
+        monitor: usize,
+        x: i32,
+        y: i32,
+    ) -> Result<(), ExecutorError> {
+        ActionExecutor::mouse_move_to_monitor(self, monitor, x, y)
+    }
+
+    async fn monitors(&self) -> Result<Vec<Monitor>, ExecutorError> {
+        ActionExecutor::monitors(self)
+    }
+
+    async fn scroll(&self, dx: i32, dy: i32) -> Result<(), ExecutorError> {
+        ActionExecutor::scroll(self, dx, dy)
+    }
+
+    async fn current_position(&self) -> Result<(i32, i32), ExecutorError> {
+        ActionExecutor::current_position(self)
+    }
+
+    async fn window_titles(&self) -> Result<Vec<String>, ExecutorError> {
+        ActionExecutor::window_titles(self)
+    }
+
+    async fn pixel_color(&self, x: i32, y: i32) -> Result<(u8, u8, u8), ExecutorError> {
+        ActionExecutor::pixel_color(self, x, y)
+    }
+
+    async fn held_keys(&self) -> Result<Vec<Key>, ExecutorError> {
+        ActionExecutor::held_keys(self)
+    }
+
+    async fn lock_state(&self, lock: LockKey) -> Result<bool, ExecutorError> {
+        ActionExecutor::lock_state(self, lock)
+    }
+
+    async fn set_lock(&self, lock: LockKey, on: bool) -> Result<(), ExecutorError> {
+        ActionExecutor::set_lock(self, lock, on)
+    }
+
+    fn block_input(&self, blocked: bool) -> Result<(), ExecutorError> {
+        ActionExecutor::block_input(self, blocked)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Action, MockExecutor, RecordedCall};
+
+    #[tokio::test]
+    async fn blanket_impl_forwards_calls_and_results() {
+        let mock = MockExecutor::new()
+            .with_position(5, 5)
+            .with_lock_on(LockKey::CapsLock);
+        let executor: &dyn ActionExecutor = &mock;
+
+        AsyncActionExecutor::simulate_key(executor, Key::A, InputState::Press)
+            .await
+            .unwrap();
+        AsyncActionExecutor::mouse_move_rel(executor, 10, -5)
+            .await
+            .unwrap();
+        AsyncActionExecutor::set_lock(executor, LockKey::NumLock, true)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            AsyncActionExecutor::current_position(executor)
+                .await
+                .unwrap(),
+            (15, 0)
+        );
+        assert!(AsyncActionExecutor::lock_state(executor, LockKey::CapsLock)
+            .await
+            .unwrap());
+        assert!(AsyncActionExecutor::lock_state(executor, LockKey::NumLock)
+            .await
+            .unwrap());
+        assert_eq!(
+            mock.calls(),
+            [
+                RecordedCall::Key(Key::A, InputState::Press),
+                RecordedCall::MoveRel { dx: 10, dy: -5 },
+                RecordedCall::SetLock {
+                    lock: LockKey::NumLock,
+                    on: true
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn blanket_impl_forwards_errors() {
+        let mock = MockExecutor::failing_at(1);
+        let err = AsyncActionExecutor::scroll(&mock, 0, 1).await.unwrap_err();
+        assert!(err.to_string().contains("injected failure"), "{err}");
+        // Default methods of the sync trait come through as well
+        let err = AsyncActionExecutor::simulate_scancode(&mock, 0x1e, InputState::Press)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ExecutorError::Unsupported(_)), "{err}");
+    }
+
+    #[tokio::test]
+    async fn actions_run_against_a_sync_executor() {
+        let mock = MockExecutor::new();
+        Action::Click(MouseButton::Left)
+            .execute(&mock)
+            .await
+            .unwrap();
+        assert_eq!(
+            mock.calls(),
+            [
+                RecordedCall::Mouse(MouseButton::Left, InputState::Press),
+                RecordedCall::Mouse(MouseButton::Left, InputState::Release),
+            ]
+        );
+    }
+}
@@ -0,0 +1,146 @@
+// Chainable construction of action sequences, instead of nesting
+// `Action::Sequence(vec![...])` by hand
+
+use crate::{Action, Key, MouseButton};
+use std::time::Duration;
+
+/// Builds an `Action::Sequence` one step at a time
+///
+/// ```
+/// # use action_executor::{Action, ActionBuilder, Key, MouseButton};
+/// # use std::time::Duration;
+/// let built = ActionBuilder::new()
+///     .press(Key::A)
+///     .delay_ms(50)
+///     .click(MouseButton::Left)
+///     .build();
+/// let manual = Action::Sequence(vec![
+///     Action::PressKey(Key::A),
+///     Action::Delay(Duration::from_millis(50)),
+///     Action::Click(MouseButton::Left),
+/// ]);
+/// assert_eq!(format!("{built:?}"), format!("{manual:?}"));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ActionBuilder {
+    actions: Vec<Action>,
+}
+
+impl ActionBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Press and release a key
+    pub fn press(self, key: Key) -> Self {
+        self.then(Action::PressKey(key))
+    }
+
+    /// Hold a key down until a later `release`
+    pub fn hold(self, key: Key) -> Self {
+        self.then(Action::HoldKey(key))
+    }
+
+    /// Release a held key
+    pub fn release(self, key: Key) -> Self {
+        self.then(Action::ReleaseKey(key))
+    }
+
+    /// Click a mouse button
+    pub fn click(self, button: MouseButton) -> Self {
+        self.then(Action::Click(button))
+    }
+
+    /// Wait for `ms` milliseconds
+    pub fn delay_ms(self, ms: u64) -> Self {
+        self.then(Action::Delay(Duration::from_millis(ms)))
+    }
+
+    /// Type a text string
+    pub fn type_text(self, text: impl Into<String>) -> Self {
+        self.then(Action::TypeText(text.into()))
+    }
+
+    /// Append any action
+    pub fn then(mut self, action: Action) -> Self {
+        self.actions.push(action);
+        self
+    }
+
+    /// Run the most recently added step `count` times in total; does
+    /// nothing before the first step
+    ///
+    /// ```
+    /// # use action_executor::{Action, ActionBuilder, Key};
+    /// let built = ActionBuilder::new()
+    ///     .hold(Key::Shift)
+    ///     .press(Key::W)
+    ///     .repeat(5)
+    ///     .release(Key::Shift)
+    ///     .build();
+    /// let manual = Action::Sequence(vec![
+    ///     Action::HoldKey(Key::Shift),
+    ///     Action::Repeat {
+    ///         inner: Box::new(Action::PressKey(Key::W)),
+    ///         count: 5,
+    ///     },
+    ///     Action::ReleaseKey(Key::Shift),
+    /// ]);
+    /// assert_eq!(format!("{built:?}"), format!("{manual:?}"));
+    /// ```
+    pub fn repeat(mut self, count: u32) -> Self {
+        if let Some(last) = self.actions.pop() {
+            self.actions.push(Action::Repeat {
+                inner: Box::new(last),
+                count,
+            });
+        }
+        self
+    }
+
+    pub fn build(self) -> Action {
+        Action::Sequence(self.actions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{MockExecutor, RecordedCall};
+    use crate::InputState::{Press, Release};
+
+    #[test]
+    fn built_sequence_runs_its_steps_in_order() {
+        let executor = MockExecutor::new();
+        ActionBuilder::new()
+            .hold(Key::Shift)
+            .press(Key::W)
+            .repeat(2)
+            .release(Key::Shift)
+            .click(MouseButton::Left)
+            .build()
+            .execute_blocking(&executor)
+            .unwrap();
+        assert_eq!(
+            executor.calls(),
+            [
+                RecordedCall::Key(Key::Shift, Press),
+                RecordedCall::Key(Key::W, Press),
+                RecordedCall::Key(Key::W, Release),
+                RecordedCall::Key(Key::W, Press),
+                RecordedCall::Key(Key::W, Release),
+                RecordedCall::Key(Key::Shift, Release),
+                RecordedCall::Mouse(MouseButton::Left, Press),
+                RecordedCall::Mouse(MouseButton::Left, Release),
+            ]
+        );
+    }
+
+    #[test]
+    fn repeat_before_any_step_does_nothing() {
+        let Action::Sequence(steps) = ActionBuilder::new().repeat(3).press(Key::A).build() else {
+            panic!("not a sequence");
+        };
+        assert!(matches!(steps[..], [Action::PressKey(Key::A)]));
+    }
+}
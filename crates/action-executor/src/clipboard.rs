@@ -0,0 +1,53 @@
+// Clipboard access for pasting text instead of typing it
+
+use anyhow::{Context as _, Result};
+use std::fmt::Debug;
+use std::sync::Mutex;
+
+/// Text clipboard, separate from the executor so it can be mocked
+pub trait Clipboard: Debug + Send + Sync {
+    /// Current text contents, or None if the clipboard holds no text
+    fn get_text(&self) -> Result<Option<String>>;
+
+    /// Replace the contents with `text`
+    fn set_text(&self, text: &str) -> Result<()>;
+}
+
+/// The OS clipboard
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClipboard;
+
+// On X11 and Wayland the contents are served by whoever set them, so the
+// handle lives for the whole process rather than one paste
+static SYSTEM: Mutex<Option<arboard::Clipboard>> = Mutex::new(None);
+
+impl SystemClipboard {
+    fn with<T>(f: impl FnOnce(&mut arboard::Clipboard) -> Result<T>) -> Result<T> {
+        let mut system = SYSTEM
+            .lock()
+            .map_err(|_| anyhow::anyhow!("clipboard lock poisoned"))?;
+        let clipboard = match &mut *system {
+            Some(clipboard) => clipboard,
+            None => system.insert(arboard::Clipboard::new().context("cannot open the clipboard")?),
+        };
+        f(clipboard)
+    }
+}
+
+impl Clipboard for SystemClipboard {
+    fn get_text(&self) -> Result<Option<String>> {
+        Self::with(|clipboard| match clipboard.get_text() {
+            Ok(text) => Ok(Some(text)),
+            Err(arboard::Error::ContentNotAvailable) => Ok(None),
+            Err(err) => Err(err).context("failed to read the clipboard"),
+        })
+    }
+
+    fn set_text(&self, text: &str) -> Result<()> {
+        Self::with(|clipboard| {
+            clipboard
+                .set_text(text)
+                .context("failed to write the clipboard")
+        })
+    }
+}
@@ -0,0 +1,242 @@
+// Input emulation over libei, for Wayland sessions without uinput access.
+//
+// The compositor (or the RemoteDesktop portal on its behalf) hands out an
+// EIS socket through `LIBEI_SOCKET`. Known to work with GNOME 45+ (Mutter)
+// and KDE Plasma 6.1+ (KWin); wlroots compositors such as Sway have no EIS
+// server, so use `UinputExecutor` there.
+
+use crate::{ActionExecutor, ExecutorError, InputState, Key, MouseButton};
+use anyhow::{Context as _, Result};
+use reis::ei;
+use reis::event::{DeviceCapability, EiEvent, EiEventConverter};
+use std::io;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How long the constructor waits for the compositor to offer devices
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+const CAPABILITIES: &[DeviceCapability] = &[
+    DeviceCapability::Keyboard,
+    DeviceCapability::Pointer,
+    DeviceCapability::PointerAbsolute,
+    DeviceCapability::Button,
+    DeviceCapability::Scroll,
+];
+
+/// Executor emulating input as a libei sender client
+pub struct EiExecutor {
+    state: Mutex<Session>,
+}
+
+struct Session {
+    context: ei::Context,
+    converter: EiEventConverter,
+    devices: Vec<reis::event::Device>,
+    // Set once the compositor dropped us; every later call fails
+    disconnected: bool,
+}
+
+impl EiExecutor {
+    /// Connect to `LIBEI_SOCKET`, complete the handshake and wait until the
+    /// compositor has resumed at least one device to emulate on
+    pub fn new() -> Result<Self> {
+        let context = ei::Context::connect_to_env()
+            .context("failed to connect to the EIS socket")?
+            .context("LIBEI_SOCKET is not set; is this a libei-capable Wayland session?")?;
+        let handshake = reis::handshake::ei_handshake_blocking(
+            &context,
+            "HandPlusPlus",
+            ei::handshake::ContextType::Sender,
+        )
+        .context("libei handshake failed")?;
+
+        let mut session = Session {
+            converter: EiEventConverter::new(&context, handshake),
+            context,
+            devices: Vec::new(),
+            disconnected: false,
+        };
+        let deadline = Instant::now() + HANDSHAKE_TIMEOUT;
+        while session.devices.is_empty() {
+            if Instant::now() >= deadline {
+                anyhow::bail!(
+                    "the compositor offered no input devices within {HANDSHAKE_TIMEOUT:?}"
+                );
+            }
+            session.dispatch()?;
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        Ok(Self {
+            state: Mutex::new(session),
+        })
+    }
+
+    /// Run `emit` against the first device with `capability`, then frame
+    /// and flush
+    fn with_device(
+        &self,
+        capability: DeviceCapability,
+        emit: impl FnOnce(&reis::event::Device) -> Option<()>,
+    ) -> Result<(), ExecutorError> {
+        let mut session = self
+            .state
+            .lock()
+            .map_err(|_| anyhow::anyhow!("libei session lock poisoned"))?;
+        session.dispatch()?;
+        let serial = session.converter.connection().serial();
+        let device = session
+            .devices
+            .iter()
+            .find(|device| device.has_capability(capability))
+            .with_context(|| format!("the compositor offers no device with {capability:?}"))?;
+        emit(device).with_context(|| format!("device lacks the {capability:?} interface"))?;
+        device.device().frame(serial, timestamp_us());
+        session.flush()?;
+        Ok(())
+    }
+}
+
+impl Session {
+    /// Read pending events without blocking, tracking seats and devices
+    fn dispatch(&mut self) -> Result<()> {
+        if self.disconnected {
+            anyhow::bail!("the compositor closed the libei connection");
+        }
+        match self.context.read() {
+            Ok(0) => return self.disconnect(),
+            Ok(_) => {}
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => {}
+            Err(err) => return self.fail(err),
+        }
+        while let Some(request) = self.context.pending_event() {
+            match request {
+                reis::PendingRequestResult::Request(event) => {
+                    if let Err(err) = self.converter.handle_event(event) {
+                        tracing::warn!("invalid libei event: {err}");
+                    }
+                }
+                reis::PendingRequestResult::ParseError(err) => {
+                    tracing::warn!("unparsable libei event: {err}");
+                }
+                reis::PendingRequestResult::InvalidObject(_) => {}
+            }
+        }
+        while let Some(event) = self.converter.next_event() {
+            match event {
+                EiEvent::SeatAdded(added) => added.seat.bind_capabilities(CAPABILITIES),
+                EiEvent::DeviceResumed(resumed) => {
+                    let device = resumed.device;
+                    device.device().start_emulating(resumed.serial, 1);
+                    self.devices.push(device);
+                }
+                EiEvent::DevicePaused(paused) => {
+                    self.devices.retain(|device| device != &paused.device)
+                }
+                EiEvent::DeviceRemoved(removed) => {
+                    self.devices.retain(|device| device != &removed.device)
+                }
+                EiEvent::Disconnected(_) => return self.disconnect(),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        match self.context.flush() {
+            Ok(()) => Ok(()),
+            Err(err) if err.kind() == io::ErrorKind::BrokenPipe => self.disconnect(),
+            Err(err) => self.fail(err),
+        }
+    }
+
+    fn disconnect(&mut self) -> Result<()> {
+        self.disconnected = true;
+        self.devices.clear();
+        anyhow::bail!("the compositor closed the libei connection")
+    }
+
+    fn fail(&mut self, err: io::Error) -> Result<()> {
+        self.disconnected = true;
+        self.devices.clear();
+        Err(anyhow::Error::new(err).context("libei connection failed"))
+    }
+}
+
+fn timestamp_us() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_micros() as u64)
+}
+
+/// evdev code for a mouse button (BTN_LEFT onwards)
+fn button_code(button: MouseButton) -> u32 {
+    match button {
+        MouseButton::Left => 0x110,
+        MouseButton::Right => 0x111,
+        MouseButton::Middle => 0x112,
+        MouseButton::Button4 => 0x113,
+        MouseButton::Button5 => 0x114,
+    }
+}
+
+fn key_state(state: InputState) -> ei::keyboard::KeyState {
+    match state {
+        InputState::Press => ei::keyboard::KeyState::Press,
+        InputState::Release => ei::keyboard::KeyState::Released,
+    }
+}
+
+impl ActionExecutor for EiExecutor {
+    fn simulate_key(&self, key: Key, state: InputState) -> Result<(), ExecutorError> {
+        self.simulate_scancode(key.physical().0, state)
+    }
+
+    fn simulate_mouse(&self, button: MouseButton, state: InputState) -> Result<(), ExecutorError> {
+        let state = match state {
+            InputState::Press => ei::button::ButtonState::Press,
+            InputState::Release => ei::button::ButtonState::Released,
+        };
+        self.with_device(DeviceCapability::Button, |device| {
+            device
+                .interface::<ei::Button>()
+                .map(|buttons| buttons.button(button_code(button), state))
+        })
+    }
+
+    fn simulate_scancode(&self, code: u16, state: InputState) -> Result<(), ExecutorError> {
+        // libei keycodes are evdev codes
+        self.with_device(DeviceCapability::Keyboard, |device| {
+            device
+                .interface::<ei::Keyboard>()
+                .map(|keyboard| keyboard.key(code.into(), key_state(state)))
+        })
+    }
+
+    fn mouse_move_abs(&self, x: i32, y: i32) -> Result<(), ExecutorError> {
+        self.with_device(DeviceCapability::PointerAbsolute, |device| {
+            device
+                .interface::<ei::PointerAbsolute>()
+                .map(|pointer| pointer.motion_absolute(x as f32, y as f32))
+        })
+    }
+
+    fn mouse_move_rel(&self, dx: i32, dy: i32) -> Result<(), ExecutorError> {
+        self.with_device(DeviceCapability::Pointer, |device| {
+            device
+                .interface::<ei::Pointer>()
+                .map(|pointer| pointer.motion_relative(dx as f32, dy as f32))
+        })
+    }
+
+    fn scroll(&self, dx: i32, dy: i32) -> Result<(), ExecutorError> {
+        // Discrete scroll is in 1/120ths of a detent, positive down
+        self.with_device(DeviceCapability::Scroll, |device| {
+            device
+                .interface::<ei::Scroll>()
+                .map(|scroll| scroll.scroll_discrete(dx * 120, -dy * 120))
+        })
+    }
+}
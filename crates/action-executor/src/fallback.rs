@@ -0,0 +1,269 @@
+// Executor fallback chain for heterogeneous environments
+
+use crate::{
+    AsyncActionExecutor, ExecutorError, InputKind, InputState, Key, LockKey, Monitor, MouseButton,
+};
+use anyhow::Result;
+use std::any::type_name;
+use std::future::Future;
+
+/// Executor that tries a primary backend, then a fallback, per call
+///
+/// Only calls the primary doesn't support (`ExecutorError::Unsupported`)
+/// go to the fallback, whose result is returned. Other errors are returned
+/// as they are: the primary may have injected part of a batch before
+/// failing, and sending it again would repeat presses. Longer chains nest,
+/// since executors with async methods can't be boxed into a list:
+/// `FallbackExecutor::new(a, FallbackExecutor::new(b, c))`.
+pub struct FallbackExecutor<A, B> {
+    primary: A,
+    fallback: B,
+}
+
+impl<A: AsyncActionExecutor, B: AsyncActionExecutor> FallbackExecutor<A, B> {
+    pub fn new(primary: A, fallback: B) -> Self {
+        Self { primary, fallback }
+    }
+
+    pub fn into_inner(self) -> (A, B) {
+        (self.primary, self.fallback)
+    }
+
+    // `fallback` is only awaited, and so only run, if `first` failed
+    async fn or_fallback<T>(
+        op: &str,
+        first: Result<T, ExecutorError>,
+        fallback: impl Future<Output = Result<T, ExecutorError>>,
+    ) -> Result<T, ExecutorError> {
+        match first {
+            Ok(value) => {
+                tracing::trace!(op, executor = type_name::<A>(), "handled");
+                Ok(value)
+            }
+            Err(ExecutorError::Unsupported(what)) => {
+                tracing::debug!(
+                    op,
+                    executor = type_name::<B>(),
+                    "{what} not supported by {}, falling back",
+                    type_name::<A>()
+                );
+                fallback.await
+            }
+            Err(err) => Err(err),
+        }
+    }
+}
+
+impl<A: AsyncActionExecutor, B: AsyncActionExecutor> AsyncActionExecutor
+    for FallbackExecutor<A, B>
+{
+    async fn simulate_key(&self, key: Key, state: InputState) -> Result<(), ExecutorError> {
+        let first = self.primary.simulate_key(key, state).await;
+        let fallback = self.fallback.simulate_key(key, state);
+        Self::or_fallback("simulate_key", first, fallback).await
+    }
+
+    async fn simulate_mouse(
+        &self,
+        button: MouseButton,
+        state: InputState,
+    ) -> Result<(), ExecutorError> {
+        let first = self.primary.simulate_mouse(button, state).await;
+        let fallback = self.fallback.simulate_mouse(button, state);
+        Self::or_fallback("simulate_mouse", first, fallback).await
+    }
+
+    async fn simulate_batch(
+        &self,
+        events: &[(InputKind, InputState)],
+    ) -> Result<(), ExecutorError> {
+        let first = self.primary.simulate_batch(events).await;
+        let fallback = self.fallback.simulate_batch(events);
+        Self::or_fallback("simulate_batch", first, fallback).await
+    }
+
+    async fn simulate_scancode(&self, code: u16, state: InputState) -> Result<(), ExecutorError> {
+        let first = self.primary.simulate_scancode(code, state).await;
+        let fallback = self.fallback.simulate_scancode(code, state);
+        Self::or_fallback("simulate_scancode", first, fallback).await
+    }
+
+    async fn send_unicode(&self, c: char) -> Result<(), ExecutorError> {
+        let first = self.primary.send_unicode(c).await;
+        Self::or_fallback("send_unicode", first, self.fallback.send_unicode(c)).await
+    }
+
+    async fn mouse_move_abs(&self, x: i32, y: i32) -> Result<(), ExecutorError> {
+        let first = self.primary.mouse_move_abs(x, y).await;
+        Self::or_fallback("mouse_move_abs", first, self.fallback.mouse_move_abs(x, y)).await
+    }
+
+    async fn mouse_move_rel(&self, dx: i32, dy: i32) -> Result<(), ExecutorError> {
+        let first = self.primary.mouse_move_rel(dx, dy).await;
+        let fallback = self.fallback.mouse_move_rel(dx, dy);
+        Self::or_fallback("mouse_move_rel", first, fallback).await
+    }
+
+    async fn mouse_move_to_monitor(
+        &self,
+        monitor: usize,
+        x: i32,
+        y: i32,
+    ) -> Result<(), ExecutorError> {
+        let first = self.primary.mouse_move_to_monitor(monitor, x, y).await;
+        let fallback = self.fallback.mouse_move_to_monitor(monitor, x, y);
+        Self::or_fallback("mouse_move_to_monitor", first, fallback).await
+    }
+
+    async fn monitors(&self) -> Result<Vec<Monitor>, ExecutorError> {
+        let first = self.primary.monitors().await;
+        Self::or_fallback("monitors", first, self.fallback.monitors()).await
+    }
+
+    async fn scroll(&self, dx: i32, dy: i32) -> Result<(), ExecutorError> {
+        let first = self.primary.scroll(dx, dy).await;
+        Self::or_fallback("scroll", first, self.fallback.scroll(dx, dy)).await
+    }
+
+    async fn current_position(&self) -> Result<(i32, i32), ExecutorError> {
+        let first = self.primary.current_position().await;
+        Self::or_fallback("current_position", first, self.fallback.current_position()).await
+    }
+
+    async fn window_titles(&self) -> Result<Vec<String>, ExecutorError> {
+        let first = self.primary.window_titles().await;
+        Self::or_fallback("window_titles", first, self.fallback.window_titles()).await
+    }
+
+    async fn pixel_color(&self, x: i32, y: i32) -> Result<(u8, u8, u8), ExecutorError> {
+        let first = self.primary.pixel_color(x, y).await;
+        Self::or_fallback("pixel_color", first, self.fallback.pixel_color(x, y)).await
+    }
+
+    async fn held_keys(&self) -> Result<Vec<Key>, ExecutorError> {
+        let first = self.primary.held_keys().await;
+        Self::or_fallback("held_keys", first, self.fallback.held_keys()).await
+    }
+
+    async fn lock_state(&self, lock: LockKey) -> Result<bool, ExecutorError> {
+        let first = self.primary.lock_state(lock).await;
+        Self::or_fallback("lock_state", first, self.fallback.lock_state(lock)).await
+    }
+
+    async fn set_lock(&self, lock: LockKey, on: bool) -> Result<(), ExecutorError> {
+        let first = self.primary.set_lock(lock, on).await;
+        Self::or_fallback("set_lock", first, self.fallback.set_lock(lock, on)).await
+    }
+
+    fn block_input(&self, blocked: bool) -> Result<(), ExecutorError> {
+        match self.primary.block_input(blocked) {
+            Err(ExecutorError::Unsupported(what)) => {
+                tracing::debug!(
+                    op = "block_input",
+                    executor = type_name::<B>(),
+                    "{what} not supported by {}, falling back",
+                    type_name::<A>()
+                );
+                self.fallback.block_input(blocked)
+            }
+            result => result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Action, ActionExecutor, MockExecutor, RateLimiter, RecordedCall};
+
+    /// Backend that supports nothing, like one missing from this session
+    struct Unavailable;
+
+    impl ActionExecutor for Unavailable {
+        fn simulate_key(&self, _key: Key, _state: InputState) -> Result<(), ExecutorError> {
+            Err(ExecutorError::Unsupported("keys"))
+        }
+
+        fn simulate_mouse(
+            &self,
+            _button: MouseButton,
+            _state: InputState,
+        ) -> Result<(), ExecutorError> {
+            Err(ExecutorError::Unsupported("mouse buttons"))
+        }
+
+        fn mouse_move_abs(&self, _x: i32, _y: i32) -> Result<(), ExecutorError> {
+            Err(ExecutorError::Unsupported("mouse moves"))
+        }
+
+        fn mouse_move_rel(&self, _dx: i32, _dy: i32) -> Result<(), ExecutorError> {
+            Err(ExecutorError::Unsupported("mouse moves"))
+        }
+    }
+
+    fn presses(count: usize) -> Vec<RecordedCall> {
+        vec![RecordedCall::Key(Key::A, InputState::Press); count]
+    }
+
+    #[tokio::test]
+    async fn primary_handles_calls_it_can() {
+        let chain = FallbackExecutor::new(MockExecutor::new(), MockExecutor::new());
+        chain.simulate_key(Key::A, InputState::Press).await.unwrap();
+        let (primary, fallback) = chain.into_inner();
+        assert_eq!(primary.calls(), presses(1));
+        assert!(fallback.calls().is_empty());
+    }
+
+    #[tokio::test]
+    async fn unsupported_calls_fall_through_to_the_fallback() {
+        let chain = FallbackExecutor::new(Unavailable, MockExecutor::new());
+        for _ in 0..3 {
+            chain.simulate_key(Key::A, InputState::Press).await.unwrap();
+        }
+        let (_, fallback) = chain.into_inner();
+        assert_eq!(fallback.calls(), presses(3));
+    }
+
+    #[tokio::test]
+    async fn failed_calls_are_not_sent_again() {
+        // The press goes out before the release fails
+        let chain = FallbackExecutor::new(MockExecutor::failing_at(2), MockExecutor::new());
+        let err = Action::PressKey(Key::A).execute(&chain).await.unwrap_err();
+        assert!(
+            matches!(err.downcast_ref(), Some(ExecutorError::Backend(_))),
+            "{err}"
+        );
+        let (primary, fallback) = chain.into_inner();
+        assert_eq!(primary.calls(), presses(1));
+        assert!(fallback.calls().is_empty());
+    }
+
+    #[tokio::test]
+    async fn fallback_error_is_returned_when_both_fail() {
+        let chain = FallbackExecutor::new(Unavailable, MockExecutor::new());
+        // Neither supports raw scan codes
+        let err = chain
+            .simulate_scancode(0x1e, InputState::Press)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ExecutorError::Unsupported(_)), "{err}");
+        let chain = FallbackExecutor::new(Unavailable, MockExecutor::failing_at(1));
+        let err = chain.mouse_move_rel(0, 1).await.unwrap_err();
+        assert!(matches!(err, ExecutorError::Backend(_)), "{err}");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn chains_async_only_executors() {
+        let chain = FallbackExecutor::new(Unavailable, RateLimiter::new(MockExecutor::new(), 1000));
+        Action::PressKey(Key::A).execute(&chain).await.unwrap();
+        let (_, fallback) = chain.into_inner();
+        // The batch was unsupported by the primary and went through whole
+        assert_eq!(
+            fallback.into_inner().calls(),
+            [
+                RecordedCall::Key(Key::A, InputState::Press),
+                RecordedCall::Key(Key::A, InputState::Release),
+            ]
+        );
+    }
+}
@@ -0,0 +1,422 @@
+// Character to key translation for typing text
+//
+// Keys are named by their US QWERTY position, so a layout describes which
+// positions the OS's active layout needs pressed to produce a character.
+
+use crate::{InputState, Key};
+use std::fmt::Debug;
+
+/// Translates characters into the key events that type them
+pub trait KeyboardLayout: Debug + Send + Sync {
+    /// Key events producing `c`, or None if the layout can't type it
+    ///
+    /// Characters behind a dead key take two taps: the dead key, then the
+    /// base character.
+    fn char_to_keys(&self, c: char) -> Option<Vec<(Key, InputState)>>;
+}
+
+/// Standard US QWERTY
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UsQwerty;
+
+/// US Dvorak
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Dvorak;
+
+/// German QWERTZ (T1), using AltGr for its third level and its dead keys
+/// (´ ` ^) for accented vowels
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GermanQwertz;
+
+impl KeyboardLayout for UsQwerty {
+    fn char_to_keys(&self, c: char) -> Option<Vec<(Key, InputState)>> {
+        let (key, level) = common(c, letter).or_else(|| us_symbol(c))?;
+        Some(level.tap(key))
+    }
+}
+
+impl KeyboardLayout for Dvorak {
+    fn char_to_keys(&self, c: char) -> Option<Vec<(Key, InputState)>> {
+        let (key, level) = common(c, dvorak_letter).or_else(|| dvorak_symbol(c))?;
+        Some(level.tap(key))
+    }
+}
+
+impl KeyboardLayout for GermanQwertz {
+    fn char_to_keys(&self, c: char) -> Option<Vec<(Key, InputState)>> {
+        if let Some((key, level)) = common(c, qwertz_letter).or_else(|| qwertz_symbol(c)) {
+            return Some(level.tap(key));
+        }
+        let ((dead, dead_level), base) = qwertz_dead(c)?;
+        let (key, level) = common(base, qwertz_letter)?;
+        let mut events = dead_level.tap(dead);
+        events.extend(level.tap(key));
+        Some(events)
+    }
+}
+
+/// Modifier state a character sits on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Level {
+    Base,
+    Shift,
+    AltGr,
+}
+
+impl Level {
+    fn tap(self, key: Key) -> Vec<(Key, InputState)> {
+        let modifier = match self {
+            Level::Base => None,
+            Level::Shift => Some(Key::Shift),
+            Level::AltGr => Some(Key::AltGr),
+        };
+        let mut events = Vec::with_capacity(4);
+        events.extend(modifier.map(|m| (m, InputState::Press)));
+        events.push((key, InputState::Press));
+        events.push((key, InputState::Release));
+        events.extend(modifier.map(|m| (m, InputState::Release)));
+        events
+    }
+}
+
+/// Letters, digits and whitespace, which only differ in letter positions
+fn common(c: char, letter: fn(char) -> Option<Key>) -> Option<(Key, Level)> {
+    if c.is_ascii_alphabetic() {
+        let level = if c.is_ascii_uppercase() {
+            Level::Shift
+        } else {
+            Level::Base
+        };
+        return letter(c.to_ascii_lowercase()).map(|key| (key, level));
+    }
+    let key = match c {
+        '0' => Key::Num0,
+        '1' => Key::Num1,
+        '2' => Key::Num2,
+        '3' => Key::Num3,
+        '4' => Key::Num4,
+        '5' => Key::Num5,
+        '6' => Key::Num6,
+        '7' => Key::Num7,
+        '8' => Key::Num8,
+        '9' => Key::Num9,
+        ' ' => Key::Space,
+        '\n' => Key::Enter,
+        '\t' => Key::Tab,
+        _ => return None,
+    };
+    Some((key, Level::Base))
+}
+
+fn letter(c: char) -> Option<Key> {
+    let key = match c {
+        'a' => Key::A,
+        'b' => Key::B,
+        'c' => Key::C,
+        'd' => Key::D,
+        'e' => Key::E,
+        'f' => Key::F,
+        'g' => Key::G,
+        'h' => Key::H,
+        'i' => Key::I,
+        'j' => Key::J,
+        'k' => Key::K,
+        'l' => Key::L,
+        'm' => Key::M,
+        'n' => Key::N,
+        'o' => Key::O,
+        'p' => Key::P,
+        'q' => Key::Q,
+        'r' => Key::R,
+        's' => Key::S,
+        't' => Key::T,
+        'u' => Key::U,
+        'v' => Key::V,
+        'w' => Key::W,
+        'x' => Key::X,
+        'y' => Key::Y,
+        'z' => Key::Z,
+        _ => return None,
+    };
+    Some(key)
+}
+
+fn us_symbol(c: char) -> Option<(Key, Level)> {
+    use Level::*;
+    let mapped = match c {
+        '!' => (Key::Num1, Shift),
+        '@' => (Key::Num2, Shift),
+        '#' => (Key::Num3, Shift),
+        '$' => (Key::Num4, Shift),
+        '%' => (Key::Num5, Shift),
+        '^' => (Key::Num6, Shift),
+        '&' => (Key::Num7, Shift),
+        '*' => (Key::Num8, Shift),
+        '(' => (Key::Num9, Shift),
+        ')' => (Key::Num0, Shift),
+        '`' => (Key::Grave, Base),
+        '~' => (Key::Grave, Shift),
+        '-' => (Key::Minus, Base),
+        '_' => (Key::Minus, Shift),
+        '=' => (Key::Equals, Base),
+        '+' => (Key::Equals, Shift),
+        '[' => (Key::LeftBracket, Base),
+        '{' => (Key::LeftBracket, Shift),
+        ']' => (Key::RightBracket, Base),
+        '}' => (Key::RightBracket, Shift),
+        '\\' => (Key::Backslash, Base),
+        '|' => (Key::Backslash, Shift),
+        ';' => (Key::Semicolon, Base),
+        ':' => (Key::Semicolon, Shift),
+        '\'' => (Key::Quote, Base),
+        '"' => (Key::Quote, Shift),
+        ',' => (Key::Comma, Base),
+        '<' => (Key::Comma, Shift),
+        '.' => (Key::Period, Base),
+        '>' => (Key::Period, Shift),
+        '/' => (Key::Slash, Base),
+        '?' => (Key::Slash, Shift),
+        _ => return None,
+    };
+    Some(mapped)
+}
+
+fn dvorak_letter(c: char) -> Option<Key> {
+    let key = match c {
+        'a' => Key::A,
+        'b' => Key::N,
+        'c' => Key::I,
+        'd' => Key::H,
+        'e' => Key::D,
+        'f' => Key::Y,
+        'g' => Key::U,
+        'h' => Key::J,
+        'i' => Key::G,
+        'j' => Key::C,
+        'k' => Key::V,
+        'l' => Key::P,
+        'm' => Key::M,
+        'n' => Key::L,
+        'o' => Key::S,
+        'p' => Key::R,
+        'q' => Key::X,
+        'r' => Key::O,
+        's' => Key::Semicolon,
+        't' => Key::K,
+        'u' => Key::F,
+        'v' => Key::Period,
+        'w' => Key::Comma,
+        'x' => Key::B,
+        'y' => Key::T,
+        'z' => Key::Slash,
+        _ => return None,
+    };
+    Some(key)
+}
+
+fn dvorak_symbol(c: char) -> Option<(Key, Level)> {
+    use Level::*;
+    let mapped = match c {
+        // The number row matches US QWERTY
+        '!' | '@' | '#' | '$' | '%' | '^' | '&' | '*' | '(' | ')' | '`' | '~' => {
+            return us_symbol(c)
+        }
+        '[' => (Key::Minus, Base),
+        '{' => (Key::Minus, Shift),
+        ']' => (Key::Equals, Base),
+        '}' => (Key::Equals, Shift),
+        '\'' => (Key::Q, Base),
+        '"' => (Key::Q, Shift),
+        ',' => (Key::W, Base),
+        '<' => (Key::W, Shift),
+        '.' => (Key::E, Base),
+        '>' => (Key::E, Shift),
+        '/' => (Key::LeftBracket, Base),
+        '?' => (Key::LeftBracket, Shift),
+        '=' => (Key::RightBracket, Base),
+        '+' => (Key::RightBracket, Shift),
+        '\\' => (Key::Backslash, Base),
+        '|' => (Key::Backslash, Shift),
+        ';' => (Key::Z, Base),
+        ':' => (Key::Z, Shift),
+        '-' => (Key::Quote, Base),
+        '_' => (Key::Quote, Shift),
+        _ => return None,
+    };
+    Some(mapped)
+}
+
+fn qwertz_letter(c: char) -> Option<Key> {
+    // Y and Z trade places; everything else matches US QWERTY
+    match c {
+        'y' => Some(Key::Z),
+        'z' => Some(Key::Y),
+        _ => letter(c),
+    }
+}
+
+fn qwertz_symbol(c: char) -> Option<(Key, Level)> {
+    use Level::*;
+    let mapped = match c {
+        '!' => (Key::Num1, Shift),
+        '"' => (Key::Num2, Shift),
+        '§' => (Key::Num3, Shift),
+        '$' => (Key::Num4, Shift),
+        '%' => (Key::Num5, Shift),
+        '&' => (Key::Num6, Shift),
+        '/' => (Key::Num7, Shift),
+        '(' => (Key::Num8, Shift),
+        ')' => (Key::Num9, Shift),
+        '=' => (Key::Num0, Shift),
+        '{' => (Key::Num7, AltGr),
+        '[' => (Key::Num8, AltGr),
+        ']' => (Key::Num9, AltGr),
+        '}' => (Key::Num0, AltGr),
+        '@' => (Key::Q, AltGr),
+        '€' => (Key::E, AltGr),
+        'ß' => (Key::Minus, Base),
+        '?' => (Key::Minus, Shift),
+        '\\' => (Key::Minus, AltGr),
+        'ü' => (Key::LeftBracket, Base),
+        'Ü' => (Key::LeftBracket, Shift),
+        '+' => (Key::RightBracket, Base),
+        '*' => (Key::RightBracket, Shift),
+        '~' => (Key::RightBracket, AltGr),
+        'ö' => (Key::Semicolon, Base),
+        'Ö' => (Key::Semicolon, Shift),
+        'ä' => (Key::Quote, Base),
+        'Ä' => (Key::Quote, Shift),
+        '#' => (Key::Backslash, Base),
+        '\'' => (Key::Backslash, Shift),
+        ',' => (Key::Comma, Base),
+        ';' => (Key::Comma, Shift),
+        '.' => (Key::Period, Base),
+        ':' => (Key::Period, Shift),
+        '-' => (Key::Slash, Base),
+        '_' => (Key::Slash, Shift),
+        '°' => (Key::Grave, Shift),
+        _ => return None,
+    };
+    Some(mapped)
+}
+
+/// Dead key and base character composing `c`; a space after the dead key
+/// types the accent itself
+fn qwertz_dead(c: char) -> Option<((Key, Level), char)> {
+    const ACUTE: (Key, Level) = (Key::Equals, Level::Base);
+    const GRAVE: (Key, Level) = (Key::Equals, Level::Shift);
+    const CIRCUMFLEX: (Key, Level) = (Key::Grave, Level::Base);
+    let composed = match c {
+        '´' => (ACUTE, ' '),
+        '`' => (GRAVE, ' '),
+        '^' => (CIRCUMFLEX, ' '),
+        _ => {
+            let lower = c.to_lowercase().next()?;
+            let (dead, base) = match lower {
+                'á' => (ACUTE, 'a'),
+                'é' => (ACUTE, 'e'),
+                'í' => (ACUTE, 'i'),
+                'ó' => (ACUTE, 'o'),
+                'ú' => (ACUTE, 'u'),
+                'ý' => (ACUTE, 'y'),
+                'à' => (GRAVE, 'a'),
+                'è' => (GRAVE, 'e'),
+                'ì' => (GRAVE, 'i'),
+                'ò' => (GRAVE, 'o'),
+                'ù' => (GRAVE, 'u'),
+                'â' => (CIRCUMFLEX, 'a'),
+                'ê' => (CIRCUMFLEX, 'e'),
+                'î' => (CIRCUMFLEX, 'i'),
+                'ô' => (CIRCUMFLEX, 'o'),
+                'û' => (CIRCUMFLEX, 'u'),
+                _ => return None,
+            };
+            let base = if c == lower {
+                base
+            } else {
+                base.to_ascii_uppercase()
+            };
+            (dead, base)
+        }
+    };
+    Some(composed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Action, ExecutionContext, MockExecutor, RecordedCall};
+    use std::sync::Arc;
+    use InputState::{Press, Release};
+
+    fn tap(key: Key) -> Vec<(Key, InputState)> {
+        vec![(key, Press), (key, Release)]
+    }
+
+    fn with(modifier: Key, key: Key) -> Vec<(Key, InputState)> {
+        vec![
+            (modifier, Press),
+            (key, Press),
+            (key, Release),
+            (modifier, Release),
+        ]
+    }
+
+    #[test]
+    fn us_qwerty_shifts_the_number_row_symbols() {
+        assert_eq!(UsQwerty.char_to_keys('q'), Some(tap(Key::Q)));
+        assert_eq!(
+            UsQwerty.char_to_keys('@'),
+            Some(with(Key::Shift, Key::Num2))
+        );
+        assert_eq!(UsQwerty.char_to_keys('/'), Some(tap(Key::Slash)));
+        assert_eq!(UsQwerty.char_to_keys('ä'), None);
+    }
+
+    #[test]
+    fn dvorak_types_letters_and_symbols_from_their_positions() {
+        assert_eq!(Dvorak.char_to_keys('s'), Some(tap(Key::Semicolon)));
+        assert_eq!(Dvorak.char_to_keys('E'), Some(with(Key::Shift, Key::D)));
+        assert_eq!(Dvorak.char_to_keys('"'), Some(with(Key::Shift, Key::Q)));
+        // The number row is the same as on QWERTY
+        assert_eq!(Dvorak.char_to_keys('@'), UsQwerty.char_to_keys('@'));
+    }
+
+    #[test]
+    fn german_qwertz_uses_altgr_and_dead_keys() {
+        assert_eq!(GermanQwertz.char_to_keys('z'), Some(tap(Key::Y)));
+        assert_eq!(
+            GermanQwertz.char_to_keys('@'),
+            Some(with(Key::AltGr, Key::Q))
+        );
+        assert_eq!(GermanQwertz.char_to_keys('ß'), Some(tap(Key::Minus)));
+        assert_eq!(
+            GermanQwertz.char_to_keys('é'),
+            Some([tap(Key::Equals), tap(Key::E)].concat())
+        );
+        assert_eq!(
+            GermanQwertz.char_to_keys('À'),
+            Some([with(Key::Shift, Key::Equals), with(Key::Shift, Key::A)].concat())
+        );
+        assert_eq!(GermanQwertz.char_to_keys('å'), None);
+    }
+
+    #[tokio::test]
+    async fn type_text_follows_the_context_layout() {
+        let executor = MockExecutor::new();
+        let ctx = ExecutionContext {
+            layout: Arc::new(GermanQwertz),
+            ..ExecutionContext::default()
+        };
+        Action::TypeText("z@".into())
+            .execute_with(&executor, &ctx)
+            .await
+            .unwrap();
+        let expected: Vec<RecordedCall> = [tap(Key::Y), with(Key::AltGr, Key::Q)]
+            .concat()
+            .into_iter()
+            .map(|(key, state)| RecordedCall::Key(key, state))
+            .collect();
+        assert_eq!(executor.calls(), expected);
+    }
+}
@@ -1,35 +1,419 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
+use rand::rngs::StdRng;
+use rand::{Rng, RngCore, SeedableRng};
+use serde::{Deserialize, Serialize};
 use std::future::Future;
 use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
+use tracing::Instrument;
+
+mod async_executor;
+mod builder;
+mod clipboard;
+#[cfg(all(target_os = "linux", feature = "wayland-ei"))]
+mod ei;
+mod fallback;
+mod layout;
+mod mock;
+mod rate_limit;
+mod replay;
+mod repr;
+mod timeline;
+#[cfg(target_os = "linux")]
+mod uinput;
+
+pub use async_executor::AsyncActionExecutor;
+pub use builder::ActionBuilder;
+pub use clipboard::{Clipboard, SystemClipboard};
+pub use fallback::FallbackExecutor;
+pub use layout::{Dvorak, GermanQwertz, KeyboardLayout, UsQwerty};
+pub use mock::{MockClipboard, MockExecutor, RecordedCall};
+pub use rate_limit::{Clock, RateLimiter, TokioClock};
+pub use replay::replay;
+pub use timeline::TimelineExecutor;
 
 // Re-export types from input-capture for convenience
-pub use input_capture::{Key, MouseButton};
+pub use input_capture::{Hotkey, InputEvent, Key, Modifier, MouseButton, ScanCode, Trigger};
 
 /// Key or button state
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum InputState {
     Press,
     Release,
 }
 
+/// Key or mouse button, for batched injection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputKind {
+    Key(Key),
+    Mouse(MouseButton),
+}
+
+/// Toggle key with a lock state (and usually an LED)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LockKey {
+    CapsLock,
+    NumLock,
+    ScrollLock,
+}
+
+/// Failure reported by an `ActionExecutor`
+///
+/// Converts into `anyhow::Error` with `?`, and callers holding one can
+/// `downcast_ref::<ExecutorError>()` to decide whether to retry, warn, or
+/// give up on the backend.
+#[derive(Debug, thiserror::Error)]
+pub enum ExecutorError {
+    /// The backend has no way to perform this operation
+    #[error("{0} not supported by this executor")]
+    Unsupported(&'static str),
+
+    /// The OS refused the injection, e.g. into an elevated window
+    #[error("permission denied: {0}")]
+    PermissionDenied(String),
+
+    /// The key has no code on this backend or keyboard layout
+    #[error("{0:?} has no mapping on this backend")]
+    UnmappedKey(Key),
+
+    /// Any other backend failure
+    #[error("{0}")]
+    Backend(String),
+}
+
+impl From<std::io::Error> for ExecutorError {
+    fn from(err: std::io::Error) -> Self {
+        match err.kind() {
+            std::io::ErrorKind::PermissionDenied => {
+                ExecutorError::PermissionDenied(err.to_string())
+            }
+            _ => ExecutorError::Backend(err.to_string()),
+        }
+    }
+}
+
+impl From<anyhow::Error> for ExecutorError {
+    fn from(err: anyhow::Error) -> Self {
+        match err.downcast::<ExecutorError>() {
+            Ok(err) => err,
+            Err(err) => ExecutorError::Backend(format!("{err:#}")),
+        }
+    }
+}
+
 /// Platform abstraction for simulating input
 pub trait ActionExecutor: Send + Sync {
     /// Simulate a key press or release
-    fn simulate_key(&self, key: Key, state: InputState) -> Result<()>;
+    fn simulate_key(&self, key: Key, state: InputState) -> Result<(), ExecutorError>;
 
     /// Simulate a mouse button press or release
-    fn simulate_mouse(&self, button: MouseButton, state: InputState) -> Result<()>;
+    fn simulate_mouse(&self, button: MouseButton, state: InputState) -> Result<(), ExecutorError>;
+
+    /// Simulate several key and button events back-to-back; backends that
+    /// can inject them in one call override this
+    fn simulate_batch(&self, events: &[(InputKind, InputState)]) -> Result<(), ExecutorError> {
+        for &(kind, state) in events {
+            match kind {
+                InputKind::Key(key) => ActionExecutor::simulate_key(self, key, state)?,
+                InputKind::Mouse(button) => ActionExecutor::simulate_mouse(self, button, state)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Simulate a raw hardware scan code (set-1 on Windows, evdev on Linux)
+    fn simulate_scancode(&self, _code: u16, _state: InputState) -> Result<(), ExecutorError> {
+        Err(ExecutorError::Unsupported("raw scan codes"))
+    }
+
+    /// Type a character by code point, bypassing the keyboard layout
+    fn send_unicode(&self, _c: char) -> Result<(), ExecutorError> {
+        Err(ExecutorError::Unsupported("unicode injection"))
+    }
 
     /// Move mouse cursor to absolute position
-    fn mouse_move_abs(&self, x: i32, y: i32) -> Result<()>;
+    fn mouse_move_abs(&self, x: i32, y: i32) -> Result<(), ExecutorError>;
 
     /// Move mouse cursor by relative offset
-    fn mouse_move_rel(&self, dx: i32, dy: i32) -> Result<()>;
+    fn mouse_move_rel(&self, dx: i32, dy: i32) -> Result<(), ExecutorError>;
+
+    /// Move the cursor to a position relative to a monitor's top-left
+    fn mouse_move_to_monitor(&self, monitor: usize, x: i32, y: i32) -> Result<(), ExecutorError> {
+        let monitors = ActionExecutor::monitors(self)?;
+        let target = monitors.get(monitor).ok_or_else(|| {
+            ExecutorError::Backend(format!(
+                "no monitor {monitor} ({} connected)",
+                monitors.len()
+            ))
+        })?;
+        let (x, y) = target.to_global(x, y);
+        ActionExecutor::mouse_move_abs(self, x, y)
+    }
+
+    /// Connected monitors, ordered left to right then top to bottom
+    fn monitors(&self) -> Result<Vec<Monitor>, ExecutorError> {
+        Err(ExecutorError::Unsupported("monitor enumeration"))
+    }
+
+    /// Scroll the wheel by whole detents (positive `dx` scrolls right,
+    /// positive `dy` up); a no-op on backends without a wheel
+    fn scroll(&self, _dx: i32, _dy: i32) -> Result<(), ExecutorError> {
+        Ok(())
+    }
+
+    /// Current cursor position in screen coordinates
+    fn current_position(&self) -> Result<(i32, i32), ExecutorError> {
+        Err(ExecutorError::Unsupported("cursor position query"))
+    }
+
+    /// Titles of all visible top-level windows
+    fn window_titles(&self) -> Result<Vec<String>, ExecutorError> {
+        Err(ExecutorError::Unsupported("window enumeration"))
+    }
+
+    /// Color of the screen pixel at a position
+    fn pixel_color(&self, _x: i32, _y: i32) -> Result<(u8, u8, u8), ExecutorError> {
+        Err(ExecutorError::Unsupported("screen reads"))
+    }
+
+    /// Keys the OS currently reports as held
+    fn held_keys(&self) -> Result<Vec<Key>, ExecutorError> {
+        Err(ExecutorError::Unsupported("key state query"))
+    }
+
+    /// Whether a lock key is currently toggled on
+    fn lock_state(&self, _lock: LockKey) -> Result<bool, ExecutorError> {
+        Err(ExecutorError::Unsupported("lock state query"))
+    }
+
+    /// Turn a lock key on or off
+    fn set_lock(&self, _lock: LockKey, _on: bool) -> Result<(), ExecutorError> {
+        Err(ExecutorError::Unsupported("setting lock state"))
+    }
+
+    /// Block or unblock physical keyboard and mouse input system-wide
+    fn block_input(&self, _blocked: bool) -> Result<(), ExecutorError> {
+        Err(ExecutorError::Unsupported("input blocking"))
+    }
 }
 
-/// High-level actions composed of executor primitives
+/// Progress curve for movement spread over time
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Easing {
+    Linear,
+    /// Slow start and end, fastest in the middle
+    EaseInOut,
+    /// CSS-style `cubic-bezier(x1, y1, x2, y2)` with fixed end points
+    /// (0,0) and (1,1); `x1` and `x2` must lie in 0..=1
+    CubicBezier {
+        x1: f64,
+        y1: f64,
+        x2: f64,
+        y2: f64,
+    },
+}
+
+impl Easing {
+    /// Map the elapsed fraction of the duration to the fraction of progress
+    pub fn apply(self, t: f64) -> f64 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseInOut if t < 0.5 => 2.0 * t * t,
+            Easing::EaseInOut => 1.0 - (2.0 - 2.0 * t).powi(2) / 2.0,
+            Easing::CubicBezier { x1, y1, x2, y2 } => {
+                // x(u) is monotonic for x1, x2 in 0..=1, so bisect for the
+                // curve parameter at time t
+                let bezier = |p1: f64, p2: f64, u: f64| {
+                    let v = 1.0 - u;
+                    3.0 * v * v * u * p1 + 3.0 * v * u * u * p2 + u * u * u
+                };
+                let (mut lo, mut hi) = (0.0, 1.0);
+                for _ in 0..32 {
+                    let mid = (lo + hi) / 2.0;
+                    if bezier(x1, x2, mid) < t {
+                        lo = mid;
+                    } else {
+                        hi = mid;
+                    }
+                }
+                bezier(y1, y2, (lo + hi) / 2.0)
+            }
+        }
+    }
+}
+
+/// A display's rectangle in global screen coordinates
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Monitor {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Monitor {
+    /// Global coordinates of a point relative to this monitor's top-left
+    pub fn to_global(&self, x: i32, y: i32) -> (i32, i32) {
+        (self.x + x, self.y + y)
+    }
+}
+
+/// Keys and mouse buttons believed to be held down
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HeldInput {
+    pub keys: Vec<Key>,
+    pub buttons: Vec<MouseButton>,
+}
+
+impl HeldInput {
+    pub fn is_key_held(&self, key: &Key) -> bool {
+        self.keys.contains(key)
+    }
+
+    pub fn is_button_held(&self, button: &MouseButton) -> bool {
+        self.buttons.contains(button)
+    }
+
+    /// Record a key press or release made by an action
+    pub fn set_key(&mut self, key: Key, state: InputState) {
+        self.keys.retain(|held| *held != key);
+        if state == InputState::Press {
+            self.keys.push(key);
+        }
+    }
+}
+
+/// State shared by an action and everything nested inside it
 #[derive(Debug, Clone)]
+pub struct ExecutionContext {
+    /// Hotkey whose binding started this execution
+    pub trigger: Option<Hotkey>,
+    /// Layout `TypeText` translates characters with
+    pub layout: Arc<dyn KeyboardLayout>,
+    /// Set while the trigger is held; `RepeatWhileHeld` loops until cleared
+    pub held: Option<Arc<AtomicBool>>,
+    /// Source of randomness; None draws from `thread_rng`
+    pub rng: Option<Arc<Mutex<StdRng>>>,
+    /// Held input as of the start of execution (from the engine's
+    /// `StateTracker`), updated as actions hold and release keys
+    pub input: Arc<Mutex<HeldInput>>,
+    /// Keys pressed by this execution and not yet released
+    pub pressed: Arc<Mutex<Vec<Key>>>,
+    /// Cancelled to stop the action at its next await point
+    pub cancel: CancellationToken,
+    /// Clipboard `PasteText` goes through
+    pub clipboard: Arc<dyn Clipboard>,
+    /// Captured input, for actions that wait on it (`WaitForKey`)
+    pub events: Option<broadcast::Sender<InputEvent>>,
+}
+
+impl Default for ExecutionContext {
+    fn default() -> Self {
+        Self {
+            trigger: None,
+            layout: Arc::new(UsQwerty),
+            held: None,
+            rng: None,
+            input: Arc::default(),
+            pressed: Arc::default(),
+            cancel: CancellationToken::new(),
+            clipboard: Arc::new(SystemClipboard),
+            events: None,
+        }
+    }
+}
+
+impl ExecutionContext {
+    pub fn triggered_by(hotkey: Hotkey) -> Self {
+        Self {
+            trigger: Some(hotkey),
+            ..Self::default()
+        }
+    }
+
+    /// Draw randomness from a generator seeded with `seed`, so runs repeat
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = Some(Arc::new(Mutex::new(StdRng::seed_from_u64(seed))));
+        self
+    }
+
+    /// Start from a snapshot of held keys and buttons
+    pub fn with_held_input(mut self, held: HeldInput) -> Self {
+        self.input = Arc::new(Mutex::new(held));
+        self
+    }
+
+    /// Stop the action when `cancel` is cancelled
+    pub fn with_cancel(mut self, cancel: CancellationToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    /// Paste through `clipboard` instead of the OS clipboard
+    pub fn with_clipboard(mut self, clipboard: Arc<dyn Clipboard>) -> Self {
+        self.clipboard = clipboard;
+        self
+    }
+
+    /// Let actions wait on events sent through `events`
+    pub fn with_events(mut self, events: broadcast::Sender<InputEvent>) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    /// Run `f` with this context's random number generator
+    pub fn with_rng<T>(&self, f: impl FnOnce(&mut dyn RngCore) -> T) -> T {
+        match &self.rng {
+            Some(rng) => f(&mut *rng.lock().unwrap()),
+            None => f(&mut rand::thread_rng()),
+        }
+    }
+
+    /// Note a key an action pressed or released
+    fn record_key(&self, key: Key, state: InputState) {
+        self.input.lock().unwrap().set_key(key, state);
+        let mut pressed = self.pressed.lock().unwrap();
+        pressed.retain(|&k| k != key);
+        if state == InputState::Press {
+            pressed.push(key);
+        }
+    }
+
+    /// Sleep for `duration`, returning false if cancelled first
+    async fn sleep(&self, duration: Duration) -> bool {
+        tokio::select! {
+            _ = tokio::time::sleep(duration) => true,
+            _ = self.cancel.cancelled() => false,
+        }
+    }
+}
+
+/// High-level actions composed of executor primitives
+///
+/// Serialized internally tagged with durations in milliseconds, e.g.
+/// `{ "type": "Delay", "duration": 50 }`; tuple variants name their field
+/// (`key`, `button`, `actions`, `text`, `action`, `choices`, `template`,
+/// `duration`).
+///
+/// ```
+/// # use action_executor::Action;
+/// # use std::time::Duration;
+/// #[derive(serde::Deserialize)]
+/// struct Binding {
+///     action: Action,
+/// }
+///
+/// let text = r#"action = { type = "Delay", duration = 50 }"#;
+/// let binding: Binding = toml::from_str(text).unwrap();
+/// assert!(matches!(binding.action, Action::Delay(d) if d == Duration::from_millis(50)));
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "repr::ActionRepr", into = "repr::ActionRepr")]
 pub enum Action {
     /// Press and release a key
     PressKey(Key),
@@ -43,13 +427,36 @@ pub enum Action {
     /// Release a held key
     ReleaseKey(Key),
 
+    /// Press a key if it is up, release it if it is held (sticky keys)
+    ToggleKey(Key),
+
+    /// Press `modifiers` in order, tap `key`, then release the modifiers in
+    /// reverse; the modifiers are released even if the tap fails
+    KeyCombo { modifiers: Vec<Modifier>, key: Key },
+
+    /// Press or release a raw scan code, bypassing `Key` entirely;
+    /// `Key::to_scancode` gives a key's code on this platform
+    RawScanCode { code: u16, state: InputState },
+
     /// Sequence of actions executed in order
     Sequence(Vec<Action>),
 
-    /// Repeat actions while condition is true
+    /// Run an action `count` times back-to-back
+    Repeat { inner: Box<Action>, count: u32 },
+
+    /// Run a list of actions in order, `count` times over (0 does nothing)
+    Loop { actions: Vec<Action>, count: u32 },
+
+    /// Repeat actions every `interval` while the trigger is held
+    ///
+    /// Only the event loop knows when the trigger is released, so this must
+    /// be started with `binding_engine::spawn_repeat`. `max_duration` caps
+    /// the loop in case the trigger's release event is missed; it also stops
+    /// on the next focus change.
     RepeatWhileHeld {
         actions: Vec<Action>,
         interval: Duration,
+        max_duration: Option<Duration>,
     },
 
     /// Delay execution
@@ -60,89 +467,1624 @@ pub enum Action {
 
     /// Type a text string
     TypeText(String),
+
+    /// Put text on the clipboard and paste it with Ctrl+V (Cmd+V on
+    /// macOS); `restore` puts the previous text back afterwards
+    PasteText { text: String, restore: bool },
+
+    /// Type a text string with a random pause before each character after
+    /// the first, within `jitter` of `mean`
+    TypeTextHumanized {
+        text: String,
+        mean: Duration,
+        jitter: Duration,
+    },
+
+    /// Poll the window list until a title contains the given text
+    /// (errors once `timeout` elapses)
+    WaitForWindow {
+        title_contains: String,
+        timeout: Duration,
+        poll_interval: Duration,
+    },
+
+    /// Wait until `key` is pressed, erroring if `timeout` passes first;
+    /// needs a context with an event stream (`ExecutionContext::with_events`)
+    WaitForKey { key: Key, timeout: Option<Duration> },
+
+    /// Move the cursor to an absolute screen position
+    MouseMoveAbs { x: i32, y: i32 },
+
+    /// Move the cursor by a relative offset
+    MouseMoveRel { dx: i32, dy: i32 },
+
+    /// Move the cursor through waypoints in order, spending
+    /// `duration_per_segment` on each; `smooth` interpolates between them
+    MousePath {
+        points: Vec<(i32, i32)>,
+        duration_per_segment: Duration,
+        smooth: bool,
+        /// Offset each waypoint by up to this many pixels on each axis,
+        /// drawn from the context RNG
+        jitter: u32,
+    },
+
+    /// Glide from the current cursor position to `to` over `duration`
+    MoveSmooth {
+        to: (i32, i32),
+        duration: Duration,
+        easing: Easing,
+    },
+
+    /// Press `button` at `from`, move to `to` in `steps` even increments
+    /// and release (0 steps jumps straight there)
+    MouseDrag {
+        button: MouseButton,
+        from: (i32, i32),
+        to: (i32, i32),
+        steps: u32,
+    },
+
+    /// Scroll the wheel by `amount` detents (positive scrolls up)
+    Scroll { amount: i32 },
+
+    /// Scroll the horizontal wheel by `amount` detents (positive scrolls right)
+    ScrollHorizontal { amount: i32 },
+
+    /// Scroll `total` detents vertically over `duration`, spread out along
+    /// an easing curve
+    SmoothScroll {
+        total: i32,
+        duration: Duration,
+        easing: Easing,
+    },
+
+    /// Poll a screen pixel until every channel is within `tolerance` of
+    /// `color` (errors once `timeout` elapses; needs `screen-capture`)
+    WaitForPixel {
+        x: i32,
+        y: i32,
+        color: (u8, u8, u8),
+        tolerance: u8,
+        timeout: Duration,
+        poll_interval: Duration,
+    },
+
+    /// Run an action, then move the cursor back to where it started
+    PreserveCursor(Box<Action>),
+
+    /// Run an action, failing (and releasing the keys it held) if it is
+    /// still running after `limit`
+    WithTimeout {
+        action: Box<Action>,
+        limit: Duration,
+    },
+
+    /// Run `then` if `key` is held when this starts, else `otherwise`
+    IfHeld {
+        key: Key,
+        then: Box<Action>,
+        otherwise: Option<Box<Action>>,
+    },
+
+    /// Turn a lock key on or off, leaving it alone if already there
+    SetLock { lock: LockKey, on: bool },
+
+    /// Run `then` if `lock` is on when this starts, else `otherwise`
+    IfLockOn {
+        lock: LockKey,
+        then: Box<Action>,
+        otherwise: Option<Box<Action>>,
+    },
+
+    /// Run one action picked at random, weighted by the paired number
+    WeightedChoice(Vec<(f64, Action)>),
+
+    /// Re-read real OS key state into the engine to clear phantom holds
+    ///
+    /// Handled by the event loop via `EventProcessor::resync`; executing it
+    /// directly does nothing.
+    ResyncState,
+
+    /// Log `EventProcessor::debug_snapshot` for a bug report
+    ///
+    /// Handled by the event processor when the binding fires; executing it
+    /// directly does nothing.
+    DumpState,
+
+    /// Release every key and mouse button believed held (a no-op when
+    /// nothing is), for recovering from stuck input
+    ReleaseAll,
+
+    /// Launch a program without waiting for it; a failed launch is
+    /// logged rather than failing the action
+    RunCommand { program: String, args: Vec<String> },
+
+    /// Type text with `{key}` replaced by the name of the triggering key,
+    /// so one binding can serve a whole row of keys
+    TypeTemplate(String),
+
+    /// Ignore physical keyboard and mouse input for a duration
+    ///
+    /// Input is unblocked when the duration ends, and also if execution
+    /// errors, panics or is cancelled. Still, a blocked system only responds
+    /// to Ctrl+Alt+Del (Windows), so keep durations short. On Windows this
+    /// needs an elevated process.
+    #[cfg(feature = "block-input")]
+    BlockInput(Duration),
 }
 
 impl Action {
+    /// Variant name, used as the `kind` field of execution spans
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Action::PressKey(_) => "PressKey",
+            Action::Click(_) => "Click",
+            Action::HoldKey(_) => "HoldKey",
+            Action::ReleaseKey(_) => "ReleaseKey",
+            Action::ToggleKey(_) => "ToggleKey",
+            Action::KeyCombo { .. } => "KeyCombo",
+            Action::RawScanCode { .. } => "RawScanCode",
+            Action::Sequence(_) => "Sequence",
+            Action::Repeat { .. } => "Repeat",
+            Action::Loop { .. } => "Loop",
+            Action::RepeatWhileHeld { .. } => "RepeatWhileHeld",
+            Action::Delay(_) => "Delay",
+            Action::RandomDelay { .. } => "RandomDelay",
+            Action::TypeText(_) => "TypeText",
+            Action::PasteText { .. } => "PasteText",
+            Action::TypeTextHumanized { .. } => "TypeTextHumanized",
+            Action::WaitForWindow { .. } => "WaitForWindow",
+            Action::WaitForKey { .. } => "WaitForKey",
+            Action::MouseMoveAbs { .. } => "MouseMoveAbs",
+            Action::MouseMoveRel { .. } => "MouseMoveRel",
+            Action::MousePath { .. } => "MousePath",
+            Action::MoveSmooth { .. } => "MoveSmooth",
+            Action::MouseDrag { .. } => "MouseDrag",
+            Action::Scroll { .. } => "Scroll",
+            Action::ScrollHorizontal { .. } => "ScrollHorizontal",
+            Action::SmoothScroll { .. } => "SmoothScroll",
+            Action::WaitForPixel { .. } => "WaitForPixel",
+            Action::PreserveCursor(_) => "PreserveCursor",
+            Action::WithTimeout { .. } => "WithTimeout",
+            Action::IfHeld { .. } => "IfHeld",
+            Action::SetLock { .. } => "SetLock",
+            Action::IfLockOn { .. } => "IfLockOn",
+            Action::WeightedChoice(_) => "WeightedChoice",
+            Action::ResyncState => "ResyncState",
+            Action::DumpState => "DumpState",
+            Action::ReleaseAll => "ReleaseAll",
+            Action::RunCommand { .. } => "RunCommand",
+            Action::TypeTemplate(_) => "TypeTemplate",
+            #[cfg(feature = "block-input")]
+            Action::BlockInput(_) => "BlockInput",
+        }
+    }
+
+    /// Span an execution runs in, naming the key or button acted on;
+    /// `elapsed_ms` is filled in when it finishes
+    fn span(&self) -> tracing::Span {
+        use tracing::field::{debug, Empty};
+        let span = tracing::debug_span!(
+            "action",
+            kind = self.kind(),
+            key = Empty,
+            button = Empty,
+            elapsed_ms = Empty,
+        );
+        match self {
+            Action::PressKey(key)
+            | Action::HoldKey(key)
+            | Action::ReleaseKey(key)
+            | Action::ToggleKey(key)
+            | Action::KeyCombo { key, .. }
+            | Action::WaitForKey { key, .. }
+            | Action::IfHeld { key, .. } => {
+                span.record("key", debug(key));
+            }
+            Action::Click(button) | Action::MouseDrag { button, .. } => {
+                span.record("button", debug(button));
+            }
+            _ => {}
+        }
+        span
+    }
+
+    /// Events for actions that are nothing but key and button events
+    fn input_events(&self) -> Option<Vec<(InputKind, InputState)>> {
+        let events = match self {
+            Action::PressKey(key) => vec![
+                (InputKind::Key(*key), InputState::Press),
+                (InputKind::Key(*key), InputState::Release),
+            ],
+            Action::Click(button) => vec![
+                (InputKind::Mouse(*button), InputState::Press),
+                (InputKind::Mouse(*button), InputState::Release),
+            ],
+            Action::HoldKey(key) => vec![(InputKind::Key(*key), InputState::Press)],
+            Action::ReleaseKey(key) => vec![(InputKind::Key(*key), InputState::Release)],
+            _ => return None,
+        };
+        Some(events)
+    }
+
+    /// Check constraints that should reject an action at config load
+    pub fn validate(&self) -> Result<()> {
+        match self {
+            Action::Sequence(actions)
+            | Action::Loop { actions, .. }
+            | Action::RepeatWhileHeld { actions, .. } => {
+                actions.iter().try_for_each(Action::validate)
+            }
+            Action::PreserveCursor(inner) | Action::WithTimeout { action: inner, .. } => {
+                inner.validate()
+            }
+            Action::IfHeld {
+                then, otherwise, ..
+            }
+            | Action::IfLockOn {
+                then, otherwise, ..
+            } => {
+                then.validate()?;
+                otherwise.as_deref().map_or(Ok(()), Action::validate)
+            }
+            #[cfg(not(feature = "screen-capture"))]
+            Action::WaitForPixel { .. } => {
+                anyhow::bail!("WaitForPixel needs the `screen-capture` feature")
+            }
+            Action::Repeat { inner, count } => {
+                if *count == 0 {
+                    anyhow::bail!("Repeat count must be greater than zero");
+                }
+                inner.validate()
+            }
+            Action::MoveSmooth {
+                easing: Easing::CubicBezier { x1, x2, .. },
+                ..
+            }
+            | Action::SmoothScroll {
+                easing: Easing::CubicBezier { x1, x2, .. },
+                ..
+            } => {
+                if !(0.0..=1.0).contains(x1) || !(0.0..=1.0).contains(x2) {
+                    anyhow::bail!("CubicBezier x1 and x2 must be between 0 and 1");
+                }
+                Ok(())
+            }
+            Action::RandomDelay { min, max } => {
+                if min > max {
+                    anyhow::bail!("RandomDelay min {min:?} is greater than max {max:?}");
+                }
+                Ok(())
+            }
+            Action::WeightedChoice(options) => {
+                if options.is_empty() {
+                    anyhow::bail!("WeightedChoice needs at least one option");
+                }
+                for (weight, action) in options {
+                    if !weight.is_finite() || *weight < 0.0 {
+                        anyhow::bail!("WeightedChoice weight {weight} must be non-negative");
+                    }
+                    action.validate()?;
+                }
+                let total: f64 = options.iter().map(|(weight, _)| weight).sum();
+                if total == 0.0 {
+                    anyhow::bail!("WeightedChoice weights are all zero");
+                }
+                if !total.is_finite() {
+                    anyhow::bail!("WeightedChoice weights sum to {total}");
+                }
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
     /// Execute this action using the provided executor
     pub fn execute<'a>(
         &'a self,
-        executor: &'a impl ActionExecutor,
+        executor: &'a impl AsyncActionExecutor,
     ) -> Pin<Box<dyn Future<Output = Result<()>> + 'a>> {
         Box::pin(async move {
+            self.execute_with(executor, &ExecutionContext::default())
+                .await
+        })
+    }
+
+    /// Execute this action synchronously on the current thread
+    ///
+    /// For embedders without an async runtime: a private single-threaded
+    /// runtime drives the action to completion, so delays block this thread
+    /// and the caller never sees a future. This is the async walk, not a
+    /// second one built on `std::thread::sleep`; timeouts, cancellation and
+    /// waiting on captured input only exist there. Fails when called from
+    /// inside a tokio runtime, which cannot be blocked on.
+    pub fn execute_blocking(&self, executor: &impl AsyncActionExecutor) -> Result<()> {
+        if tokio::runtime::Handle::try_current().is_ok() {
+            anyhow::bail!("execute_blocking called inside a tokio runtime; use execute");
+        }
+        tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()?
+            .block_on(self.execute(executor))
+    }
+
+    /// Execute this action with context from the binding that started it
+    pub fn execute_with<'a>(
+        &'a self,
+        executor: &'a impl AsyncActionExecutor,
+        ctx: &'a ExecutionContext,
+    ) -> Pin<Box<dyn Future<Output = Result<()>> + 'a>> {
+        let body = async move {
             match self {
                 Action::PressKey(key) => {
-                    executor.simulate_key(*key, InputState::Press)?;
-                    executor.simulate_key(*key, InputState::Release)?;
+                    let key = InputKind::Key(*key);
+                    executor
+                        .simulate_batch(&[(key, InputState::Press), (key, InputState::Release)])
+                        .await?;
                 }
                 Action::Click(button) => {
-                    executor.simulate_mouse(*button, InputState::Press)?;
-                    executor.simulate_mouse(*button, InputState::Release)?;
+                    let button = InputKind::Mouse(*button);
+                    executor
+                        .simulate_batch(&[
+                            (button, InputState::Press),
+                            (button, InputState::Release),
+                        ])
+                        .await?;
                 }
                 Action::HoldKey(key) => {
-                    executor.simulate_key(*key, InputState::Press)?;
+                    executor.simulate_key(*key, InputState::Press).await?;
+                    ctx.record_key(*key, InputState::Press);
                 }
                 Action::ReleaseKey(key) => {
-                    executor.simulate_key(*key, InputState::Release)?;
+                    executor.simulate_key(*key, InputState::Release).await?;
+                    ctx.record_key(*key, InputState::Release);
+                }
+                Action::ToggleKey(key) => {
+                    let state = if ctx.input.lock().unwrap().is_key_held(key) {
+                        InputState::Release
+                    } else {
+                        InputState::Press
+                    };
+                    executor.simulate_key(*key, state).await?;
+                    ctx.record_key(*key, state);
+                }
+                Action::KeyCombo { modifiers, key } => {
+                    let mut held = Vec::with_capacity(modifiers.len());
+                    let mut result = Ok(());
+                    for modifier in modifiers {
+                        let modifier = modifier.key();
+                        result = executor.simulate_key(modifier, InputState::Press).await;
+                        if result.is_err() {
+                            break;
+                        }
+                        ctx.record_key(modifier, InputState::Press);
+                        held.push(modifier);
+                    }
+                    if result.is_ok() {
+                        let key = InputKind::Key(*key);
+                        result = executor
+                            .simulate_batch(&[(key, InputState::Press), (key, InputState::Release)])
+                            .await;
+                    }
+                    for &modifier in held.iter().rev() {
+                        let released = executor.simulate_key(modifier, InputState::Release).await;
+                        ctx.record_key(modifier, InputState::Release);
+                        result = result.and(released);
+                    }
+                    result?;
+                }
+                Action::RawScanCode { code, state } => {
+                    executor.simulate_scancode(*code, *state).await?;
                 }
                 Action::Sequence(actions) => {
-                    for action in actions {
-                        action.execute(executor).await?;
+                    let mut step = 0;
+                    while step < actions.len() && !ctx.cancel.is_cancelled() {
+                        // Runs of plain key and button actions go out as one batch
+                        let run: Vec<_> = actions[step..]
+                            .iter()
+                            .map_while(Action::input_events)
+                            .collect();
+                        if run.len() > 1 {
+                            let events = run.concat();
+                            executor.simulate_batch(&events).await?;
+                            for (kind, state) in events {
+                                if let InputKind::Key(key) = kind {
+                                    ctx.record_key(key, state);
+                                }
+                            }
+                            step += run.len();
+                            continue;
+                        }
+                        actions[step]
+                            .execute_with(executor, ctx)
+                            .instrument(tracing::trace_span!("step", step))
+                            .await?;
+                        step += 1;
+                    }
+                }
+                Action::Repeat { inner, count } => {
+                    for _ in 0..*count {
+                        if ctx.cancel.is_cancelled() {
+                            break;
+                        }
+                        inner.execute_with(executor, ctx).await?;
+                    }
+                }
+                Action::Loop { actions, count } => {
+                    'passes: for iteration in 0..*count {
+                        for action in actions {
+                            if ctx.cancel.is_cancelled() {
+                                break 'passes;
+                            }
+                            action
+                                .execute_with(executor, ctx)
+                                .instrument(tracing::trace_span!("iteration", iteration))
+                                .await?;
+                        }
                     }
                 }
                 Action::Delay(duration) => {
-                    tokio::time::sleep(*duration).await;
+                    ctx.sleep(*duration).await;
                 }
                 Action::RandomDelay { min, max } => {
-                    use rand::Rng;
-                    let delay = rand::thread_rng().gen_range(min.as_millis()..=max.as_millis());
-                    tokio::time::sleep(Duration::from_millis(delay as u64)).await;
+                    // gen_range panics on an empty range, so check even if
+                    // validation was skipped
+                    if min > max {
+                        anyhow::bail!("RandomDelay min {min:?} is greater than max {max:?}");
+                    }
+                    let delay =
+                        ctx.with_rng(|rng| rng.gen_range(min.as_millis()..=max.as_millis()));
+                    ctx.sleep(Duration::from_millis(delay as u64)).await;
+                }
+                Action::RepeatWhileHeld {
+                    actions,
+                    interval,
+                    max_duration,
+                } => {
+                    let Some(held) = &ctx.held else {
+                        anyhow::bail!("RepeatWhileHeld must be started by the event loop");
+                    };
+                    let started = tokio::time::Instant::now();
+                    // Checked once per cycle, so a release stops the loop
+                    // within one interval
+                    while held.load(Ordering::Acquire) && !ctx.cancel.is_cancelled() {
+                        if max_duration.is_some_and(|max| started.elapsed() >= max) {
+                            tracing::warn!("RepeatWhileHeld stopped after {max_duration:?}");
+                            break;
+                        }
+                        for action in actions {
+                            action.execute_with(executor, ctx).await?;
+                        }
+                        ctx.sleep(*interval).await;
+                    }
+                }
+                Action::TypeText(text) => {
+                    let caps_lock = caps_lock_on(executor).await;
+                    for c in text.chars() {
+                        type_char(executor, ctx, c, caps_lock).await?;
+                    }
+                }
+                Action::TypeTextHumanized { text, mean, jitter } => {
+                    let caps_lock = caps_lock_on(executor).await;
+                    for (i, c) in text.chars().enumerate() {
+                        if i > 0 {
+                            // Sum of three uniform draws: bell-shaped, but
+                            // never further than `jitter` from `mean`
+                            let offset = ctx.with_rng(|rng| {
+                                (0..3).map(|_| rng.gen_range(-1.0..=1.0)).sum::<f64>() / 3.0
+                            });
+                            let delay = mean.as_secs_f64() + jitter.as_secs_f64() * offset;
+                            if !ctx.sleep(Duration::from_secs_f64(delay.max(0.0))).await {
+                                break;
+                            }
+                        }
+                        type_char(executor, ctx, c, caps_lock).await?;
+                    }
+                }
+                Action::WaitForWindow {
+                    title_contains,
+                    timeout,
+                    poll_interval,
+                } => {
+                    // Cancelling the context (or dropping the future) ends
+                    // the wait between polls
+                    let deadline = tokio::time::Instant::now() + *timeout;
+                    loop {
+                        let titles = executor.window_titles().await?;
+                        if titles.iter().any(|t| t.contains(title_contains.as_str())) {
+                            break;
+                        }
+                        if tokio::time::Instant::now() >= deadline {
+                            anyhow::bail!(
+                                "timed out after {:?} waiting for window {:?}",
+                                timeout,
+                                title_contains
+                            );
+                        }
+                        if !ctx.sleep(*poll_interval).await {
+                            break;
+                        }
+                    }
+                }
+                Action::WaitForKey { key, timeout } => {
+                    // Subscribed before waiting, so a press arriving while
+                    // the timer starts is not missed
+                    let mut events = ctx
+                        .events
+                        .as_ref()
+                        .map(broadcast::Sender::subscribe)
+                        .context("WaitForKey needs the captured event stream")?;
+                    let pressed = async {
+                        loop {
+                            match events.recv().await {
+                                Ok(InputEvent::KeyPress(pressed)) if pressed == *key => {
+                                    return Ok(());
+                                }
+                                Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => {}
+                                Err(broadcast::error::RecvError::Closed) => {
+                                    anyhow::bail!("event stream closed waiting for {key:?}")
+                                }
+                            }
+                        }
+                    };
+                    let wait = async {
+                        match timeout {
+                            Some(limit) => tokio::time::timeout(*limit, pressed)
+                                .await
+                                .unwrap_or_else(|_| {
+                                    Err(anyhow::anyhow!(
+                                        "timed out after {limit:?} waiting for {key:?}"
+                                    ))
+                                }),
+                            None => pressed.await,
+                        }
+                    };
+                    tokio::select! {
+                        result = wait => result?,
+                        _ = ctx.cancel.cancelled() => {}
+                    }
+                }
+                Action::MouseMoveAbs { x, y } => {
+                    executor.mouse_move_abs(*x, *y).await?;
+                }
+                Action::MouseMoveRel { dx, dy } => {
+                    executor.mouse_move_rel(*dx, *dy).await?;
+                }
+                Action::MousePath {
+                    points,
+                    duration_per_segment,
+                    smooth,
+                    jitter,
+                } => {
+                    let mut from = if *smooth {
+                        executor.current_position().await?
+                    } else {
+                        (0, 0)
+                    };
+                    let jitter = i32::try_from(*jitter).unwrap_or(i32::MAX);
+                    for (index, &(x, y)) in points.iter().enumerate() {
+                        let point = ctx.with_rng(|rng| {
+                            (
+                                x.saturating_add(rng.gen_range(-jitter..=jitter)),
+                                y.saturating_add(rng.gen_range(-jitter..=jitter)),
+                            )
+                        });
+                        if *smooth {
+                            glide(executor, ctx, from, point, *duration_per_segment).await?;
+                        } else {
+                            executor.mouse_move_abs(point.0, point.1).await?;
+                            // Nothing to wait for after the last point
+                            if index + 1 < points.len() {
+                                ctx.sleep(*duration_per_segment).await;
+                            }
+                        }
+                        if ctx.cancel.is_cancelled() {
+                            break;
+                        }
+                        from = point;
+                    }
+                }
+                Action::MoveSmooth {
+                    to,
+                    duration,
+                    easing,
+                } => {
+                    let from = executor.current_position().await?;
+                    let steps = (duration.as_millis() / MOVE_STEP.as_millis()).max(1) as u32;
+                    let lerp = |a: i32, b: i32, progress: f64| {
+                        a + (f64::from(b - a) * progress).round() as i32
+                    };
+                    for step in 1..=steps {
+                        let progress = easing.apply(f64::from(step) / f64::from(steps));
+                        executor
+                            .mouse_move_abs(
+                                lerp(from.0, to.0, progress),
+                                lerp(from.1, to.1, progress),
+                            )
+                            .await?;
+                        if step < steps && !ctx.sleep(*duration / steps).await {
+                            break;
+                        }
+                    }
+                }
+                Action::MouseDrag {
+                    button,
+                    from,
+                    to,
+                    steps,
+                } => {
+                    executor.mouse_move_abs(from.0, from.1).await?;
+                    executor.simulate_mouse(*button, InputState::Press).await?;
+                    let steps = i64::from((*steps).max(1));
+                    let lerp =
+                        |a: i32, b: i32, step: i64| a + ((b - a) as i64 * step / steps) as i32;
+                    let mut moved = Ok(());
+                    for step in 1..=steps {
+                        moved = executor
+                            .mouse_move_abs(lerp(from.0, to.0, step), lerp(from.1, to.1, step))
+                            .await;
+                        if moved.is_err() {
+                            break;
+                        }
+                    }
+                    // Release even if a move failed, or the button stays stuck
+                    executor
+                        .simulate_mouse(*button, InputState::Release)
+                        .await?;
+                    moved?;
+                }
+                Action::Scroll { amount } => {
+                    executor.scroll(0, *amount).await?;
+                }
+                Action::ScrollHorizontal { amount } => {
+                    executor.scroll(*amount, 0).await?;
+                }
+                Action::SmoothScroll {
+                    total,
+                    duration,
+                    easing,
+                } => {
+                    let steps = (duration.as_millis() / MOVE_STEP.as_millis()).max(1) as u32;
+                    let mut emitted = 0;
+                    for step in 1..=steps {
+                        let progress = easing.apply(f64::from(step) / f64::from(steps));
+                        let target = (f64::from(*total) * progress).round() as i32;
+                        if target != emitted {
+                            executor.scroll(0, target - emitted).await?;
+                            emitted = target;
+                        }
+                        if step < steps && !ctx.sleep(*duration / steps).await {
+                            break;
+                        }
+                    }
+                }
+                #[cfg(feature = "screen-capture")]
+                Action::WaitForPixel {
+                    x,
+                    y,
+                    color,
+                    tolerance,
+                    timeout,
+                    poll_interval,
+                } => {
+                    let close = |a: u8, b: u8| a.abs_diff(b) <= *tolerance;
+                    let deadline = tokio::time::Instant::now() + *timeout;
+                    loop {
+                        let (r, g, b) = executor.pixel_color(*x, *y).await?;
+                        if close(r, color.0) && close(g, color.1) && close(b, color.2) {
+                            break;
+                        }
+                        if tokio::time::Instant::now() >= deadline {
+                            anyhow::bail!(
+                                "timed out after {:?} waiting for pixel ({}, {}) to be {:?}",
+                                timeout,
+                                x,
+                                y,
+                                color
+                            );
+                        }
+                        if !ctx.sleep(*poll_interval).await {
+                            break;
+                        }
+                    }
+                }
+                #[cfg(not(feature = "screen-capture"))]
+                Action::WaitForPixel { .. } => {
+                    anyhow::bail!("WaitForPixel needs the `screen-capture` feature");
+                }
+                Action::PreserveCursor(inner) => {
+                    let (x, y) = executor.current_position().await?;
+                    let result = inner.execute_with(executor, ctx).await;
+                    // Restore even if the inner action failed
+                    let restored = executor.mouse_move_abs(x, y).await;
+                    result?;
+                    restored?;
                 }
-                Action::RepeatWhileHeld { .. } => {
-                    // This requires state tracking from binding-engine
-                    todo!("RepeatWhileHeld requires integration with event loop")
+                Action::WithTimeout { action, limit } => {
+                    let before = ctx.pressed.lock().unwrap().clone();
+                    let run = action.execute_with(executor, ctx);
+                    if let Ok(result) = tokio::time::timeout(*limit, run).await {
+                        result?;
+                    } else {
+                        // Keys held before the inner action started stay down
+                        if let Err(err) = release_pressed(executor, ctx, &before).await {
+                            tracing::warn!("failed to release keys after timeout: {err:#}");
+                        }
+                        anyhow::bail!("{} timed out after {limit:?}", action.kind());
+                    }
+                }
+                Action::WeightedChoice(options) => {
+                    // Weights are normalized here, so they need not sum to 1
+                    let total: f64 = options.iter().map(|(weight, _)| weight).sum();
+                    // gen_range panics on an infinite or NaN total
+                    let usable = total.is_finite() && total > 0.0;
+                    if options.is_empty() || !usable {
+                        anyhow::bail!("WeightedChoice needs a positive, finite total weight");
+                    }
+                    let mut pick = ctx.with_rng(|rng| rng.gen_range(0.0..total));
+                    let mut chosen = &options[options.len() - 1].1;
+                    for (weight, action) in options {
+                        if pick < *weight {
+                            chosen = action;
+                            break;
+                        }
+                        pick -= weight;
+                    }
+                    chosen.execute_with(executor, ctx).await?;
+                }
+                Action::IfHeld {
+                    key,
+                    then,
+                    otherwise,
+                } => {
+                    // Decided once up front; the branch may change key state
+                    let held = ctx.input.lock().unwrap().is_key_held(key);
+                    let branch = if held { Some(then) } else { otherwise.as_ref() };
+                    if let Some(action) = branch {
+                        action.execute_with(executor, ctx).await?;
+                    }
+                }
+                Action::SetLock { lock, on } => {
+                    if executor.lock_state(*lock).await? != *on {
+                        executor.set_lock(*lock, *on).await?;
+                    }
+                }
+                Action::IfLockOn {
+                    lock,
+                    then,
+                    otherwise,
+                } => {
+                    let branch = if executor.lock_state(*lock).await? {
+                        Some(then)
+                    } else {
+                        otherwise.as_ref()
+                    };
+                    if let Some(action) = branch {
+                        action.execute_with(executor, ctx).await?;
+                    }
+                }
+                Action::ResyncState => {
+                    tracing::debug!("ResyncState is applied by the event loop");
+                }
+                Action::DumpState => {
+                    tracing::debug!("DumpState is applied by the event processor");
+                }
+                Action::ReleaseAll => {
+                    let held = std::mem::take(&mut *ctx.input.lock().unwrap());
+                    ctx.pressed.lock().unwrap().clear();
+                    // Attempt every release even if one fails
+                    let mut result = Ok(());
+                    for key in held.keys.iter().rev() {
+                        result = result.and(executor.simulate_key(*key, InputState::Release).await);
+                    }
+                    for button in &held.buttons {
+                        result =
+                            result.and(executor.simulate_mouse(*button, InputState::Release).await);
+                    }
+                    result?;
+                }
+                Action::PasteText { text, restore } => {
+                    let previous = if *restore {
+                        ctx.clipboard.get_text()?
+                    } else {
+                        None
+                    };
+                    ctx.clipboard.set_text(text)?;
+                    // Shortcuts follow the layout, so find where it puts V
+                    let v = ctx
+                        .layout
+                        .char_to_keys('v')
+                        .and_then(|keys| keys.first().map(|&(key, _)| key))
+                        .unwrap_or(Key::V);
+                    let (modifier, v) = (InputKind::Key(PASTE_MODIFIER), InputKind::Key(v));
+                    executor
+                        .simulate_batch(&[
+                            (modifier, InputState::Press),
+                            (v, InputState::Press),
+                            (v, InputState::Release),
+                            (modifier, InputState::Release),
+                        ])
+                        .await?;
+                    if let Some(previous) = previous {
+                        // The target reads the clipboard after the keystroke
+                        // arrives, so give it a moment before swapping back;
+                        // not cut short by cancellation, so the user's text
+                        // always comes back
+                        tokio::time::sleep(PASTE_SETTLE).await;
+                        ctx.clipboard.set_text(&previous)?;
+                    }
+                }
+                Action::RunCommand { program, args } => {
+                    let mut command = tokio::process::Command::new(program);
+                    // Null stdio so the child holds none of the daemon's
+                    // handles open
+                    command
+                        .args(args)
+                        .stdin(std::process::Stdio::null())
+                        .stdout(std::process::Stdio::null())
+                        .stderr(std::process::Stdio::null());
+                    // Own process group, so a Ctrl+C to the daemon spares it
+                    #[cfg(unix)]
+                    command.process_group(0);
+                    // Dropping the child neither kills nor waits on it;
+                    // tokio reaps it once it exits
+                    match command.spawn() {
+                        Ok(child) => tracing::debug!(%program, pid = child.id(), "spawned"),
+                        Err(err) => tracing::error!("failed to run {program:?}: {err}"),
+                    }
+                }
+                Action::TypeTemplate(template) => {
+                    let key = match ctx.trigger.as_ref().map(|hotkey| &hotkey.trigger) {
+                        Some(Trigger::Key(key)) => *key,
+                        other => anyhow::bail!("TypeTemplate needs a key trigger, got {:?}", other),
+                    };
+                    let text = template.replace("{key}", key.name());
+                    Action::TypeText(text).execute_with(executor, ctx).await?;
                 }
-                Action::TypeText(_text) => {
-                    // TODO: Map characters to key sequences
-                    todo!("TypeText requires character-to-key mapping")
+                #[cfg(feature = "block-input")]
+                Action::BlockInput(duration) => {
+                    executor.block_input(true)?;
+                    let _unblock = UnblockOnDrop(executor);
+                    ctx.sleep(*duration).await;
                 }
             }
             Ok(())
+        };
+        let future = async move {
+            let started = std::time::Instant::now();
+            let result = body.await;
+            if ctx.cancel.is_cancelled() {
+                release_pressed(executor, ctx, &[]).await?;
+            }
+            let elapsed_ms = started.elapsed().as_millis() as u64;
+            tracing::Span::current().record("elapsed_ms", elapsed_ms);
+            tracing::trace!(ok = result.is_ok(), "action finished");
+            result
+        };
+        // Made on first poll, so it nests under the `step` or `iteration`
+        // span the caller wraps this future in
+        Box::pin(async move {
+            let span = self.span();
+            future.instrument(span).await
         })
     }
 }
 
-// Platform-specific implementations
-#[cfg(windows)]
-pub mod platform {
-    pub use super::windows_impl::WindowsExecutor as PlatformExecutor;
+/// Release the keys an execution still holds, except those in `keep`
+async fn release_pressed(
+    executor: &impl AsyncActionExecutor,
+    ctx: &ExecutionContext,
+    keep: &[Key],
+) -> Result<(), ExecutorError> {
+    let pressed = {
+        let mut pressed = ctx.pressed.lock().unwrap();
+        let (kept, released): (Vec<Key>, Vec<Key>) =
+            pressed.iter().partition(|key| keep.contains(key));
+        *pressed = kept;
+        released
+    };
+    let mut result = Ok(());
+    for &key in pressed.iter().rev() {
+        let released = executor.simulate_key(key, InputState::Release).await;
+        ctx.input.lock().unwrap().set_key(key, InputState::Release);
+        result = result.and(released);
+    }
+    result
 }
 
-#[cfg(target_os = "linux")]
-pub mod platform {
-    pub use super::linux_impl::X11Executor as PlatformExecutor;
-}
+/// Modifier held for the paste shortcut
+#[cfg(target_os = "macos")]
+const PASTE_MODIFIER: Key = Key::Meta;
+#[cfg(not(target_os = "macos"))]
+const PASTE_MODIFIER: Key = Key::Ctrl;
 
-#[cfg(windows)]
+/// How long `PasteText` waits before restoring the previous clipboard
+const PASTE_SETTLE: Duration = Duration::from_millis(150);
+
+/// Step interval for interpolated cursor movement (~60Hz)
+const MOVE_STEP: Duration = Duration::from_millis(16);
+
+/// Move the cursor in a straight line over `duration`, stopping early if
+/// the context is cancelled
+async fn glide(
+    executor: &impl AsyncActionExecutor,
+    ctx: &ExecutionContext,
+    from: (i32, i32),
+    to: (i32, i32),
+    duration: Duration,
+) -> Result<()> {
+    let steps = (duration.as_millis() / MOVE_STEP.as_millis()).max(1) as i32;
+    for step in 1..=steps {
+        let x = from.0 + (to.0 - from.0) * step / steps;
+        let y = from.1 + (to.1 - from.1) * step / steps;
+        executor.mouse_move_abs(x, y).await?;
+        if step < steps && !ctx.sleep(duration / steps as u32).await {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Type one character through the context's layout
+///
+/// With `caps_lock` on, letters are typed as their opposite case so the
+/// lock flips them back.
+async fn type_char(
+    executor: &impl AsyncActionExecutor,
+    ctx: &ExecutionContext,
+    c: char,
+    caps_lock: bool,
+) -> Result<()> {
+    let flipped = caps_lock
+        .then(|| swap_case(c))
+        .flatten()
+        .and_then(|swapped| ctx.layout.char_to_keys(swapped));
+    match flipped.or_else(|| ctx.layout.char_to_keys(c)) {
+        Some(keys) => Ok(type_keys(executor, &keys).await?),
+        // Emoji and anything else off the layout go in by code point
+        None => executor
+            .send_unicode(c)
+            .await
+            .with_context(|| format!("cannot type {:?} with the {:?} layout", c, ctx.layout)),
+    }
+}
+
+/// `c` in the opposite case, if that is a different single character
+fn swap_case(c: char) -> Option<char> {
+    let swapped: Vec<char> = if c.is_uppercase() {
+        c.to_lowercase().collect()
+    } else {
+        c.to_uppercase().collect()
+    };
+    match swapped[..] {
+        [swapped] if swapped != c => Some(swapped),
+        _ => None,
+    }
+}
+
+/// Whether CapsLock is on, treating an unsupported query as off
+async fn caps_lock_on(executor: &impl AsyncActionExecutor) -> bool {
+    executor
+        .lock_state(LockKey::CapsLock)
+        .await
+        .inspect_err(|err| tracing::debug!("assuming CapsLock is off: {err}"))
+        .unwrap_or(false)
+}
+
+/// Send one character's key events, releasing anything left held if
+/// one fails
+async fn type_keys(
+    executor: &impl AsyncActionExecutor,
+    keys: &[(Key, InputState)],
+) -> Result<(), ExecutorError> {
+    let mut held = Vec::new();
+    for &(key, state) in keys {
+        if let Err(err) = executor.simulate_key(key, state).await {
+            for &key in held.iter().rev() {
+                let _ = executor.simulate_key(key, InputState::Release).await;
+            }
+            return Err(err);
+        }
+        match state {
+            InputState::Press => held.push(key),
+            InputState::Release => held.retain(|&k| k != key),
+        }
+    }
+    Ok(())
+}
+
+/// Unblocks input when dropped, covering errors, panics and cancellation
+#[cfg(feature = "block-input")]
+struct UnblockOnDrop<'a, E: AsyncActionExecutor>(&'a E);
+
+#[cfg(feature = "block-input")]
+impl<E: AsyncActionExecutor> Drop for UnblockOnDrop<'_, E> {
+    fn drop(&mut self) {
+        if let Err(err) = self.0.block_input(false) {
+            tracing::error!("failed to unblock input: {err:#}");
+        }
+    }
+}
+
+// Platform-specific implementations
+#[cfg(windows)]
+pub mod platform {
+    pub use super::windows_impl::WindowsExecutor as PlatformExecutor;
+}
+
+#[cfg(target_os = "linux")]
+pub mod platform {
+    #[cfg(feature = "wayland-ei")]
+    pub use super::ei::EiExecutor;
+    pub use super::linux_impl::X11Executor as PlatformExecutor;
+    pub use super::uinput::UinputExecutor;
+}
+
+#[cfg(target_os = "macos")]
+pub mod platform {
+    pub use super::macos_impl::MacExecutor as PlatformExecutor;
+}
+
+#[cfg(windows)]
 mod windows_impl {
     use super::*;
+    use windows::core::BOOL;
+    use windows::Win32::Foundation::{E_ACCESSDENIED, HWND, LPARAM, POINT, RECT};
+    use windows::Win32::Graphics::Gdi::{
+        EnumDisplayMonitors, GetMonitorInfoW, MonitorFromPoint, HDC, HMONITOR, MONITORINFO,
+        MONITOR_DEFAULTTONEAREST,
+    };
+    #[cfg(feature = "screen-capture")]
+    use windows::Win32::Graphics::Gdi::{GetDC, GetPixel, ReleaseDC};
+    use windows::Win32::UI::HiDpi::{
+        GetDpiForMonitor, SetProcessDpiAwarenessContext,
+        DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2, MDT_EFFECTIVE_DPI,
+    };
+    use windows::Win32::UI::Input::KeyboardAndMouse::{
+        BlockInput, GetAsyncKeyState, GetKeyState, MapVirtualKeyW, SendInput, INPUT, INPUT_0,
+        INPUT_KEYBOARD, INPUT_MOUSE, KEYBDINPUT, KEYBD_EVENT_FLAGS, KEYEVENTF_EXTENDEDKEY,
+        KEYEVENTF_KEYUP, KEYEVENTF_SCANCODE, KEYEVENTF_UNICODE, MAPVK_VSC_TO_VK_EX,
+        MOUSEEVENTF_ABSOLUTE, MOUSEEVENTF_HWHEEL, MOUSEEVENTF_LEFTDOWN, MOUSEEVENTF_LEFTUP,
+        MOUSEEVENTF_MIDDLEDOWN, MOUSEEVENTF_MIDDLEUP, MOUSEEVENTF_MOVE, MOUSEEVENTF_RIGHTDOWN,
+        MOUSEEVENTF_RIGHTUP, MOUSEEVENTF_VIRTUALDESK, MOUSEEVENTF_WHEEL, MOUSEEVENTF_XDOWN,
+        MOUSEEVENTF_XUP, MOUSEINPUT, MOUSE_EVENT_FLAGS, VIRTUAL_KEY, VK_CAPITAL, VK_NUMLOCK,
+        VK_PAUSE, VK_SCROLL,
+    };
+    use windows::Win32::UI::WindowsAndMessaging::{
+        EnumWindows, GetCursorPos, GetSystemMetrics, GetWindowTextW, IsWindowVisible,
+        SM_CXVIRTUALSCREEN, SM_CYVIRTUALSCREEN, SM_XVIRTUALSCREEN, SM_YVIRTUALSCREEN, WHEEL_DELTA,
+    };
 
+    // `mouseData` values for MOUSEEVENTF_XDOWN/XUP
+    const XBUTTON1: u32 = 0x0001;
+    const XBUTTON2: u32 = 0x0002;
+
+    /// Coordinates are logical (96 DPI) units, scaled per monitor
     pub struct WindowsExecutor;
 
+    /// DPI at which logical and physical pixels coincide (100% scaling)
+    const BASE_DPI: u32 = 96;
+
+    /// Hand events to `SendInput`, failing if any were not inserted
+    fn send(inputs: &[INPUT]) -> Result<(), ExecutorError> {
+        let sent = unsafe { SendInput(inputs, std::mem::size_of::<INPUT>() as i32) };
+        if (sent as usize) < inputs.len() {
+            let err = std::io::Error::last_os_error();
+            let message = format!(
+                "SendInput inserted {sent} of {} events: {err}",
+                inputs.len()
+            );
+            // ERROR_ACCESS_DENIED is UIPI: the foreground window runs at a
+            // higher integrity level
+            return Err(match err.raw_os_error() {
+                Some(5) => ExecutorError::PermissionDenied(message),
+                _ => ExecutorError::Backend(message),
+            });
+        }
+        Ok(())
+    }
+
+    fn keyboard(scan: u16, flags: KEYBD_EVENT_FLAGS) -> INPUT {
+        INPUT {
+            r#type: INPUT_KEYBOARD,
+            Anonymous: INPUT_0 {
+                ki: KEYBDINPUT {
+                    wVk: VIRTUAL_KEY(0),
+                    wScan: scan,
+                    dwFlags: flags,
+                    time: 0,
+                    dwExtraInfo: input_capture::INJECTED_EXTRA_INFO,
+                },
+            },
+        }
+    }
+
+    fn virtual_key(vk: VIRTUAL_KEY, flags: KEYBD_EVENT_FLAGS) -> INPUT {
+        INPUT {
+            r#type: INPUT_KEYBOARD,
+            Anonymous: INPUT_0 {
+                ki: KEYBDINPUT {
+                    wVk: vk,
+                    wScan: 0,
+                    dwFlags: flags,
+                    time: 0,
+                    dwExtraInfo: input_capture::INJECTED_EXTRA_INFO,
+                },
+            },
+        }
+    }
+
+    fn mouse(dx: i32, dy: i32, data: u32, flags: MOUSE_EVENT_FLAGS) -> INPUT {
+        INPUT {
+            r#type: INPUT_MOUSE,
+            Anonymous: INPUT_0 {
+                mi: MOUSEINPUT {
+                    dx,
+                    dy,
+                    mouseData: data,
+                    dwFlags: flags,
+                    time: 0,
+                    dwExtraInfo: input_capture::INJECTED_EXTRA_INFO,
+                },
+            },
+        }
+    }
+
+    /// Set-1 scan code for a key, and whether it needs the E0 prefix
+    ///
+    /// Scan codes rather than virtual keys, since `Key` names positions and
+    /// virtual keys follow the active layout. Pause has none and goes
+    /// through its virtual key instead.
+    fn scan_code(key: Key) -> (u16, bool) {
+        let ScanCode(code) = key.set1_scan_code().unwrap_or(ScanCode(0));
+        (code & 0xFF, code >> 8 == 0xE0)
+    }
+
+    fn key_input(key: Key, state: InputState) -> INPUT {
+        if key == Key::Pause {
+            // Pause sends an E1-prefixed sequence no single scan code covers
+            let flags = match state {
+                InputState::Press => KEYBD_EVENT_FLAGS(0),
+                InputState::Release => KEYEVENTF_KEYUP,
+            };
+            return virtual_key(VK_PAUSE, flags);
+        }
+        let (scan, extended) = scan_code(key);
+        keyboard(scan, key_flags(state, extended))
+    }
+
+    /// One INPUT per event, in order
+    fn batch_inputs(events: &[(InputKind, InputState)]) -> Vec<INPUT> {
+        events
+            .iter()
+            .map(|&(kind, state)| match kind {
+                InputKind::Key(key) => key_input(key, state),
+                InputKind::Mouse(button) => mouse_input(button, state),
+            })
+            .collect()
+    }
+
+    fn mouse_input(button: MouseButton, state: InputState) -> INPUT {
+        let press = state == InputState::Press;
+        let (flags, data) = match button {
+            MouseButton::Left if press => (MOUSEEVENTF_LEFTDOWN, 0),
+            MouseButton::Left => (MOUSEEVENTF_LEFTUP, 0),
+            MouseButton::Right if press => (MOUSEEVENTF_RIGHTDOWN, 0),
+            MouseButton::Right => (MOUSEEVENTF_RIGHTUP, 0),
+            MouseButton::Middle if press => (MOUSEEVENTF_MIDDLEDOWN, 0),
+            MouseButton::Middle => (MOUSEEVENTF_MIDDLEUP, 0),
+            MouseButton::Button4 if press => (MOUSEEVENTF_XDOWN, XBUTTON1),
+            MouseButton::Button4 => (MOUSEEVENTF_XUP, XBUTTON1),
+            MouseButton::Button5 if press => (MOUSEEVENTF_XDOWN, XBUTTON2),
+            MouseButton::Button5 => (MOUSEEVENTF_XUP, XBUTTON2),
+        };
+        mouse(0, 0, data, flags)
+    }
+
+    fn key_flags(state: InputState, extended: bool) -> KEYBD_EVENT_FLAGS {
+        let mut flags = KEYEVENTF_SCANCODE;
+        if extended {
+            flags |= KEYEVENTF_EXTENDEDKEY;
+        }
+        if state == InputState::Release {
+            flags |= KEYEVENTF_KEYUP;
+        }
+        flags
+    }
+
+    fn lock_vk(lock: LockKey) -> VIRTUAL_KEY {
+        match lock {
+            LockKey::CapsLock => VK_CAPITAL,
+            LockKey::NumLock => VK_NUMLOCK,
+            LockKey::ScrollLock => VK_SCROLL,
+        }
+    }
+
+    /// Scale a logical coordinate to physical pixels about a monitor's
+    /// origin, which is the same in both spaces
+    fn logical_to_physical(logical: i32, origin: i32, dpi: u32) -> i32 {
+        origin + ((logical - origin) as i64 * dpi as i64 / BASE_DPI as i64) as i32
+    }
+
+    fn physical_to_logical(physical: i32, origin: i32, dpi: u32) -> i32 {
+        origin + ((physical - origin) as i64 * BASE_DPI as i64 / dpi.max(1) as i64) as i32
+    }
+
+    /// Map a physical pixel to the 0..=65535 range across the virtual desktop
+    fn to_absolute(physical: i32, origin: i32, size: i32) -> i32 {
+        ((physical - origin) as i64 * 65535 / (size as i64 - 1)) as i32
+    }
+
+    /// Origin and effective DPI of the monitor nearest a point
+    fn monitor_scale(x: i32, y: i32) -> Result<((i32, i32), u32)> {
+        ensure_dpi_aware();
+        let monitor = unsafe { MonitorFromPoint(POINT { x, y }, MONITOR_DEFAULTTONEAREST) };
+        let (rect, dpi) = monitor_info(monitor)?;
+        Ok(((rect.left, rect.top), dpi))
+    }
+
+    /// Physical rectangle and effective DPI of a monitor
+    fn monitor_info(monitor: HMONITOR) -> Result<(RECT, u32)> {
+        let mut info = MONITORINFO {
+            cbSize: std::mem::size_of::<MONITORINFO>() as u32,
+            ..Default::default()
+        };
+        unsafe { GetMonitorInfoW(monitor, &mut info) }
+            .ok()
+            .context("GetMonitorInfoW failed")?;
+        let (mut dpi_x, mut dpi_y) = (0, 0);
+        unsafe { GetDpiForMonitor(monitor, MDT_EFFECTIVE_DPI, &mut dpi_x, &mut dpi_y) }
+            .context("GetDpiForMonitor failed")?;
+        Ok((info.rcMonitor, dpi_x))
+    }
+
+    unsafe extern "system" fn collect_title(window: HWND, data: LPARAM) -> BOOL {
+        if IsWindowVisible(window).as_bool() {
+            let mut title = [0u16; 512];
+            let len = GetWindowTextW(window, &mut title);
+            if len > 0 {
+                let title = String::from_utf16_lossy(&title[..len as usize]);
+                (*(data.0 as *mut Vec<String>)).push(title);
+            }
+        }
+        true.into()
+    }
+
+    unsafe extern "system" fn collect_monitor(
+        monitor: HMONITOR,
+        _: HDC,
+        _: *mut RECT,
+        data: LPARAM,
+    ) -> BOOL {
+        (*(data.0 as *mut Vec<HMONITOR>)).push(monitor);
+        true.into()
+    }
+
+    /// Make the process per-monitor DPI aware, so Windows reports and takes
+    /// physical pixels instead of virtualizing them
+    fn ensure_dpi_aware() {
+        static AWARE: std::sync::Once = std::sync::Once::new();
+        AWARE.call_once(|| {
+            let result = unsafe {
+                SetProcessDpiAwarenessContext(DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2)
+            };
+            // Fails if a manifest or an earlier call already chose a mode
+            if let Err(err) = result {
+                tracing::debug!("DPI awareness not changed: {err}");
+            }
+        });
+    }
+
+    /// `KEYEVENTF_UNICODE` events typing `c`: a down for each UTF-16 unit
+    /// (two for a surrogate pair above U+FFFF), then the matching ups
+    fn unicode_inputs(c: char) -> Vec<INPUT> {
+        let mut units = [0u16; 2];
+        let units = c.encode_utf16(&mut units);
+        let downs = units.iter().map(|&u| keyboard(u, KEYEVENTF_UNICODE));
+        let ups = units
+            .iter()
+            .map(|&u| keyboard(u, KEYEVENTF_UNICODE | KEYEVENTF_KEYUP));
+        downs.chain(ups).collect()
+    }
+
     impl ActionExecutor for WindowsExecutor {
-        fn simulate_key(&self, _key: Key, _state: InputState) -> Result<()> {
-            todo!("Implement using SendInput")
+        fn simulate_key(&self, key: Key, state: InputState) -> Result<(), ExecutorError> {
+            send(&[key_input(key, state)])
+        }
+
+        fn simulate_mouse(
+            &self,
+            button: MouseButton,
+            state: InputState,
+        ) -> Result<(), ExecutorError> {
+            send(&[mouse_input(button, state)])
+        }
+
+        fn simulate_batch(&self, events: &[(InputKind, InputState)]) -> Result<(), ExecutorError> {
+            // One SendInput call, so real input can't land mid-batch
+            send(&batch_inputs(events))
+        }
+
+        fn simulate_scancode(&self, code: u16, state: InputState) -> Result<(), ExecutorError> {
+            // E0-prefixed codes are passed as 0xE0xx
+            let extended = code & 0xFF00 == 0xE000;
+            send(&[keyboard(code & 0xFF, key_flags(state, extended))])
+        }
+
+        fn send_unicode(&self, c: char) -> Result<(), ExecutorError> {
+            send(&unicode_inputs(c))
+        }
+
+        fn mouse_move_abs(&self, x: i32, y: i32) -> Result<(), ExecutorError> {
+            let ((left, top), dpi) = monitor_scale(x, y)?;
+            let x = logical_to_physical(x, left, dpi);
+            let y = logical_to_physical(y, top, dpi);
+            // With VIRTUALDESK, 0..=65535 spans every monitor, so points
+            // left of or above the primary one are reachable
+            let (left, top, width, height) = unsafe {
+                (
+                    GetSystemMetrics(SM_XVIRTUALSCREEN),
+                    GetSystemMetrics(SM_YVIRTUALSCREEN),
+                    GetSystemMetrics(SM_CXVIRTUALSCREEN),
+                    GetSystemMetrics(SM_CYVIRTUALSCREEN),
+                )
+            };
+            if width <= 1 || height <= 1 {
+                return Err(ExecutorError::Backend(
+                    "could not read the virtual screen size".into(),
+                ));
+            }
+            let nx = to_absolute(x, left, width);
+            let ny = to_absolute(y, top, height);
+            let flags = MOUSEEVENTF_MOVE | MOUSEEVENTF_ABSOLUTE | MOUSEEVENTF_VIRTUALDESK;
+            send(&[mouse(nx, ny, 0, flags)])
         }
 
-        fn simulate_mouse(&self, _button: MouseButton, _state: InputState) -> Result<()> {
-            todo!("Implement using SendInput")
+        fn mouse_move_rel(&self, dx: i32, dy: i32) -> Result<(), ExecutorError> {
+            // Subject to the user's pointer acceleration setting
+            send(&[mouse(dx, dy, 0, MOUSEEVENTF_MOVE)])
         }
 
-        fn mouse_move_abs(&self, _x: i32, _y: i32) -> Result<()> {
-            todo!("Implement using SendInput")
+        fn scroll(&self, dx: i32, dy: i32) -> Result<(), ExecutorError> {
+            // Positive deltas scroll up and right, matching `dx`/`dy`
+            let wheel =
+                |detents: i32, flags| mouse(0, 0, (detents * WHEEL_DELTA as i32) as u32, flags);
+            let mut inputs = Vec::with_capacity(2);
+            if dy != 0 {
+                inputs.push(wheel(dy, MOUSEEVENTF_WHEEL));
+            }
+            if dx != 0 {
+                inputs.push(wheel(dx, MOUSEEVENTF_HWHEEL));
+            }
+            if inputs.is_empty() {
+                return Ok(());
+            }
+            send(&inputs)
+        }
+
+        fn current_position(&self) -> Result<(i32, i32), ExecutorError> {
+            let mut point = POINT::default();
+            ensure_dpi_aware();
+            unsafe { GetCursorPos(&mut point) }.context("GetCursorPos failed")?;
+            let ((left, top), dpi) = monitor_scale(point.x, point.y)?;
+            Ok((
+                physical_to_logical(point.x, left, dpi),
+                physical_to_logical(point.y, top, dpi),
+            ))
+        }
+
+        fn monitors(&self) -> Result<Vec<Monitor>, ExecutorError> {
+            ensure_dpi_aware();
+            let mut handles: Vec<HMONITOR> = Vec::new();
+            unsafe {
+                EnumDisplayMonitors(
+                    None,
+                    None,
+                    Some(collect_monitor),
+                    LPARAM(&mut handles as *mut Vec<HMONITOR> as isize),
+                )
+            }
+            .ok()
+            .context("EnumDisplayMonitors failed")?;
+            let mut monitors = handles
+                .into_iter()
+                .map(|handle| {
+                    // Sizes in logical units, matching `mouse_move_abs`
+                    let (rect, dpi) = monitor_info(handle)?;
+                    Ok(Monitor {
+                        x: rect.left,
+                        y: rect.top,
+                        width: physical_to_logical(rect.right, rect.left, dpi) - rect.left,
+                        height: physical_to_logical(rect.bottom, rect.top, dpi) - rect.top,
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?;
+            monitors.sort_by_key(|m| (m.x, m.y));
+            Ok(monitors)
+        }
+
+        fn window_titles(&self) -> Result<Vec<String>, ExecutorError> {
+            let mut titles: Vec<String> = Vec::new();
+            unsafe {
+                EnumWindows(
+                    Some(collect_title),
+                    LPARAM(&mut titles as *mut Vec<String> as isize),
+                )
+            }
+            .context("EnumWindows failed")?;
+            Ok(titles)
+        }
+
+        #[cfg(feature = "screen-capture")]
+        fn pixel_color(&self, x: i32, y: i32) -> Result<(u8, u8, u8), ExecutorError> {
+            let ((left, top), dpi) = monitor_scale(x, y)?;
+            let (x, y) = (
+                logical_to_physical(x, left, dpi),
+                logical_to_physical(y, top, dpi),
+            );
+            // The screen DC covers the whole virtual desktop
+            let color = unsafe {
+                let dc = GetDC(None);
+                let color = GetPixel(dc, x, y);
+                ReleaseDC(None, dc);
+                color
+            };
+            // CLR_INVALID: off the screen or clipped away
+            if color.0 == u32::MAX {
+                return Err(ExecutorError::Backend(format!(
+                    "cannot read the pixel at ({x}, {y})"
+                )));
+            }
+            let [r, g, b, _] = color.0.to_le_bytes();
+            Ok((r, g, b))
+        }
+
+        fn held_keys(&self) -> Result<Vec<Key>, ExecutorError> {
+            Ok(Key::ALL
+                .iter()
+                .copied()
+                .filter(|&key| {
+                    let vk = if key == Key::Pause {
+                        u32::from(VK_PAUSE.0)
+                    } else {
+                        let (scan, extended) = scan_code(key);
+                        let scan = if extended { 0xE000 | scan } else { scan };
+                        unsafe { MapVirtualKeyW(u32::from(scan), MAPVK_VSC_TO_VK_EX) }
+                    };
+                    // The high bit is set while the key is down
+                    vk != 0 && unsafe { GetAsyncKeyState(vk as i32) } < 0
+                })
+                .collect())
+        }
+
+        fn lock_state(&self, lock: LockKey) -> Result<bool, ExecutorError> {
+            let vk = lock_vk(lock);
+            // The low bit is the toggle state
+            Ok(unsafe { GetKeyState(vk.0 as i32) } & 1 != 0)
+        }
+
+        fn set_lock(&self, lock: LockKey, on: bool) -> Result<(), ExecutorError> {
+            if ActionExecutor::lock_state(self, lock)? == on {
+                return Ok(());
+            }
+            // A tap toggles; virtual keys are fine here as lock keys
+            // don't move between layouts
+            let vk = lock_vk(lock);
+            send(&[
+                virtual_key(vk, KEYBD_EVENT_FLAGS(0)),
+                virtual_key(vk, KEYEVENTF_KEYUP),
+            ])
         }
 
-        fn mouse_move_rel(&self, _dx: i32, _dy: i32) -> Result<()> {
-            todo!("Implement using SendInput")
+        fn block_input(&self, blocked: bool) -> Result<(), ExecutorError> {
+            // Only physical input is blocked; SendInput from this thread
+            // still goes through
+            unsafe { BlockInput(blocked) }.map_err(|err| match err.code() {
+                // Blocking needs a process at least as elevated as the
+                // foreground one
+                code if code == E_ACCESSDENIED => ExecutorError::PermissionDenied(err.message()),
+                _ => ExecutorError::Backend(format!("BlockInput failed: {err}")),
+            })
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use std::collections::HashSet;
+
+        #[test]
+        fn every_key_has_a_distinct_scan_code() {
+            let mut seen = HashSet::new();
+            // Pause goes through its virtual key instead
+            for &key in Key::ALL.iter().filter(|&&key| key != Key::Pause) {
+                let (scan, extended) = scan_code(key);
+                assert!(scan > 0 && scan < 0x80, "{key:?} -> {scan:#x}");
+                assert!(seen.insert((scan, extended)), "{key:?} shares {scan:#x}");
+            }
+        }
+
+        /// Scan code and flags of each keyboard input
+        fn units(inputs: &[INPUT]) -> Vec<(u16, KEYBD_EVENT_FLAGS)> {
+            inputs
+                .iter()
+                .map(|input| {
+                    assert_eq!(input.r#type, INPUT_KEYBOARD);
+                    let ki = unsafe { input.Anonymous.ki };
+                    assert_eq!(ki.wVk, VIRTUAL_KEY(0));
+                    (ki.wScan, ki.dwFlags)
+                })
+                .collect()
+        }
+
+        #[test]
+        fn a_click_is_one_array_of_down_then_up() {
+            let button = InputKind::Mouse(MouseButton::Left);
+            let inputs =
+                batch_inputs(&[(button, InputState::Press), (button, InputState::Release)]);
+            let flags: Vec<_> = inputs
+                .iter()
+                .map(|input| {
+                    assert_eq!(input.r#type, INPUT_MOUSE);
+                    unsafe { input.Anonymous.mi.dwFlags }
+                })
+                .collect();
+            assert_eq!(flags, [MOUSEEVENTF_LEFTDOWN, MOUSEEVENTF_LEFTUP]);
+        }
+
+        #[test]
+        fn logical_points_scale_from_the_monitor_origin() {
+            // 100%, 150% and 200% scaling, on a primary and a second monitor
+            for (dpi, scaled) in [(96, 200), (144, 300), (192, 400)] {
+                assert_eq!(logical_to_physical(200, 0, dpi), scaled);
+                assert_eq!(logical_to_physical(1920 + 200, 1920, dpi), 1920 + scaled);
+                assert_eq!(physical_to_logical(scaled, 0, dpi), 200);
+                assert_eq!(physical_to_logical(1920 + scaled, 1920, dpi), 1920 + 200);
+            }
+            // Monitors left of the primary have negative origins
+            assert_eq!(logical_to_physical(-1000, -1280, 192), -1280 + 560);
+            assert_eq!(physical_to_logical(-1280 + 560, -1280, 192), -1000);
+        }
+
+        #[test]
+        fn scaled_points_span_a_mixed_dpi_desktop() {
+            // A 2560px 200% monitor left of a 1920px 100% primary
+            let (left, width) = (-2560, 2560 + 1920);
+            // 640 logical pixels into the left monitor are 1280 physical ones
+            let on_left = logical_to_physical(-2560 + 640, -2560, 192);
+            assert_eq!(on_left, -1280);
+            assert_eq!(to_absolute(-2560, left, width), 0);
+            assert_eq!(to_absolute(1919, left, width), 65535);
+            assert_eq!(to_absolute(on_left, left, width), 1280 * 65535 / 4479);
+            assert_eq!(
+                to_absolute(logical_to_physical(0, 0, 96), left, width),
+                2560 * 65535 / 4479
+            );
+        }
+
+        #[test]
+        fn unicode_inputs_send_one_unit_for_bmp_characters() {
+            let up = KEYEVENTF_UNICODE | KEYEVENTF_KEYUP;
+            assert_eq!(
+                units(&unicode_inputs('é')),
+                [(0x00E9, KEYEVENTF_UNICODE), (0x00E9, up)]
+            );
+        }
+
+        #[test]
+        fn unicode_inputs_split_astral_characters_into_surrogates() {
+            let up = KEYEVENTF_UNICODE | KEYEVENTF_KEYUP;
+            // U+1F600 is D83D DE00 in UTF-16
+            assert_eq!(
+                units(&unicode_inputs('\u{1F600}')),
+                [
+                    (0xD83D, KEYEVENTF_UNICODE),
+                    (0xDE00, KEYEVENTF_UNICODE),
+                    (0xD83D, up),
+                    (0xDE00, up),
+                ]
+            );
         }
     }
 }
@@ -150,24 +2092,1626 @@ mod windows_impl {
 #[cfg(target_os = "linux")]
 mod linux_impl {
     use super::*;
+    use std::collections::HashMap;
+    use x11rb::connection::{Connection, RequestConnection};
+    use x11rb::protocol::xinerama::ConnectionExt as _;
+    use x11rb::protocol::xinput::{self, ConnectionExt as _, DeviceType, GrabOwner};
+    use x11rb::protocol::xkb::{self, ConnectionExt as _};
+    use x11rb::protocol::xproto::{
+        Atom, AtomEnum, ConnectionExt as _, GrabMode, GrabStatus, ModMask, Window,
+        BUTTON_PRESS_EVENT, BUTTON_RELEASE_EVENT, KEY_PRESS_EVENT, KEY_RELEASE_EVENT,
+        MOTION_NOTIFY_EVENT,
+    };
+    #[cfg(feature = "screen-capture")]
+    use x11rb::protocol::xproto::{ImageFormat, ImageOrder, VisualClass};
+    use x11rb::protocol::xtest::{self, ConnectionExt as _};
+    use x11rb::rust_connection::RustConnection;
+
+    /// XTest-based executor holding its own X connection
+    ///
+    /// Keys resolve through the server's keymap (by keysym), so letters and
+    /// symbols follow the active layout; pair `TypeText` with `UsQwerty`.
+    pub struct X11Executor {
+        conn: RustConnection,
+        root: Window,
+        // Lowest keycode producing each keysym, read at construction
+        keycodes: HashMap<u32, u8>,
+        // Devices grabbed by `block_input`
+        grabbed: Mutex<Vec<xinput::DeviceId>>,
+    }
+
+    impl X11Executor {
+        /// Connect to `$DISPLAY`, failing early if XTest is unavailable
+        pub fn new() -> Result<Self> {
+            let (conn, screen) = x11rb::connect(None).context("cannot connect to the X server")?;
+            if conn
+                .extension_information(xtest::X11_EXTENSION_NAME)?
+                .is_none()
+            {
+                anyhow::bail!("the X server does not support the XTEST extension");
+            }
+            let root = conn.setup().roots[screen].root;
+            let keycodes = keysym_map(&conn)?;
+            Ok(Self {
+                conn,
+                root,
+                keycodes,
+                grabbed: Mutex::default(),
+            })
+        }
+
+        fn fake_input(&self, kind: u8, detail: u8, x: i32, y: i32) -> Result<(), ExecutorError> {
+            let clamp = |v: i32| v.clamp(i16::MIN.into(), i16::MAX.into()) as i16;
+            self.conn.xtest_fake_input(
+                kind,
+                detail,
+                x11rb::CURRENT_TIME,
+                self.root,
+                clamp(x),
+                clamp(y),
+                0,
+            )?;
+            // Requests are buffered until flushed
+            self.conn.flush()?;
+            Ok(())
+        }
+
+        /// Modifier a lock key locks, found through the modifier mapping
+        fn lock_modifier(&self, lock: LockKey) -> Result<ModMask> {
+            let keysym = match lock {
+                LockKey::CapsLock => return Ok(ModMask::LOCK),
+                LockKey::NumLock => 0xff7f,
+                LockKey::ScrollLock => 0xff14,
+            };
+            let keycode = *self
+                .keycodes
+                .get(&keysym)
+                .with_context(|| format!("no key produces {lock:?}"))?;
+            let mapping = self.conn.get_modifier_mapping()?.reply()?;
+            let per_modifier = (mapping.keycodes.len() / 8).max(1);
+            let index = mapping
+                .keycodes
+                .iter()
+                .position(|&k| k == keycode)
+                .with_context(|| format!("{lock:?} is not bound to a modifier"))?;
+            Ok(ModMask::from(1u16 << (index / per_modifier)))
+        }
 
-    pub struct X11Executor;
+        /// Keycode producing `key` in the server's keymap
+        fn keycode(&self, key: Key) -> Result<u8, ExecutorError> {
+            match key.keysym().and_then(|keysym| self.keycodes.get(&keysym)) {
+                Some(&keycode) => Ok(keycode),
+                // Not in the keymap (e.g. no AltGr on a US layout), so
+                // fall back to the key's position
+                None => {
+                    u8::try_from(key.physical().0 + 8).map_err(|_| ExecutorError::UnmappedKey(key))
+                }
+            }
+        }
+
+        fn atom(&self, name: &[u8]) -> Result<Atom, ExecutorError> {
+            Ok(self.conn.intern_atom(false, name)?.reply()?.atom)
+        }
+
+        fn fake_key(&self, keycode: u8, state: InputState) -> Result<(), ExecutorError> {
+            let kind = match state {
+                InputState::Press => KEY_PRESS_EVENT,
+                InputState::Release => KEY_RELEASE_EVENT,
+            };
+            self.fake_input(kind, keycode, 0, 0)
+        }
+    }
+
+    impl From<x11rb::errors::ConnectionError> for ExecutorError {
+        fn from(err: x11rb::errors::ConnectionError) -> Self {
+            ExecutorError::Backend(format!("X connection failed: {err}"))
+        }
+    }
+
+    impl From<x11rb::errors::ReplyError> for ExecutorError {
+        fn from(err: x11rb::errors::ReplyError) -> Self {
+            ExecutorError::Backend(format!("X request failed: {err}"))
+        }
+    }
+
+    fn keysym_map(conn: &RustConnection) -> Result<HashMap<u32, u8>> {
+        let setup = conn.setup();
+        let (min, max) = (setup.min_keycode, setup.max_keycode);
+        let mapping = conn.get_keyboard_mapping(min, max - min + 1)?.reply()?;
+        let per_keycode = usize::from(mapping.keysyms_per_keycode).max(1);
+        let mut keycodes = HashMap::new();
+        for (keycode, keysyms) in (min..=max).zip(mapping.keysyms.chunks(per_keycode)) {
+            for &keysym in keysyms.iter().filter(|&&sym| sym != 0) {
+                keycodes.entry(keysym).or_insert(keycode);
+            }
+        }
+        Ok(keycodes)
+    }
 
     impl ActionExecutor for X11Executor {
-        fn simulate_key(&self, _key: Key, _state: InputState) -> Result<()> {
-            todo!("Implement using XTest extension")
+        fn simulate_key(&self, key: Key, state: InputState) -> Result<(), ExecutorError> {
+            self.fake_key(self.keycode(key)?, state)
         }
 
-        fn simulate_mouse(&self, _button: MouseButton, _state: InputState) -> Result<()> {
-            todo!("Implement using XTest extension")
+        fn simulate_mouse(
+            &self,
+            button: MouseButton,
+            state: InputState,
+        ) -> Result<(), ExecutorError> {
+            // 4-7 are the scroll wheel, so side buttons start at 8
+            let detail = match button {
+                MouseButton::Left => 1,
+                MouseButton::Middle => 2,
+                MouseButton::Right => 3,
+                MouseButton::Button4 => 8,
+                MouseButton::Button5 => 9,
+            };
+            let kind = match state {
+                InputState::Press => BUTTON_PRESS_EVENT,
+                InputState::Release => BUTTON_RELEASE_EVENT,
+            };
+            self.fake_input(kind, detail, 0, 0)
         }
 
-        fn mouse_move_abs(&self, _x: i32, _y: i32) -> Result<()> {
-            todo!("Implement using XTest extension")
+        fn simulate_scancode(&self, code: u16, state: InputState) -> Result<(), ExecutorError> {
+            let keycode = u8::try_from(code.saturating_add(8))
+                .with_context(|| format!("evdev code {code} has no X keycode"))?;
+            self.fake_key(keycode, state)
         }
 
-        fn mouse_move_rel(&self, _dx: i32, _dy: i32) -> Result<()> {
-            todo!("Implement using XTest extension")
+        fn mouse_move_abs(&self, x: i32, y: i32) -> Result<(), ExecutorError> {
+            self.fake_input(MOTION_NOTIFY_EVENT, 0, x, y)
         }
+
+        fn mouse_move_rel(&self, dx: i32, dy: i32) -> Result<(), ExecutorError> {
+            // A detail of 1 makes the motion relative
+            self.fake_input(MOTION_NOTIFY_EVENT, 1, dx, dy)
+        }
+
+        fn scroll(&self, dx: i32, dy: i32) -> Result<(), ExecutorError> {
+            // One click per detent: 4/5 scroll up/down, 6/7 left/right
+            let vertical = if dy > 0 { 4 } else { 5 };
+            let horizontal = if dx > 0 { 7 } else { 6 };
+            for (button, detents) in [(vertical, dy), (horizontal, dx)] {
+                for _ in 0..detents.unsigned_abs() {
+                    self.fake_input(BUTTON_PRESS_EVENT, button, 0, 0)?;
+                    self.fake_input(BUTTON_RELEASE_EVENT, button, 0, 0)?;
+                }
+            }
+            Ok(())
+        }
+
+        fn current_position(&self) -> Result<(i32, i32), ExecutorError> {
+            let pointer = self
+                .conn
+                .query_pointer(self.root)?
+                .reply()
+                .context("QueryPointer failed")?;
+            Ok((pointer.root_x.into(), pointer.root_y.into()))
+        }
+
+        fn monitors(&self) -> Result<Vec<Monitor>, ExecutorError> {
+            let screens = self
+                .conn
+                .xinerama_query_screens()?
+                .reply()
+                .context("Xinerama query failed")?
+                .screen_info;
+            if screens.is_empty() {
+                // Xinerama inactive: the root window is the only screen
+                let root = self.conn.get_geometry(self.root)?.reply()?;
+                return Ok(vec![Monitor {
+                    x: 0,
+                    y: 0,
+                    width: root.width.into(),
+                    height: root.height.into(),
+                }]);
+            }
+            let mut monitors: Vec<Monitor> = screens
+                .iter()
+                .map(|screen| Monitor {
+                    x: screen.x_org.into(),
+                    y: screen.y_org.into(),
+                    width: screen.width.into(),
+                    height: screen.height.into(),
+                })
+                .collect();
+            monitors.sort_by_key(|m| (m.x, m.y));
+            Ok(monitors)
+        }
+
+        fn window_titles(&self) -> Result<Vec<String>, ExecutorError> {
+            // The window manager lists the windows it manages on the root
+            let client_list = self.atom(b"_NET_CLIENT_LIST")?;
+            let clients = self
+                .conn
+                .get_property(false, self.root, client_list, AtomEnum::WINDOW, 0, u32::MAX)?
+                .reply()
+                .context("reading _NET_CLIENT_LIST failed")?;
+            let Some(windows) = clients.value32() else {
+                return Err(ExecutorError::Unsupported(
+                    "window enumeration without an EWMH window manager",
+                ));
+            };
+            let (net_wm_name, utf8) = (self.atom(b"_NET_WM_NAME")?, self.atom(b"UTF8_STRING")?);
+            let mut titles = Vec::new();
+            for window in windows {
+                // A window closed since the list was read fails with
+                // BadWindow; skip it
+                let Ok(mut name) = self
+                    .conn
+                    .get_property(false, window, net_wm_name, utf8, 0, u32::MAX)?
+                    .reply()
+                else {
+                    continue;
+                };
+                if name.value.is_empty() {
+                    // Fall back to the legacy WM_NAME
+                    let legacy = self.conn.get_property(
+                        false,
+                        window,
+                        AtomEnum::WM_NAME,
+                        AtomEnum::STRING,
+                        0,
+                        u32::MAX,
+                    )?;
+                    let Ok(legacy) = legacy.reply() else {
+                        continue;
+                    };
+                    name = legacy;
+                }
+                if !name.value.is_empty() {
+                    titles.push(String::from_utf8_lossy(&name.value).into_owned());
+                }
+            }
+            Ok(titles)
+        }
+
+        #[cfg(feature = "screen-capture")]
+        fn pixel_color(&self, x: i32, y: i32) -> Result<(u8, u8, u8), ExecutorError> {
+            let (x, y) = (
+                i16::try_from(x).context("x is off the screen")?,
+                i16::try_from(y).context("y is off the screen")?,
+            );
+            let image = self
+                .conn
+                .get_image(ImageFormat::Z_PIXMAP, self.root, x, y, 1, 1, u32::MAX)?
+                .reply()
+                .context("GetImage failed")?;
+            let setup = self.conn.setup();
+            let bytes = setup
+                .pixmap_formats
+                .iter()
+                .find(|format| format.depth == image.depth)
+                .map(|format| usize::from(format.bits_per_pixel).div_ceil(8))
+                .filter(|&bytes| (1..=4).contains(&bytes) && bytes <= image.data.len())
+                .context("unsupported pixmap format")?;
+            let mut raw = [0u8; 4];
+            raw[..bytes].copy_from_slice(&image.data[..bytes]);
+            let pixel = if setup.image_byte_order == ImageOrder::MSB_FIRST {
+                u32::from_be_bytes(raw) >> (8 * (4 - bytes))
+            } else {
+                u32::from_le_bytes(raw)
+            };
+            let visual = setup
+                .roots
+                .iter()
+                .flat_map(|screen| &screen.allowed_depths)
+                .flat_map(|depth| &depth.visuals)
+                .find(|visual| visual.visual_id == image.visual)
+                .context("GetImage returned an unknown visual")?;
+            if visual.class != VisualClass::TRUE_COLOR && visual.class != VisualClass::DIRECT_COLOR
+            {
+                return Err(ExecutorError::Unsupported(
+                    "screen reads on colormapped displays",
+                ));
+            }
+            // Scale each channel from its mask's width to 8 bits
+            let channel = |mask: u32| {
+                let max = u64::from(mask >> mask.trailing_zeros());
+                let value = u64::from((pixel & mask) >> mask.trailing_zeros());
+                (value * 255 / max.max(1)) as u8
+            };
+            Ok((
+                channel(visual.red_mask),
+                channel(visual.green_mask),
+                channel(visual.blue_mask),
+            ))
+        }
+
+        fn held_keys(&self) -> Result<Vec<Key>, ExecutorError> {
+            // One bit per keycode, set while that key is down
+            let keymap = self
+                .conn
+                .query_keymap()?
+                .reply()
+                .context("QueryKeymap failed")?;
+            let down =
+                |keycode: u8| keymap.keys[usize::from(keycode / 8)] & (1 << (keycode % 8)) != 0;
+            Ok(Key::ALL
+                .iter()
+                .copied()
+                .filter(|&key| self.keycode(key).is_ok_and(down))
+                .collect())
+        }
+
+        fn lock_state(&self, lock: LockKey) -> Result<bool, ExecutorError> {
+            // Core LED order is Caps, Num, Scroll on XKB-based servers
+            let bit = match lock {
+                LockKey::CapsLock => 0,
+                LockKey::NumLock => 1,
+                LockKey::ScrollLock => 2,
+            };
+            let control = self
+                .conn
+                .get_keyboard_control()?
+                .reply()
+                .context("GetKeyboardControl failed")?;
+            Ok(control.led_mask & (1 << bit) != 0)
+        }
+
+        fn set_lock(&self, lock: LockKey, on: bool) -> Result<(), ExecutorError> {
+            if ActionExecutor::lock_state(self, lock)? == on {
+                return Ok(());
+            }
+            let mask = self.lock_modifier(lock)?;
+            let xkb = self
+                .conn
+                .xkb_use_extension(1, 0)?
+                .reply()
+                .context("XKB is unavailable")?;
+            if !xkb.supported {
+                return Err(ExecutorError::Backend(
+                    "the X server does not support XKB 1.0".into(),
+                ));
+            }
+            let locks = if on { mask } else { ModMask::default() };
+            self.conn.xkb_latch_lock_state(
+                xkb::ID::USE_CORE_KBD.into(),
+                mask,
+                locks,
+                false,
+                xkb::Group::M1,
+                ModMask::default(),
+                false,
+                0,
+            )?;
+            self.conn.flush()?;
+            Ok(())
+        }
+
+        fn block_input(&self, blocked: bool) -> Result<(), ExecutorError> {
+            // Also reached from `UnblockOnDrop`, so a poisoned lock must
+            // not panic
+            let mut grabbed = self
+                .grabbed
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner);
+            if !blocked {
+                for device in grabbed.drain(..) {
+                    self.conn
+                        .xinput_xi_ungrab_device(x11rb::CURRENT_TIME, device)?;
+                }
+                self.conn.flush()?;
+                return Ok(());
+            }
+            // Grabbing each physical device, rather than the core keyboard
+            // and pointer, leaves the XTest devices free so actions can still
+            // inject while real input is swallowed
+            self.conn
+                .xinput_xi_query_version(2, 0)?
+                .reply()
+                .context("XInput 2 is unavailable")?;
+            let devices = self
+                .conn
+                .xinput_xi_query_device(xinput::Device::ALL)?
+                .reply()?
+                .infos;
+            let physical = devices.iter().filter(|device| {
+                matches!(
+                    device.type_,
+                    DeviceType::SLAVE_KEYBOARD | DeviceType::SLAVE_POINTER
+                ) && !String::from_utf8_lossy(&device.name).contains("XTEST")
+            });
+            for device in physical {
+                let reply = self
+                    .conn
+                    .xinput_xi_grab_device(
+                        self.root,
+                        x11rb::CURRENT_TIME,
+                        x11rb::NONE,
+                        device.deviceid,
+                        GrabMode::ASYNC,
+                        GrabMode::ASYNC,
+                        GrabOwner::NO_OWNER,
+                        &[0],
+                    )?
+                    .reply()?;
+                if reply.status != GrabStatus::SUCCESS {
+                    let name = String::from_utf8_lossy(&device.name).into_owned();
+                    // Don't leave some devices grabbed
+                    for device in grabbed.drain(..) {
+                        self.conn
+                            .xinput_xi_ungrab_device(x11rb::CURRENT_TIME, device)?;
+                    }
+                    self.conn.flush()?;
+                    return Err(ExecutorError::Backend(format!(
+                        "cannot grab {name}: {:?}",
+                        reply.status
+                    )));
+                }
+                grabbed.push(device.deviceid);
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod macos_impl {
+    use super::*;
+    use core_graphics::event::{
+        CGEvent, CGEventTapLocation, CGEventType, CGMouseButton, EventField,
+    };
+    use core_graphics::event_source::{CGEventSource, CGEventSourceStateID};
+    use core_graphics::geometry::CGPoint;
+
+    #[link(name = "ApplicationServices", kind = "framework")]
+    extern "C" {
+        fn AXIsProcessTrusted() -> bool;
+    }
+
+    /// Posts Core Graphics events at the HID level
+    pub struct MacExecutor;
+
+    impl MacExecutor {
+        /// Fails unless the process has been granted Accessibility access,
+        /// without which posted events are silently dropped
+        pub fn new() -> Result<Self> {
+            if !unsafe { AXIsProcessTrusted() } {
+                anyhow::bail!(
+                    "HandPlusPlus needs Accessibility access to simulate input; enable it under \
+                     System Settings → Privacy & Security → Accessibility and restart"
+                );
+            }
+            Ok(Self)
+        }
+    }
+
+    fn source() -> Result<CGEventSource> {
+        CGEventSource::new(CGEventSourceStateID::HIDSystemState)
+            .map_err(|()| anyhow::anyhow!("failed to create a CGEventSource"))
+    }
+
+    fn location() -> Result<CGPoint> {
+        let event =
+            CGEvent::new(source()?).map_err(|()| anyhow::anyhow!("failed to create a CGEvent"))?;
+        Ok(event.location())
+    }
+
+    fn mouse_event(kind: CGEventType, at: CGPoint, button: CGMouseButton) -> Result<CGEvent> {
+        let event = CGEvent::new_mouse_event(source()?, kind, at, button)
+            .map_err(|()| anyhow::anyhow!("failed to create a mouse event"))?;
+        Ok(event)
+    }
+
+    impl ActionExecutor for MacExecutor {
+        fn simulate_key(&self, key: Key, state: InputState) -> Result<(), ExecutorError> {
+            let code = key.mac_key_code().ok_or(ExecutorError::UnmappedKey(key))?;
+            let event = CGEvent::new_keyboard_event(source()?, code, state == InputState::Press)
+                .map_err(|()| anyhow::anyhow!("failed to create a key event for {key}"))?;
+            event.post(CGEventTapLocation::HID);
+            Ok(())
+        }
+
+        fn simulate_mouse(
+            &self,
+            button: MouseButton,
+            state: InputState,
+        ) -> Result<(), ExecutorError> {
+            use CGEventType::*;
+            let (down, up, cg_button, number) = match button {
+                MouseButton::Left => (LeftMouseDown, LeftMouseUp, CGMouseButton::Left, 0),
+                MouseButton::Right => (RightMouseDown, RightMouseUp, CGMouseButton::Right, 1),
+                MouseButton::Middle => (OtherMouseDown, OtherMouseUp, CGMouseButton::Center, 2),
+                MouseButton::Button4 => (OtherMouseDown, OtherMouseUp, CGMouseButton::Center, 3),
+                MouseButton::Button5 => (OtherMouseDown, OtherMouseUp, CGMouseButton::Center, 4),
+            };
+            let kind = match state {
+                InputState::Press => down,
+                InputState::Release => up,
+            };
+            let event = mouse_event(kind, location()?, cg_button)?;
+            // Other-button events carry the real button in this field
+            event.set_integer_value_field(EventField::MOUSE_EVENT_BUTTON_NUMBER, number);
+            event.post(CGEventTapLocation::HID);
+            Ok(())
+        }
+
+        fn mouse_move_abs(&self, x: i32, y: i32) -> Result<(), ExecutorError> {
+            let at = CGPoint::new(x.into(), y.into());
+            mouse_event(CGEventType::MouseMoved, at, CGMouseButton::Left)?
+                .post(CGEventTapLocation::HID);
+            Ok(())
+        }
+
+        fn mouse_move_rel(&self, dx: i32, dy: i32) -> Result<(), ExecutorError> {
+            let from = location()?;
+            let at = CGPoint::new(from.x + f64::from(dx), from.y + f64::from(dy));
+            mouse_event(CGEventType::MouseMoved, at, CGMouseButton::Left)?
+                .post(CGEventTapLocation::HID);
+            Ok(())
+        }
+
+        fn current_position(&self) -> Result<(i32, i32), ExecutorError> {
+            let at = location()?;
+            Ok((at.x.round() as i32, at.y.round() as i32))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn random_delay_rejects_min_above_max() {
+        let action = Action::RandomDelay {
+            min: Duration::from_millis(50),
+            max: Duration::from_millis(10),
+        };
+        assert!(action.validate().is_err());
+        // Unvalidated, it errors instead of panicking in gen_range
+        let executor = MockExecutor::new();
+        assert!(action.execute_blocking(&executor).is_err());
+    }
+
+    #[tokio::test]
+    async fn execute_blocking_fails_inside_a_runtime() {
+        let executor = MockExecutor::new();
+        let err = Action::PressKey(Key::A)
+            .execute_blocking(&executor)
+            .unwrap_err();
+        assert!(err.to_string().contains("inside a tokio runtime"), "{err}");
+        assert!(executor.calls().is_empty());
+    }
+
+    #[test]
+    fn weighted_choice_rejects_an_overflowing_total() {
+        let action = Action::WeightedChoice(vec![
+            (1e308, Action::PressKey(Key::A)),
+            (1e308, Action::PressKey(Key::B)),
+        ]);
+        assert!(action.validate().is_err());
+        // Unvalidated, it errors instead of panicking in gen_range
+        let executor = MockExecutor::new();
+        assert!(action.execute_blocking(&executor).is_err());
+        assert!(executor.calls().is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn random_delay_stays_within_bounds() {
+        let (min, max) = (Duration::from_millis(20), Duration::from_millis(40));
+        let action = Action::RandomDelay { min, max };
+        action.validate().unwrap();
+        let executor = MockExecutor::new();
+        for seed in 0..20 {
+            let started = tokio::time::Instant::now();
+            let ctx = ExecutionContext::default().with_seed(seed);
+            action.execute_with(&executor, &ctx).await.unwrap();
+            let elapsed = started.elapsed();
+            assert!((min..=max).contains(&elapsed), "{elapsed:?}");
+        }
+    }
+
+    /// Run `action`, cancelling its context after `after`
+    async fn run_cancelled_after(action: &Action, executor: &MockExecutor, after: Duration) {
+        let ctx = ExecutionContext::default();
+        let cancel = ctx.cancel.clone();
+        let (result, ()) = tokio::join!(action.execute_with(executor, &ctx), async {
+            tokio::time::sleep(after).await;
+            cancel.cancel();
+        });
+        result.unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn cancel_cuts_delay_short_and_releases_held_keys() {
+        let action = Action::Sequence(vec![
+            Action::HoldKey(Key::A),
+            Action::Delay(Duration::from_secs(10)),
+            Action::PressKey(Key::B),
+        ]);
+        let executor = MockExecutor::new();
+        let started = tokio::time::Instant::now();
+        run_cancelled_after(&action, &executor, Duration::from_millis(100)).await;
+        assert_eq!(started.elapsed(), Duration::from_millis(100));
+        assert_eq!(
+            executor.calls(),
+            [
+                RecordedCall::Key(Key::A, InputState::Press),
+                RecordedCall::Key(Key::A, InputState::Release),
+            ]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn cancel_stops_timed_loops() {
+        let second = Duration::from_secs(1);
+        let cancel_at = Duration::from_millis(100);
+        let actions = [
+            Action::RandomDelay {
+                min: second,
+                max: second,
+            },
+            Action::Repeat {
+                inner: Box::new(Action::Delay(second)),
+                count: 10,
+            },
+            Action::TypeTextHumanized {
+                text: "abc".into(),
+                mean: second,
+                jitter: Duration::ZERO,
+            },
+            Action::MousePath {
+                points: vec![(10, 0), (20, 0), (30, 0)],
+                duration_per_segment: second,
+                smooth: true,
+                jitter: 0,
+            },
+            Action::MousePath {
+                points: vec![(10, 0), (20, 0), (30, 0)],
+                duration_per_segment: second,
+                smooth: false,
+                jitter: 0,
+            },
+            Action::MoveSmooth {
+                to: (100, 100),
+                duration: second,
+                easing: Easing::Linear,
+            },
+            Action::SmoothScroll {
+                total: 50,
+                duration: second,
+                easing: Easing::Linear,
+            },
+            Action::WaitForWindow {
+                title_contains: "never".into(),
+                timeout: Duration::from_secs(60),
+                poll_interval: second,
+            },
+        ];
+        for action in &actions {
+            let executor = MockExecutor::new().with_windows(vec!["Editor".into()]);
+            let started = tokio::time::Instant::now();
+            run_cancelled_after(action, &executor, cancel_at).await;
+            assert_eq!(started.elapsed(), cancel_at, "{}", action.kind());
+        }
+    }
+
+    fn key(key: Key, state: InputState) -> RecordedCall {
+        RecordedCall::Key(key, state)
+    }
+
+    /// Press and release of `k`, with Shift around it if `shifted`
+    fn tap(k: Key, shifted: bool) -> Vec<RecordedCall> {
+        use InputState::{Press, Release};
+        let mut calls = vec![key(k, Press), key(k, Release)];
+        if shifted {
+            calls.insert(0, key(Key::Shift, Press));
+            calls.push(key(Key::Shift, Release));
+        }
+        calls
+    }
+
+    #[test]
+    fn type_text_shifts_capitals_and_symbols() {
+        let executor = MockExecutor::new();
+        Action::TypeText("Hello, W!".into())
+            .execute_blocking(&executor)
+            .unwrap();
+        let expected: Vec<RecordedCall> = [
+            (Key::H, true),
+            (Key::E, false),
+            (Key::L, false),
+            (Key::L, false),
+            (Key::O, false),
+            (Key::Comma, false),
+            (Key::Space, false),
+            (Key::W, true),
+            (Key::Num1, true),
+        ]
+        .into_iter()
+        .flat_map(|(k, shifted)| tap(k, shifted))
+        .collect();
+        assert_eq!(executor.calls(), expected);
+    }
+
+    #[test]
+    fn type_text_inverts_shift_under_caps_lock() {
+        let executor = MockExecutor::new().with_lock_on(LockKey::CapsLock);
+        Action::TypeText("aB1".into())
+            .execute_blocking(&executor)
+            .unwrap();
+        let expected = [tap(Key::A, true), tap(Key::B, false), tap(Key::Num1, false)].concat();
+        assert_eq!(executor.calls(), expected);
+    }
+
+    #[test]
+    fn type_text_errors_on_characters_it_cannot_type() {
+        // The mock has neither a key for the snowman nor Unicode injection
+        let executor = MockExecutor::new();
+        let err = Action::TypeText("a\u{2603}b".into())
+            .execute_blocking(&executor)
+            .unwrap_err();
+        assert!(format!("{err:#}").contains("cannot type '☃'"), "{err:#}");
+        // Typing stops at the character
+        assert_eq!(executor.calls(), tap(Key::A, false));
+    }
+
+    #[test]
+    fn type_text_injects_characters_off_the_layout_by_code_point() {
+        let executor = MockExecutor::new().with_unicode();
+        Action::TypeText("a\u{e9}\u{1F600}".into())
+            .execute_blocking(&executor)
+            .unwrap();
+        let expected = [
+            tap(Key::A, false),
+            vec![RecordedCall::Unicode('é'), RecordedCall::Unicode('😀')],
+        ]
+        .concat();
+        assert_eq!(executor.calls(), expected);
+    }
+
+    #[test]
+    fn mouse_moves_forward_their_arguments_inside_a_sequence() {
+        let executor = MockExecutor::new().with_position(50, 50);
+        Action::Sequence(vec![
+            Action::MouseMoveRel { dx: -20, dy: 15 },
+            Action::Click(MouseButton::Left),
+            Action::MouseMoveAbs { x: 640, y: -10 },
+        ])
+        .execute_blocking(&executor)
+        .unwrap();
+        assert_eq!(
+            executor.calls(),
+            [
+                RecordedCall::MoveRel { dx: -20, dy: 15 },
+                RecordedCall::Mouse(MouseButton::Left, InputState::Press),
+                RecordedCall::Mouse(MouseButton::Left, InputState::Release),
+                RecordedCall::MoveAbs { x: 640, y: -10 },
+            ]
+        );
+        assert_eq!(
+            ActionExecutor::current_position(&executor).unwrap(),
+            (640, -10)
+        );
+    }
+
+    fn path(jitter: u32) -> Action {
+        Action::MousePath {
+            points: vec![(100, 100), (200, 150), (300, 100)],
+            duration_per_segment: Duration::from_millis(100),
+            smooth: false,
+            jitter,
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn mouse_path_waits_between_points_only() {
+        let executor = MockExecutor::new().with_timing();
+        let started = tokio::time::Instant::now();
+        path(0).execute(&executor).await.unwrap();
+        let ms = Duration::from_millis;
+        assert_eq!(
+            executor.timed_calls(),
+            [
+                (ms(0), RecordedCall::MoveAbs { x: 100, y: 100 }),
+                (ms(100), RecordedCall::MoveAbs { x: 200, y: 150 }),
+                (ms(200), RecordedCall::MoveAbs { x: 300, y: 100 }),
+            ]
+        );
+        // No sleep after the last point
+        assert_eq!(started.elapsed(), ms(200));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn mouse_path_jitter_stays_within_bounds_per_seed() {
+        let moves = |seed| async move {
+            let executor = MockExecutor::new();
+            let ctx = ExecutionContext::default().with_seed(seed);
+            path(3).execute_with(&executor, &ctx).await.unwrap();
+            executor.calls()
+        };
+        let points = [(100, 100), (200, 150), (300, 100)];
+        let mut jittered = false;
+        for seed in 0..10 {
+            let calls = moves(seed).await;
+            assert_eq!(calls, moves(seed).await);
+            for (call, (x, y)) in calls.iter().zip(points) {
+                let RecordedCall::MoveAbs { x: cx, y: cy } = *call else {
+                    panic!("{call:?}");
+                };
+                assert!((cx - x).abs() <= 3 && (cy - y).abs() <= 3, "{call:?}");
+                jittered |= (cx, cy) != (x, y);
+            }
+        }
+        assert!(jittered);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn move_smooth_samples_every_frame_towards_the_target() {
+        let executor = MockExecutor::new().with_position(0, 100).with_timing();
+        let action = Action::MoveSmooth {
+            to: (300, 0),
+            duration: Duration::from_millis(160),
+            easing: Easing::EaseInOut,
+        };
+        action.execute(&executor).await.unwrap();
+        let calls = executor.timed_calls();
+        // One sample per 16ms frame, the last landing on the target
+        assert_eq!(calls.len(), 10);
+        let mut last = (0, 100);
+        for (i, (at, call)) in calls.iter().enumerate() {
+            assert_eq!(*at, MOVE_STEP * i as u32);
+            let RecordedCall::MoveAbs { x, y } = *call else {
+                panic!("{call:?}");
+            };
+            assert!(x >= last.0 && y <= last.1, "{last:?} -> {call:?}");
+            last = (x, y);
+        }
+        assert_eq!(last, (300, 0));
+        // Eased: the first frame covers less ground than a linear one would
+        assert!(matches!(calls[0].1, RecordedCall::MoveAbs { x, .. } if x < 30));
+    }
+
+    #[test]
+    fn easing_curves_run_from_start_to_end() {
+        let curves = [
+            Easing::Linear,
+            Easing::EaseInOut,
+            Easing::CubicBezier {
+                x1: 0.42,
+                y1: 0.0,
+                x2: 0.58,
+                y2: 1.0,
+            },
+        ];
+        for easing in curves {
+            assert!(easing.apply(0.0).abs() < 1e-6, "{easing:?}");
+            assert!((easing.apply(1.0) - 1.0).abs() < 1e-6, "{easing:?}");
+            let samples: Vec<f64> = (0..=20)
+                .map(|i| easing.apply(f64::from(i) / 20.0))
+                .collect();
+            assert!(samples.windows(2).all(|w| w[0] <= w[1]), "{easing:?}");
+        }
+        // Symmetric curves pass the midpoint halfway through
+        assert_eq!(Easing::EaseInOut.apply(0.5), 0.5);
+        let linear = Easing::CubicBezier {
+            x1: 0.0,
+            y1: 0.0,
+            x2: 1.0,
+            y2: 1.0,
+        };
+        assert!((linear.apply(0.3) - 0.3).abs() < 1e-6);
+    }
+
+    /// Offsets of each key press in a `TypeTextHumanized` run
+    async fn humanized_presses(seed: u64) -> Vec<Duration> {
+        let executor = MockExecutor::new().with_timing();
+        let action = Action::TypeTextHumanized {
+            text: "hello".into(),
+            mean: Duration::from_millis(100),
+            jitter: Duration::from_millis(40),
+        };
+        let ctx = ExecutionContext::default().with_seed(seed);
+        action.execute_with(&executor, &ctx).await.unwrap();
+        let calls = executor.timed_calls();
+        // Same keys as plain TypeText
+        let keys: Vec<RecordedCall> = calls.iter().map(|(_, call)| call.clone()).collect();
+        let plain = MockExecutor::new();
+        Action::TypeText("hello".into())
+            .execute(&plain)
+            .await
+            .unwrap();
+        assert_eq!(keys, plain.calls());
+        calls
+            .into_iter()
+            .filter(|(_, call)| matches!(call, RecordedCall::Key(_, InputState::Press)))
+            .map(|(at, _)| at)
+            .collect()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn humanized_typing_pauses_within_jitter_of_the_mean() {
+        for seed in 0..10 {
+            let presses = humanized_presses(seed).await;
+            assert_eq!(presses.len(), 5);
+            assert_eq!(presses[0], Duration::ZERO);
+            for gap in presses.windows(2).map(|w| w[1] - w[0]) {
+                let range = Duration::from_millis(60)..=Duration::from_millis(140);
+                assert!(range.contains(&gap), "seed {seed}: {gap:?}");
+            }
+            // A fixed seed gives the same pauses every time
+            assert_eq!(humanized_presses(seed).await, presses);
+        }
+    }
+
+    fn presses_of(executor: &MockExecutor, k: Key) -> usize {
+        let press = key(k, InputState::Press);
+        executor
+            .calls()
+            .iter()
+            .filter(|call| **call == press)
+            .count()
+    }
+
+    #[test]
+    fn loop_runs_its_actions_count_times() {
+        let w = || Action::PressKey(Key::W);
+        for count in [0, 1, 5] {
+            let executor = MockExecutor::new();
+            let action = Action::Loop {
+                actions: vec![w()],
+                count,
+            };
+            action.execute_blocking(&executor).unwrap();
+            assert_eq!(presses_of(&executor, Key::W), count as usize);
+        }
+        // Nested loops multiply, and the inner sequence keeps its order
+        let executor = MockExecutor::new();
+        Action::Loop {
+            actions: vec![
+                Action::Loop {
+                    actions: vec![w()],
+                    count: 3,
+                },
+                Action::PressKey(Key::A),
+            ],
+            count: 4,
+        }
+        .execute_blocking(&executor)
+        .unwrap();
+        assert_eq!(presses_of(&executor, Key::W), 12);
+        assert_eq!(presses_of(&executor, Key::A), 4);
+        let mut pass = Vec::new();
+        for _ in 0..3 {
+            pass.extend(tap(Key::W, false));
+        }
+        pass.extend(tap(Key::A, false));
+        let expected: Vec<RecordedCall> = (0..4).flat_map(|_| pass.clone()).collect();
+        assert_eq!(executor.calls(), expected);
+    }
+
+    fn holding(keys: Vec<Key>) -> ExecutionContext {
+        ExecutionContext::default().with_held_input(HeldInput {
+            keys,
+            buttons: Vec::new(),
+        })
+    }
+
+    #[tokio::test]
+    async fn release_all_releases_exactly_what_is_held() {
+        let executor = MockExecutor::new();
+        let ctx = holding(vec![Key::Ctrl, Key::A]);
+        Action::ReleaseAll
+            .execute_with(&executor, &ctx)
+            .await
+            .unwrap();
+        use InputState::Release;
+        assert_eq!(
+            executor.calls(),
+            vec![key(Key::A, Release), key(Key::Ctrl, Release)]
+        );
+        assert!(ctx.input.lock().unwrap().keys.is_empty());
+        // Nothing left to release the second time
+        Action::ReleaseAll
+            .execute_with(&executor, &ctx)
+            .await
+            .unwrap();
+        assert_eq!(executor.calls().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn toggle_key_presses_then_releases() {
+        let executor = MockExecutor::new();
+        let ctx = ExecutionContext::default();
+        let toggle = Action::ToggleKey(Key::Shift);
+        toggle.execute_with(&executor, &ctx).await.unwrap();
+        assert!(ctx.input.lock().unwrap().is_key_held(&Key::Shift));
+        toggle.execute_with(&executor, &ctx).await.unwrap();
+        use InputState::{Press, Release};
+        assert_eq!(
+            executor.calls(),
+            vec![key(Key::Shift, Press), key(Key::Shift, Release)]
+        );
+        assert!(!ctx.input.lock().unwrap().is_key_held(&Key::Shift));
+    }
+
+    #[tokio::test]
+    async fn if_held_picks_a_branch_from_the_held_state() {
+        // `then` lets go of Shift; the check must not flip to `otherwise`
+        let action = Action::IfHeld {
+            key: Key::Shift,
+            then: Box::new(Action::Sequence(vec![
+                Action::ReleaseKey(Key::Shift),
+                Action::PressKey(Key::A),
+            ])),
+            otherwise: Some(Box::new(Action::PressKey(Key::B))),
+        };
+        use InputState::Release;
+        let executor = MockExecutor::new();
+        let ctx = holding(vec![Key::Shift]);
+        action.execute_with(&executor, &ctx).await.unwrap();
+        let mut expected = vec![key(Key::Shift, Release)];
+        expected.extend(tap(Key::A, false));
+        assert_eq!(executor.calls(), expected);
+
+        let executor = MockExecutor::new();
+        action
+            .execute_with(&executor, &ExecutionContext::default())
+            .await
+            .unwrap();
+        assert_eq!(executor.calls(), tap(Key::B, false));
+
+        // No `otherwise` means nothing happens
+        let executor = MockExecutor::new();
+        let action = Action::IfHeld {
+            key: Key::Shift,
+            then: Box::new(Action::PressKey(Key::A)),
+            otherwise: None,
+        };
+        action
+            .execute_with(&executor, &ExecutionContext::default())
+            .await
+            .unwrap();
+        assert!(executor.calls().is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn with_timeout_stops_the_action_and_releases_its_keys() {
+        let executor = MockExecutor::new();
+        // Ctrl was down before; only the inner action's keys are released
+        let ctx = ExecutionContext::default();
+        ctx.record_key(Key::Ctrl, InputState::Press);
+        let limit = Duration::from_millis(100);
+        let action = Action::WithTimeout {
+            action: Box::new(Action::Sequence(vec![
+                Action::HoldKey(Key::A),
+                Action::Delay(Duration::from_secs(10)),
+                Action::ReleaseKey(Key::A),
+            ])),
+            limit,
+        };
+        let started = tokio::time::Instant::now();
+        let err = action.execute_with(&executor, &ctx).await.unwrap_err();
+        assert_eq!(started.elapsed(), limit);
+        assert!(format!("{err:#}").contains("timed out"), "{err:#}");
+        use InputState::{Press, Release};
+        assert_eq!(
+            executor.calls(),
+            vec![key(Key::A, Press), key(Key::A, Release)]
+        );
+        let input = ctx.input.lock().unwrap();
+        assert!(input.is_key_held(&Key::Ctrl) && !input.is_key_held(&Key::A));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn with_timeout_lets_a_quick_action_finish() {
+        let executor = MockExecutor::new();
+        let action = Action::WithTimeout {
+            action: Box::new(Action::Sequence(vec![
+                Action::Delay(Duration::from_millis(50)),
+                Action::PressKey(Key::A),
+            ])),
+            limit: Duration::from_millis(100),
+        };
+        action
+            .execute_with(&executor, &ExecutionContext::default())
+            .await
+            .unwrap();
+        assert_eq!(executor.calls(), tap(Key::A, false));
+    }
+
+    /// Number of events in each injection call, single events counting as 1
+    #[derive(Default)]
+    struct BatchSizes(Mutex<Vec<usize>>);
+
+    impl ActionExecutor for BatchSizes {
+        fn simulate_key(&self, _key: Key, _state: InputState) -> Result<(), ExecutorError> {
+            self.0.lock().unwrap().push(1);
+            Ok(())
+        }
+
+        fn simulate_mouse(
+            &self,
+            _button: MouseButton,
+            _state: InputState,
+        ) -> Result<(), ExecutorError> {
+            self.0.lock().unwrap().push(1);
+            Ok(())
+        }
+
+        fn simulate_batch(&self, events: &[(InputKind, InputState)]) -> Result<(), ExecutorError> {
+            self.0.lock().unwrap().push(events.len());
+            Ok(())
+        }
+
+        fn mouse_move_abs(&self, _x: i32, _y: i32) -> Result<(), ExecutorError> {
+            Ok(())
+        }
+
+        fn mouse_move_rel(&self, _dx: i32, _dy: i32) -> Result<(), ExecutorError> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn taps_and_runs_of_taps_go_out_as_one_batch() {
+        let cases = [
+            (Action::Click(MouseButton::Left), vec![2]),
+            (Action::PressKey(Key::A), vec![2]),
+            (
+                Action::Sequence(vec![
+                    Action::PressKey(Key::A),
+                    Action::Click(MouseButton::Right),
+                    Action::MouseMoveAbs { x: 1, y: 1 },
+                    Action::PressKey(Key::B),
+                ]),
+                vec![4, 2],
+            ),
+        ];
+        for (action, sizes) in cases {
+            let executor = BatchSizes::default();
+            action.execute_blocking(&executor).unwrap();
+            assert_eq!(*executor.0.lock().unwrap(), sizes, "{}", action.kind());
+        }
+    }
+
+    #[test]
+    fn preserve_cursor_returns_to_where_the_cursor_was() {
+        let executor = MockExecutor::new().with_position(40, 60);
+        Action::PreserveCursor(Box::new(Action::Sequence(vec![
+            Action::MouseMoveAbs { x: 500, y: 10 },
+            Action::Click(MouseButton::Left),
+        ])))
+        .execute_blocking(&executor)
+        .unwrap();
+        assert!(matches!(
+            executor.calls().last(),
+            Some(RecordedCall::MoveAbs { x: 40, y: 60 })
+        ));
+        assert_eq!(
+            ActionExecutor::current_position(&executor).unwrap(),
+            (40, 60)
+        );
+    }
+
+    #[test]
+    fn set_lock_only_changes_a_lock_in_the_wrong_state() {
+        let on = Action::SetLock {
+            lock: LockKey::CapsLock,
+            on: true,
+        };
+        let executor = MockExecutor::new().with_lock_on(LockKey::CapsLock);
+        on.execute_blocking(&executor).unwrap();
+        assert!(executor.calls().is_empty());
+
+        let executor = MockExecutor::new();
+        on.execute_blocking(&executor).unwrap();
+        // Already on now, so the second run leaves it alone
+        on.execute_blocking(&executor).unwrap();
+        assert!(matches!(
+            executor.calls()[..],
+            [RecordedCall::SetLock {
+                lock: LockKey::CapsLock,
+                on: true
+            }]
+        ));
+        assert!(ActionExecutor::lock_state(&executor, LockKey::CapsLock).unwrap());
+    }
+
+    /// The paste shortcut with V at `v`
+    fn paste_keys(v: Key) -> Vec<RecordedCall> {
+        use InputState::{Press, Release};
+        vec![
+            key(PASTE_MODIFIER, Press),
+            key(v, Press),
+            key(v, Release),
+            key(PASTE_MODIFIER, Release),
+        ]
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn paste_text_sets_the_clipboard_then_sends_the_shortcut() {
+        let executor = MockExecutor::new();
+        let clipboard = Arc::new(MockClipboard::with_text("before"));
+        let ctx = ExecutionContext::default().with_clipboard(clipboard.clone());
+        let paste = |restore| Action::PasteText {
+            text: "hello".into(),
+            restore,
+        };
+        paste(false).execute_with(&executor, &ctx).await.unwrap();
+        assert_eq!(clipboard.history(), ["hello"]);
+        assert_eq!(executor.calls(), paste_keys(Key::V));
+
+        // Restoring puts the old text back once the target has read it
+        let executor = MockExecutor::new();
+        let clipboard = Arc::new(MockClipboard::with_text("before"));
+        let ctx = ExecutionContext::default().with_clipboard(clipboard.clone());
+        let started = tokio::time::Instant::now();
+        paste(true).execute_with(&executor, &ctx).await.unwrap();
+        assert_eq!(started.elapsed(), PASTE_SETTLE);
+        assert_eq!(clipboard.history(), ["hello", "before"]);
+        assert_eq!(executor.calls(), paste_keys(Key::V));
+    }
+
+    #[tokio::test]
+    async fn paste_text_finds_v_on_the_layout() {
+        let executor = MockExecutor::new();
+        let ctx = ExecutionContext {
+            layout: Arc::new(Dvorak),
+            ..ExecutionContext::default().with_clipboard(Arc::new(MockClipboard::new()))
+        };
+        Action::PasteText {
+            text: "hello".into(),
+            restore: false,
+        }
+        .execute_with(&executor, &ctx)
+        .await
+        .unwrap();
+        assert_eq!(executor.calls(), paste_keys(Key::Period));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn run_command_spawns_without_waiting_for_the_child() {
+        let marker = std::env::temp_dir().join(format!("run-command-{}", std::process::id()));
+        let _ = std::fs::remove_file(&marker);
+        let action = Action::RunCommand {
+            program: "sh".into(),
+            args: vec![
+                "-c".into(),
+                format!("sleep 1 && touch '{}'", marker.display()),
+            ],
+        };
+        let started = std::time::Instant::now();
+        action.execute(&MockExecutor::new()).await.unwrap();
+        assert!(started.elapsed() < Duration::from_millis(500));
+        assert!(!marker.exists());
+        // The child carries on after the action returns
+        for _ in 0..100 {
+            if marker.exists() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        assert!(marker.exists());
+        std::fs::remove_file(&marker).unwrap();
+    }
+
+    #[test]
+    fn run_command_failure_does_not_stop_the_macro() {
+        let executor = MockExecutor::new();
+        Action::Sequence(vec![
+            Action::RunCommand {
+                program: "no-such-program-for-tests".into(),
+                args: Vec::new(),
+            },
+            Action::PressKey(Key::A),
+        ])
+        .execute_blocking(&executor)
+        .unwrap();
+        assert_eq!(executor.calls(), tap(Key::A, false));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn wait_for_key_resumes_on_that_key_only() {
+        let executor = MockExecutor::new();
+        let (events, _) = broadcast::channel(16);
+        let ctx = ExecutionContext::default().with_events(events.clone());
+        let action = Action::Sequence(vec![
+            Action::HoldKey(Key::Ctrl),
+            Action::WaitForKey {
+                key: Key::Enter,
+                timeout: None,
+            },
+            Action::PressKey(Key::A),
+            Action::ReleaseKey(Key::Ctrl),
+        ]);
+        let feed = async {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            events.send(InputEvent::KeyPress(Key::Space)).unwrap();
+            events.send(InputEvent::KeyRelease(Key::Enter)).unwrap();
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            // Still waiting, with Ctrl held through the wait
+            assert_eq!(executor.calls(), [key(Key::Ctrl, InputState::Press)]);
+            events.send(InputEvent::KeyPress(Key::Enter)).unwrap();
+        };
+        let (result, ()) = tokio::join!(action.execute_with(&executor, &ctx), feed);
+        result.unwrap();
+        let mut expected = vec![key(Key::Ctrl, InputState::Press)];
+        expected.extend(tap(Key::A, false));
+        expected.push(key(Key::Ctrl, InputState::Release));
+        assert_eq!(executor.calls(), expected);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn wait_for_key_times_out_or_needs_a_stream() {
+        let executor = MockExecutor::new();
+        let limit = Duration::from_millis(200);
+        let action = Action::WaitForKey {
+            key: Key::Enter,
+            timeout: Some(limit),
+        };
+        let (events, _) = broadcast::channel(16);
+        let ctx = ExecutionContext::default().with_events(events);
+        let started = tokio::time::Instant::now();
+        let err = action.execute_with(&executor, &ctx).await.unwrap_err();
+        assert_eq!(started.elapsed(), limit);
+        assert!(format!("{err:#}").contains("timed out"), "{err:#}");
+
+        let err = action.execute(&executor).await.unwrap_err();
+        assert!(format!("{err:#}").contains("event stream"), "{err:#}");
+    }
+
+    /// Span recorded by `SpanCapture`, with its fields as text
+    #[derive(Debug, Default)]
+    struct CapturedSpan {
+        name: &'static str,
+        parent: Option<usize>,
+        fields: std::collections::HashMap<&'static str, String>,
+    }
+
+    impl tracing::field::Visit for CapturedSpan {
+        fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+            self.fields.insert(field.name(), value.to_owned());
+        }
+
+        fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+            self.fields.insert(field.name(), format!("{value:?}"));
+        }
+    }
+
+    /// Layer keeping every span in creation order; parents are indexes
+    #[derive(Clone, Default)]
+    struct SpanCapture(Arc<Mutex<Vec<CapturedSpan>>>);
+
+    impl<S> tracing_subscriber::Layer<S> for SpanCapture
+    where
+        S: tracing::Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'a>,
+    {
+        fn on_new_span(
+            &self,
+            attrs: &tracing::span::Attributes<'_>,
+            id: &tracing::span::Id,
+            ctx: tracing_subscriber::layer::Context<'_, S>,
+        ) {
+            let span = ctx.span(id).unwrap();
+            let mut captured = CapturedSpan {
+                name: attrs.metadata().name(),
+                parent: span
+                    .parent()
+                    .map(|parent| *parent.extensions().get::<usize>().unwrap()),
+                ..CapturedSpan::default()
+            };
+            attrs.record(&mut captured);
+            let mut spans = self.0.lock().unwrap();
+            span.extensions_mut().insert(spans.len());
+            spans.push(captured);
+        }
+
+        fn on_record(
+            &self,
+            id: &tracing::span::Id,
+            values: &tracing::span::Record<'_>,
+            ctx: tracing_subscriber::layer::Context<'_, S>,
+        ) {
+            let index = *ctx.span(id).unwrap().extensions().get::<usize>().unwrap();
+            values.record(&mut self.0.lock().unwrap()[index]);
+        }
+    }
+
+    #[test]
+    fn spans_name_the_key_and_nest_under_sequences_and_loops() {
+        use tracing_subscriber::layer::SubscriberExt;
+        let capture = SpanCapture::default();
+        let subscriber = tracing_subscriber::registry().with(capture.clone());
+        let action = Action::Sequence(vec![
+            Action::PressKey(Key::A),
+            Action::Loop {
+                actions: vec![Action::Click(MouseButton::Left)],
+                count: 2,
+            },
+        ]);
+        tracing::subscriber::with_default(subscriber, || {
+            action.execute_blocking(&MockExecutor::new()).unwrap();
+        });
+        let spans = capture.0.lock().unwrap();
+        let summary: Vec<_> = spans
+            .iter()
+            .map(|span| {
+                (
+                    span.name,
+                    span.fields.get("kind").map(String::as_str),
+                    span.parent,
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("action", Some("Sequence"), None),
+                ("step", None, Some(0)),
+                ("action", Some("PressKey"), Some(1)),
+                ("step", None, Some(0)),
+                ("action", Some("Loop"), Some(3)),
+                ("iteration", None, Some(4)),
+                ("action", Some("Click"), Some(5)),
+                ("iteration", None, Some(4)),
+                ("action", Some("Click"), Some(7)),
+            ]
+        );
+        assert_eq!(spans[2].fields["key"], "A");
+        assert_eq!(spans[6].fields["button"], "Left");
+        // Every action records how long it took
+        for span in spans.iter().filter(|span| span.name == "action") {
+            assert!(span.fields["elapsed_ms"].parse::<u64>().is_ok(), "{span:?}");
+        }
+    }
+
+    fn drag(steps: u32) -> Action {
+        Action::MouseDrag {
+            button: MouseButton::Left,
+            from: (0, 0),
+            to: (100, 50),
+            steps,
+        }
+    }
+
+    #[test]
+    fn mouse_drag_moves_in_even_steps_between_press_and_release() {
+        let executor = MockExecutor::new();
+        drag(10).execute_blocking(&executor).unwrap();
+        let moves = (1..=10).map(|i| RecordedCall::MoveAbs {
+            x: i * 10,
+            y: i * 5,
+        });
+        let expected: Vec<RecordedCall> = [
+            RecordedCall::MoveAbs { x: 0, y: 0 },
+            RecordedCall::Mouse(MouseButton::Left, InputState::Press),
+        ]
+        .into_iter()
+        .chain(moves)
+        .chain([RecordedCall::Mouse(MouseButton::Left, InputState::Release)])
+        .collect();
+        assert_eq!(executor.calls(), expected);
+    }
+
+    #[test]
+    fn mouse_drag_without_steps_jumps_to_the_end() {
+        let executor = MockExecutor::new();
+        drag(0).execute_blocking(&executor).unwrap();
+        assert_eq!(
+            executor.calls(),
+            [
+                RecordedCall::MoveAbs { x: 0, y: 0 },
+                RecordedCall::Mouse(MouseButton::Left, InputState::Press),
+                RecordedCall::MoveAbs { x: 100, y: 50 },
+                RecordedCall::Mouse(MouseButton::Left, InputState::Release),
+            ]
+        );
+    }
+
+    #[test]
+    fn mouse_drag_releases_the_button_when_a_move_fails() {
+        // Call 4 is the second step of the drag
+        let executor = MockExecutor::failing_at(4);
+        assert!(drag(10).execute_blocking(&executor).is_err());
+        assert_eq!(
+            executor.calls(),
+            [
+                RecordedCall::MoveAbs { x: 0, y: 0 },
+                RecordedCall::Mouse(MouseButton::Left, InputState::Press),
+                RecordedCall::MoveAbs { x: 10, y: 5 },
+                RecordedCall::Mouse(MouseButton::Left, InputState::Release),
+            ]
+        );
+    }
+
+    #[test]
+    fn key_combo_presses_in_order_and_releases_in_reverse() {
+        let executor = MockExecutor::new();
+        let action = Action::KeyCombo {
+            modifiers: vec![Modifier::Ctrl, Modifier::Shift],
+            key: Key::T,
+        };
+        action.execute_blocking(&executor).unwrap();
+        use InputState::{Press, Release};
+        assert_eq!(
+            executor.calls(),
+            [
+                key(Key::Ctrl, Press),
+                key(Key::Shift, Press),
+                key(Key::T, Press),
+                key(Key::T, Release),
+                key(Key::Shift, Release),
+                key(Key::Ctrl, Release),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn key_combo_releases_modifiers_when_the_key_fails() {
+        // Calls 1 and 2 press the modifiers, 3 is the key press
+        let executor = MockExecutor::failing_at(3);
+        let action = Action::KeyCombo {
+            modifiers: vec![Modifier::Ctrl, Modifier::Alt],
+            key: Key::Delete,
+        };
+        let ctx = ExecutionContext::default();
+        let result = action.execute_with(&executor, &ctx).await;
+        assert!(result.is_err());
+        use InputState::{Press, Release};
+        assert_eq!(
+            executor.calls(),
+            [
+                key(Key::Ctrl, Press),
+                key(Key::Alt, Press),
+                key(Key::Alt, Release),
+                key(Key::Ctrl, Release),
+            ]
+        );
+        assert!(ctx.input.lock().unwrap().keys.is_empty());
+        assert!(ctx.pressed.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn key_combo_releases_pressed_modifiers_when_one_fails() {
+        let executor = MockExecutor::failing_at(2);
+        let action = Action::KeyCombo {
+            modifiers: vec![Modifier::Ctrl, Modifier::Shift],
+            key: Key::Escape,
+        };
+        assert!(action.execute_blocking(&executor).is_err());
+        assert_eq!(
+            executor.calls(),
+            [
+                key(Key::Ctrl, InputState::Press),
+                key(Key::Ctrl, InputState::Release),
+            ]
+        );
+    }
+
+    #[test]
+    fn monitor_relative_moves_map_to_global_coordinates() {
+        // Secondary monitor to the left of the primary, so its x is negative
+        let left = Monitor {
+            x: -1920,
+            y: 0,
+            width: 1920,
+            height: 1080,
+        };
+        let primary = Monitor {
+            x: 0,
+            y: 0,
+            width: 2560,
+            height: 1440,
+        };
+        let executor = MockExecutor::new().with_monitors(vec![left, primary]);
+        ActionExecutor::mouse_move_to_monitor(&executor, 0, 100, 200).unwrap();
+        ActionExecutor::mouse_move_to_monitor(&executor, 1, 100, 200).unwrap();
+        assert_eq!(
+            executor.calls(),
+            [
+                RecordedCall::MoveAbs { x: -1820, y: 200 },
+                RecordedCall::MoveAbs { x: 100, y: 200 },
+            ]
+        );
+        let err = ActionExecutor::mouse_move_to_monitor(&executor, 2, 0, 0).unwrap_err();
+        assert!(
+            err.to_string().contains("no monitor 2 (2 connected)"),
+            "{err}"
+        );
     }
 }
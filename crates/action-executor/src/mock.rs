@@ -0,0 +1,394 @@
+// Recording executor and clipboard for asserting what actions emit without
+// an OS backend
+
+use crate::{
+    ActionExecutor, Clipboard, ExecutorError, InputState, Key, LockKey, Monitor, MouseButton,
+};
+use anyhow::Result;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Low-level call recorded by `MockExecutor`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecordedCall {
+    Key(Key, InputState),
+    Mouse(MouseButton, InputState),
+    MoveAbs { x: i32, y: i32 },
+    MoveRel { dx: i32, dy: i32 },
+    Scroll { dx: i32, dy: i32 },
+    Unicode(char),
+    SetLock { lock: LockKey, on: bool },
+}
+
+/// Executor that records calls instead of emitting input
+#[derive(Debug, Default)]
+pub struct MockExecutor {
+    calls: Mutex<Vec<RecordedCall>>,
+    // Set by `with_timing`: when the clock started, and each call's offset
+    // from it
+    started: Option<Instant>,
+    times: Mutex<Vec<Duration>>,
+    // 1-based call number that fails instead of being recorded
+    fail_at: Option<usize>,
+    attempts: Mutex<usize>,
+    // Cursor position, moved by recorded mouse moves
+    position: Mutex<(i32, i32)>,
+    // Lock keys reported as on
+    locks: Mutex<Vec<LockKey>>,
+    monitors: Vec<Monitor>,
+    windows: Vec<String>,
+    // Whether `send_unicode` is supported, as on Windows
+    unicode: bool,
+}
+
+impl MockExecutor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fail the `n`th call (counting from 1) to exercise error paths
+    pub fn failing_at(n: usize) -> Self {
+        Self {
+            fail_at: Some(n),
+            ..Self::default()
+        }
+    }
+
+    /// Also note when each call was made, on tokio's clock
+    ///
+    /// Under `tokio::time::pause()` the clock only moves when every task is
+    /// waiting, so offsets are exact and tests stay fast.
+    pub fn with_timing(mut self) -> Self {
+        self.started = Some(Instant::now());
+        self
+    }
+
+    /// Start with the cursor at `(x, y)`
+    pub fn with_position(self, x: i32, y: i32) -> Self {
+        *self.position.lock().unwrap() = (x, y);
+        self
+    }
+
+    /// Report these monitors, in this order
+    pub fn with_monitors(mut self, monitors: Vec<Monitor>) -> Self {
+        self.monitors = monitors;
+        self
+    }
+
+    /// Report these window titles
+    pub fn with_windows(mut self, titles: Vec<String>) -> Self {
+        self.windows = titles;
+        self
+    }
+
+    /// Accept `send_unicode`, recording each character
+    pub fn with_unicode(mut self) -> Self {
+        self.unicode = true;
+        self
+    }
+
+    /// Report `lock` as toggled on
+    pub fn with_lock_on(self, lock: LockKey) -> Self {
+        self.locks.lock().unwrap().push(lock);
+        self
+    }
+
+    /// Every call recorded so far, in order
+    pub fn calls(&self) -> Vec<RecordedCall> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    /// Every call recorded so far with its offset from `with_timing`
+    ///
+    /// Empty unless the executor was built `with_timing`.
+    pub fn timed_calls(&self) -> Vec<(Duration, RecordedCall)> {
+        let times = self.times.lock().unwrap();
+        times.iter().copied().zip(self.calls()).collect()
+    }
+
+    fn record(&self, call: RecordedCall) -> Result<(), ExecutorError> {
+        let mut attempts = self.attempts.lock().unwrap();
+        *attempts += 1;
+        if self.fail_at == Some(*attempts) {
+            return Err(ExecutorError::Backend(format!(
+                "injected failure at call {attempts}: {call:?}"
+            )));
+        }
+        if let Some(started) = self.started {
+            self.times.lock().unwrap().push(started.elapsed());
+        }
+        self.calls.lock().unwrap().push(call);
+        Ok(())
+    }
+}
+
+impl ActionExecutor for MockExecutor {
+    fn simulate_key(&self, key: Key, state: InputState) -> Result<(), ExecutorError> {
+        self.record(RecordedCall::Key(key, state))
+    }
+
+    fn simulate_mouse(&self, button: MouseButton, state: InputState) -> Result<(), ExecutorError> {
+        self.record(RecordedCall::Mouse(button, state))
+    }
+
+    fn send_unicode(&self, c: char) -> Result<(), ExecutorError> {
+        if !self.unicode {
+            return Err(ExecutorError::Unsupported("unicode injection"));
+        }
+        self.record(RecordedCall::Unicode(c))
+    }
+
+    fn mouse_move_abs(&self, x: i32, y: i32) -> Result<(), ExecutorError> {
+        self.record(RecordedCall::MoveAbs { x, y })?;
+        *self.position.lock().unwrap() = (x, y);
+        Ok(())
+    }
+
+    fn mouse_move_rel(&self, dx: i32, dy: i32) -> Result<(), ExecutorError> {
+        self.record(RecordedCall::MoveRel { dx, dy })?;
+        let mut position = self.position.lock().unwrap();
+        *position = (position.0 + dx, position.1 + dy);
+        Ok(())
+    }
+
+    fn scroll(&self, dx: i32, dy: i32) -> Result<(), ExecutorError> {
+        self.record(RecordedCall::Scroll { dx, dy })
+    }
+
+    fn current_position(&self) -> Result<(i32, i32), ExecutorError> {
+        Ok(*self.position.lock().unwrap())
+    }
+
+    fn monitors(&self) -> Result<Vec<Monitor>, ExecutorError> {
+        Ok(self.monitors.clone())
+    }
+
+    fn window_titles(&self) -> Result<Vec<String>, ExecutorError> {
+        Ok(self.windows.clone())
+    }
+
+    fn lock_state(&self, lock: LockKey) -> Result<bool, ExecutorError> {
+        Ok(self.locks.lock().unwrap().contains(&lock))
+    }
+
+    fn set_lock(&self, lock: LockKey, on: bool) -> Result<(), ExecutorError> {
+        self.record(RecordedCall::SetLock { lock, on })?;
+        let mut locks = self.locks.lock().unwrap();
+        locks.retain(|&l| l != lock);
+        if on {
+            locks.push(lock);
+        }
+        Ok(())
+    }
+}
+
+/// Clipboard held in memory, remembering every text set on it
+#[derive(Debug, Default)]
+pub struct MockClipboard {
+    text: Mutex<Option<String>>,
+    history: Mutex<Vec<String>>,
+}
+
+impl MockClipboard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start out holding `text`
+    pub fn with_text(text: &str) -> Self {
+        Self {
+            text: Mutex::new(Some(text.to_owned())),
+            ..Self::default()
+        }
+    }
+
+    /// Every text set so far, in order
+    pub fn history(&self) -> Vec<String> {
+        self.history.lock().unwrap().clone()
+    }
+}
+
+impl Clipboard for MockClipboard {
+    fn get_text(&self) -> Result<Option<String>> {
+        Ok(self.text.lock().unwrap().clone())
+    }
+
+    fn set_text(&self, text: &str) -> Result<()> {
+        *self.text.lock().unwrap() = Some(text.to_owned());
+        self.history.lock().unwrap().push(text.to_owned());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Action, ExecutionContext, ExecutorError};
+    use std::sync::atomic::AtomicBool;
+    use std::sync::Arc;
+
+    #[test]
+    fn calls_records_in_order() {
+        let executor = MockExecutor::new();
+        let action = Action::Sequence(vec![
+            Action::PressKey(Key::A),
+            Action::MouseMoveAbs { x: 5, y: 6 },
+            Action::Click(MouseButton::Right),
+            Action::Scroll { amount: -2 },
+        ]);
+        action.execute_blocking(&executor).unwrap();
+        assert_eq!(
+            executor.calls(),
+            [
+                RecordedCall::Key(Key::A, InputState::Press),
+                RecordedCall::Key(Key::A, InputState::Release),
+                RecordedCall::MoveAbs { x: 5, y: 6 },
+                RecordedCall::Mouse(MouseButton::Right, InputState::Press),
+                RecordedCall::Mouse(MouseButton::Right, InputState::Release),
+                RecordedCall::Scroll { dx: 0, dy: -2 },
+            ]
+        );
+    }
+
+    #[test]
+    fn current_position_returns_the_stored_position() {
+        let executor = MockExecutor::new();
+        assert_eq!(ActionExecutor::current_position(&executor).unwrap(), (0, 0));
+        let executor = MockExecutor::new().with_position(-20, 300);
+        assert_eq!(
+            ActionExecutor::current_position(&executor).unwrap(),
+            (-20, 300)
+        );
+        // Reading it injects nothing
+        assert!(executor.calls().is_empty());
+    }
+
+    #[test]
+    fn moves_update_position() {
+        let executor = MockExecutor::new().with_position(10, 10);
+        ActionExecutor::mouse_move_rel(&executor, 5, -3).unwrap();
+        assert_eq!(
+            ActionExecutor::current_position(&executor).unwrap(),
+            (15, 7)
+        );
+        ActionExecutor::mouse_move_abs(&executor, 0, 0).unwrap();
+        assert_eq!(ActionExecutor::current_position(&executor).unwrap(), (0, 0));
+    }
+
+    #[test]
+    fn failing_at_fails_that_call_only() {
+        let executor = MockExecutor::failing_at(2);
+        ActionExecutor::simulate_key(&executor, Key::A, InputState::Press).unwrap();
+        let err = ActionExecutor::simulate_key(&executor, Key::B, InputState::Press).unwrap_err();
+        assert!(matches!(err, ExecutorError::Backend(_)), "{err:?}");
+        ActionExecutor::simulate_key(&executor, Key::C, InputState::Press).unwrap();
+        // The failed call is not recorded
+        assert_eq!(
+            executor.calls(),
+            [
+                RecordedCall::Key(Key::A, InputState::Press),
+                RecordedCall::Key(Key::C, InputState::Press),
+            ]
+        );
+    }
+
+    #[test]
+    fn failing_at_propagates_through_actions() {
+        let executor = MockExecutor::failing_at(3);
+        let action = Action::Sequence(vec![
+            Action::HoldKey(Key::A),
+            Action::MouseMoveRel { dx: 1, dy: 1 },
+            Action::MouseMoveRel { dx: 1, dy: 1 },
+            Action::ReleaseKey(Key::A),
+        ]);
+        let err = action.execute_blocking(&executor).unwrap_err();
+        assert!(
+            err.downcast_ref::<ExecutorError>()
+                .is_some_and(|err| matches!(err, ExecutorError::Backend(_))),
+            "{err:#}"
+        );
+        assert!(format!("{err:#}").contains("injected failure at call 3"));
+        // Execution stops at the failure
+        assert_eq!(
+            executor.calls(),
+            [
+                RecordedCall::Key(Key::A, InputState::Press),
+                RecordedCall::MoveRel { dx: 1, dy: 1 },
+            ]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn with_timing_records_virtual_time() {
+        let executor = MockExecutor::new().with_timing();
+        let action = Action::Sequence(vec![
+            Action::HoldKey(Key::A),
+            Action::Delay(Duration::from_millis(250)),
+            Action::ReleaseKey(Key::A),
+        ]);
+        action.execute(&executor).await.unwrap();
+        assert_eq!(
+            executor.timed_calls(),
+            [
+                (Duration::ZERO, RecordedCall::Key(Key::A, InputState::Press)),
+                (
+                    Duration::from_millis(250),
+                    RecordedCall::Key(Key::A, InputState::Release)
+                ),
+            ]
+        );
+        assert!(MockExecutor::new().timed_calls().is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn repeat_while_held_fires_at_interval() {
+        let executor = MockExecutor::new().with_timing();
+        let action = Action::RepeatWhileHeld {
+            actions: vec![Action::HoldKey(Key::A), Action::ReleaseKey(Key::A)],
+            interval: Duration::from_millis(100),
+            max_duration: Some(Duration::from_millis(350)),
+        };
+        let ctx = ExecutionContext {
+            held: Some(Arc::new(AtomicBool::new(true))),
+            ..ExecutionContext::default()
+        };
+        action.execute_with(&executor, &ctx).await.unwrap();
+        let presses: Vec<Duration> = executor
+            .timed_calls()
+            .into_iter()
+            .filter(|(_, call)| *call == RecordedCall::Key(Key::A, InputState::Press))
+            .map(|(at, _)| at)
+            .collect();
+        let expected: Vec<Duration> = (0..4).map(|i| Duration::from_millis(i * 100)).collect();
+        assert_eq!(presses, expected);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn random_delay_gap_stays_in_bounds() {
+        let (min, max) = (Duration::from_millis(30), Duration::from_millis(60));
+        let action = Action::Sequence(vec![
+            Action::HoldKey(Key::A),
+            Action::RandomDelay { min, max },
+            Action::HoldKey(Key::B),
+        ]);
+        for seed in 0..10 {
+            let executor = MockExecutor::new().with_timing();
+            let ctx = ExecutionContext::default().with_seed(seed);
+            action.execute_with(&executor, &ctx).await.unwrap();
+            let calls = executor.timed_calls();
+            let gap = calls[1].0 - calls[0].0;
+            assert!((min..=max).contains(&gap), "{gap:?}");
+        }
+    }
+
+    #[test]
+    fn clipboard_remembers_history() {
+        let clipboard = MockClipboard::with_text("before");
+        assert_eq!(clipboard.get_text().unwrap().as_deref(), Some("before"));
+        clipboard.set_text("one").unwrap();
+        clipboard.set_text("two").unwrap();
+        assert_eq!(clipboard.get_text().unwrap().as_deref(), Some("two"));
+        assert_eq!(clipboard.history(), ["one", "two"]);
+    }
+}
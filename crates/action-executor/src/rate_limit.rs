@@ -0,0 +1,330 @@
+// Injection rate limiting.
+//
+// A tight RepeatWhileHeld loop can emit events faster than the OS input
+// queue drains, and the overflow is dropped silently. The limiter spaces
+// events out with a token bucket, awaiting until a token frees up so a
+// limited action yields its runtime thread instead of blocking it.
+
+use crate::{
+    AsyncActionExecutor, ExecutorError, InputKind, InputState, Key, LockKey, Monitor, MouseButton,
+};
+use anyhow::Result;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Time source for `RateLimiter`, swappable so tests need not wait
+#[allow(async_fn_in_trait)]
+pub trait Clock {
+    fn now(&self) -> Instant;
+
+    async fn sleep(&self, duration: Duration);
+}
+
+/// Tokio's clock, which `tokio::time::pause` also controls
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TokioClock;
+
+impl Clock for TokioClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    async fn sleep(&self, duration: Duration) {
+        tokio::time::sleep(duration).await;
+    }
+}
+
+/// Executor wrapper capping injected events per second
+pub struct RateLimiter<E, C = TokioClock> {
+    inner: E,
+    clock: C,
+    bucket: Mutex<Bucket>,
+}
+
+struct Bucket {
+    per_sec: f64,
+    capacity: f64,
+    // Negative while callers are queued behind the rate
+    tokens: f64,
+    refilled: Instant,
+}
+
+impl Bucket {
+    /// Take `n` tokens, returning how long to wait before they are earned
+    fn take(&mut self, n: usize, now: Instant) -> Duration {
+        let earned = now.duration_since(self.refilled).as_secs_f64() * self.per_sec;
+        self.tokens = (self.tokens + earned).min(self.capacity);
+        self.refilled = now;
+        self.tokens -= n as f64;
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.per_sec)
+        }
+    }
+}
+
+impl<E: AsyncActionExecutor> RateLimiter<E> {
+    /// Allow at most `max_per_sec` events per second, evenly spaced
+    pub fn new(inner: E, max_per_sec: u32) -> Self {
+        let per_sec = f64::from(max_per_sec.max(1));
+        Self {
+            inner,
+            clock: TokioClock,
+            bucket: Mutex::new(Bucket {
+                per_sec,
+                capacity: 1.0,
+                tokens: 1.0,
+                refilled: Instant::now(),
+            }),
+        }
+    }
+}
+
+impl<E: AsyncActionExecutor, C: Clock> RateLimiter<E, C> {
+    /// Let up to `burst` events through back-to-back after an idle spell
+    pub fn with_burst(self, burst: u32) -> Self {
+        {
+            let mut bucket = self.bucket.lock().unwrap();
+            bucket.capacity = f64::from(burst.max(1));
+            bucket.tokens = bucket.capacity;
+        }
+        self
+    }
+
+    /// Measure and wait on `clock` instead of tokio's
+    pub fn with_clock<C2: Clock>(self, clock: C2) -> RateLimiter<E, C2> {
+        let mut bucket = self.bucket.into_inner().unwrap();
+        bucket.refilled = clock.now();
+        RateLimiter {
+            inner: self.inner,
+            clock,
+            bucket: Mutex::new(bucket),
+        }
+    }
+
+    pub fn into_inner(self) -> E {
+        self.inner
+    }
+
+    /// Wait until `n` events may be injected
+    async fn acquire(&self, n: usize) {
+        let wait = self.bucket.lock().unwrap().take(n, self.clock.now());
+        if !wait.is_zero() {
+            tracing::trace!(?wait, "rate limited");
+            self.clock.sleep(wait).await;
+        }
+    }
+}
+
+impl<E: AsyncActionExecutor, C: Clock> AsyncActionExecutor for RateLimiter<E, C> {
+    async fn simulate_key(&self, key: Key, state: InputState) -> Result<(), ExecutorError> {
+        self.acquire(1).await;
+        self.inner.simulate_key(key, state).await
+    }
+
+    async fn simulate_mouse(
+        &self,
+        button: MouseButton,
+        state: InputState,
+    ) -> Result<(), ExecutorError> {
+        self.acquire(1).await;
+        self.inner.simulate_mouse(button, state).await
+    }
+
+    async fn simulate_batch(
+        &self,
+        events: &[(InputKind, InputState)],
+    ) -> Result<(), ExecutorError> {
+        self.acquire(events.len()).await;
+        self.inner.simulate_batch(events).await
+    }
+
+    async fn simulate_scancode(&self, code: u16, state: InputState) -> Result<(), ExecutorError> {
+        self.acquire(1).await;
+        self.inner.simulate_scancode(code, state).await
+    }
+
+    async fn send_unicode(&self, c: char) -> Result<(), ExecutorError> {
+        self.acquire(1).await;
+        self.inner.send_unicode(c).await
+    }
+
+    async fn mouse_move_abs(&self, x: i32, y: i32) -> Result<(), ExecutorError> {
+        self.acquire(1).await;
+        self.inner.mouse_move_abs(x, y).await
+    }
+
+    async fn mouse_move_rel(&self, dx: i32, dy: i32) -> Result<(), ExecutorError> {
+        self.acquire(1).await;
+        self.inner.mouse_move_rel(dx, dy).await
+    }
+
+    async fn mouse_move_to_monitor(
+        &self,
+        monitor: usize,
+        x: i32,
+        y: i32,
+    ) -> Result<(), ExecutorError> {
+        self.acquire(1).await;
+        self.inner.mouse_move_to_monitor(monitor, x, y).await
+    }
+
+    async fn monitors(&self) -> Result<Vec<Monitor>, ExecutorError> {
+        self.inner.monitors().await
+    }
+
+    async fn scroll(&self, dx: i32, dy: i32) -> Result<(), ExecutorError> {
+        self.acquire(1).await;
+        self.inner.scroll(dx, dy).await
+    }
+
+    async fn current_position(&self) -> Result<(i32, i32), ExecutorError> {
+        self.inner.current_position().await
+    }
+
+    async fn window_titles(&self) -> Result<Vec<String>, ExecutorError> {
+        self.inner.window_titles().await
+    }
+
+    async fn pixel_color(&self, x: i32, y: i32) -> Result<(u8, u8, u8), ExecutorError> {
+        self.inner.pixel_color(x, y).await
+    }
+
+    async fn held_keys(&self) -> Result<Vec<Key>, ExecutorError> {
+        self.inner.held_keys().await
+    }
+
+    async fn lock_state(&self, lock: LockKey) -> Result<bool, ExecutorError> {
+        self.inner.lock_state(lock).await
+    }
+
+    async fn set_lock(&self, lock: LockKey, on: bool) -> Result<(), ExecutorError> {
+        self.acquire(1).await;
+        self.inner.set_lock(lock, on).await
+    }
+
+    fn block_input(&self, blocked: bool) -> Result<(), ExecutorError> {
+        self.inner.block_input(blocked)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MockExecutor, RecordedCall};
+    use std::sync::Arc;
+
+    /// Clock whose sleeps return at once, moving its time forward
+    struct FakeClock {
+        start: Instant,
+        elapsed: Mutex<Duration>,
+    }
+
+    impl FakeClock {
+        fn new() -> Arc<Self> {
+            Arc::new(Self {
+                start: Instant::now(),
+                elapsed: Mutex::default(),
+            })
+        }
+
+        fn elapsed(&self) -> Duration {
+            *self.elapsed.lock().unwrap()
+        }
+    }
+
+    impl Clock for Arc<FakeClock> {
+        fn now(&self) -> Instant {
+            self.start + self.elapsed()
+        }
+
+        async fn sleep(&self, duration: Duration) {
+            *self.elapsed.lock().unwrap() += duration;
+        }
+    }
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    /// When each of `count` back-to-back key presses went through
+    async fn press_times<C: Clock>(
+        limiter: &RateLimiter<MockExecutor, C>,
+        clock: &FakeClock,
+        count: usize,
+    ) -> Vec<Duration> {
+        let mut times = Vec::new();
+        for _ in 0..count {
+            limiter
+                .simulate_key(Key::A, InputState::Press)
+                .await
+                .unwrap();
+            times.push(clock.elapsed());
+        }
+        times
+    }
+
+    #[tokio::test]
+    async fn burst_is_spaced_at_the_configured_rate() {
+        let clock = FakeClock::new();
+        let limiter = RateLimiter::new(MockExecutor::new(), 10).with_clock(clock.clone());
+        let times = press_times(&limiter, &clock, 5).await;
+        assert_eq!(times, [ms(0), ms(100), ms(200), ms(300), ms(400)]);
+        // Nothing is dropped, only delayed
+        assert_eq!(limiter.into_inner().calls().len(), 5);
+    }
+
+    #[tokio::test]
+    async fn burst_allowance_passes_first_events_straight_through() {
+        let clock = FakeClock::new();
+        let limiter = RateLimiter::new(MockExecutor::new(), 20)
+            .with_burst(3)
+            .with_clock(clock.clone());
+        let times = press_times(&limiter, &clock, 5).await;
+        assert_eq!(times, [ms(0), ms(0), ms(0), ms(50), ms(100)]);
+    }
+
+    #[tokio::test]
+    async fn batch_waits_for_every_event_in_it() {
+        let clock = FakeClock::new();
+        let limiter = RateLimiter::new(MockExecutor::new(), 10).with_clock(clock.clone());
+        let key = InputKind::Key(Key::A);
+        let events = [(key, InputState::Press), (key, InputState::Release)];
+        limiter.simulate_batch(&events).await.unwrap();
+        limiter.simulate_batch(&events).await.unwrap();
+        // One token up front; the first batch owes one, the second two more
+        assert_eq!(clock.elapsed(), ms(300));
+        assert_eq!(
+            limiter.into_inner().calls()[..2],
+            [
+                RecordedCall::Key(Key::A, InputState::Press),
+                RecordedCall::Key(Key::A, InputState::Release),
+            ]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn waiting_yields_to_other_tasks() {
+        let limiter = RateLimiter::new(MockExecutor::new(), 1);
+        let started = Instant::now();
+        let ticker = async {
+            tokio::time::sleep(ms(10)).await;
+            started.elapsed()
+        };
+        let limited = async {
+            for _ in 0..2 {
+                limiter
+                    .simulate_key(Key::A, InputState::Press)
+                    .await
+                    .unwrap();
+            }
+            started.elapsed()
+        };
+        let (ticked, limited) = tokio::join!(ticker, limited);
+        // A blocking sleep would have held the ticker up for a second
+        assert_eq!(ticked, ms(10));
+        assert_eq!(limited, ms(1000));
+    }
+}
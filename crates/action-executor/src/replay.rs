@@ -0,0 +1,115 @@
+// Playback of recorded input at its original absolute timing
+
+use crate::{AsyncActionExecutor, ExecutorError, InputState};
+use anyhow::Result;
+use input_capture::{InputEvent, TimedEvent};
+use tokio::time::Instant;
+
+/// Replay recorded events against a fixed start time
+///
+/// Each event is scheduled at `start + event.at`. With `drift_correction`,
+/// events that are already late fire immediately so playback catches back
+/// up to the recorded cadence; without it, the schedule is pushed back by
+/// the accumulated lateness so the gaps between events are preserved.
+pub async fn replay(
+    events: &[TimedEvent],
+    executor: &impl AsyncActionExecutor,
+    drift_correction: bool,
+) -> Result<()> {
+    let mut start = Instant::now();
+
+    for timed in events {
+        let due = start + timed.at;
+        let now = Instant::now();
+        if now < due {
+            tokio::time::sleep_until(due).await;
+        } else if !drift_correction {
+            start += now - due;
+        }
+
+        inject(&timed.event, executor).await?;
+    }
+
+    Ok(())
+}
+
+async fn inject(
+    event: &InputEvent,
+    executor: &impl AsyncActionExecutor,
+) -> Result<(), ExecutorError> {
+    match event {
+        InputEvent::KeyPress(key) => executor.simulate_key(*key, InputState::Press).await,
+        InputEvent::KeyRelease(key) => executor.simulate_key(*key, InputState::Release).await,
+        InputEvent::MousePress(button) => executor.simulate_mouse(*button, InputState::Press).await,
+        InputEvent::MouseRelease(button) => {
+            executor.simulate_mouse(*button, InputState::Release).await
+        }
+        InputEvent::MouseMove { x, y } => executor.mouse_move_abs(*x, *y).await,
+        InputEvent::MouseWheel { dx, dy } => executor.scroll(*dx, *dy).await,
+        // The logical key event of the same keystroke is replayed instead
+        InputEvent::PhysicalKeyPress(_) | InputEvent::PhysicalKeyRelease(_) => Ok(()),
+        InputEvent::FocusChanged => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Key, MockExecutor, RateLimiter};
+    use std::time::Duration;
+
+    /// Presses of successive keys, recorded at these offsets in ms
+    fn recording(offsets: &[u64]) -> Vec<TimedEvent> {
+        let keys = [Key::A, Key::B, Key::C, Key::D];
+        offsets
+            .iter()
+            .zip(keys)
+            .map(|(&ms, key)| TimedEvent {
+                event: InputEvent::KeyPress(key),
+                at: Duration::from_millis(ms),
+            })
+            .collect()
+    }
+
+    fn offsets(executor: &MockExecutor) -> Vec<u64> {
+        executor
+            .timed_calls()
+            .into_iter()
+            .map(|(at, _)| at.as_millis() as u64)
+            .collect()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn events_on_time_keep_their_recorded_offsets() {
+        for drift_correction in [false, true] {
+            let executor = MockExecutor::new().with_timing();
+            replay(&recording(&[0, 50, 120]), &executor, drift_correction)
+                .await
+                .unwrap();
+            assert_eq!(offsets(&executor), [0, 50, 120]);
+        }
+    }
+
+    /// Replay through an executor taking 100ms per event, so the third
+    /// event is due while the second is still going out
+    async fn replay_slowly(drift_correction: bool) -> Vec<u64> {
+        let executor = RateLimiter::new(MockExecutor::new().with_timing(), 10);
+        replay(&recording(&[0, 20, 40, 300]), &executor, drift_correction)
+            .await
+            .unwrap();
+        offsets(&executor.into_inner())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn drift_correction_catches_up_to_the_recorded_schedule() {
+        // 60ms late at the third event, which goes out at once; the fourth
+        // is back on its recorded time
+        assert_eq!(replay_slowly(true).await, [0, 100, 200, 300]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn without_drift_correction_lateness_shifts_the_rest() {
+        // The 60ms the third event was late pushes the fourth back too
+        assert_eq!(replay_slowly(false).await, [0, 100, 200, 360]);
+    }
+}
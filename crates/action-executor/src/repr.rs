@@ -0,0 +1,555 @@
+// Serialized form of `Action`.
+//
+// Actions are stored internally tagged, e.g. `{ "type": "PressKey", "key":
+// "Enter" }`, which serde can't derive for tuple variants. `Action`
+// converts through this mirror enum instead, where every variant has named
+// fields. Durations are whole milliseconds.
+
+use crate::{Action, Easing, InputState, LockKey};
+use input_capture::{Key, Modifier, MouseButton};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub(crate) enum ActionRepr {
+    PressKey {
+        key: Key,
+    },
+    Click {
+        button: MouseButton,
+    },
+    HoldKey {
+        key: Key,
+    },
+    ReleaseKey {
+        key: Key,
+    },
+    ToggleKey {
+        key: Key,
+    },
+    KeyCombo {
+        modifiers: Vec<Modifier>,
+        key: Key,
+    },
+    RawScanCode {
+        code: u16,
+        state: InputState,
+    },
+    Sequence {
+        actions: Vec<Action>,
+    },
+    Repeat {
+        inner: Box<Action>,
+        count: u32,
+    },
+    Loop {
+        actions: Vec<Action>,
+        count: u32,
+    },
+    RepeatWhileHeld {
+        actions: Vec<Action>,
+        #[serde(with = "millis")]
+        interval: Duration,
+        #[serde(
+            default,
+            skip_serializing_if = "Option::is_none",
+            with = "millis::option"
+        )]
+        max_duration: Option<Duration>,
+    },
+    Delay {
+        #[serde(with = "millis")]
+        duration: Duration,
+    },
+    RandomDelay {
+        #[serde(with = "millis")]
+        min: Duration,
+        #[serde(with = "millis")]
+        max: Duration,
+    },
+    TypeText {
+        text: String,
+    },
+    PasteText {
+        text: String,
+        #[serde(default)]
+        restore: bool,
+    },
+    TypeTextHumanized {
+        text: String,
+        #[serde(with = "millis")]
+        mean: Duration,
+        #[serde(with = "millis")]
+        jitter: Duration,
+    },
+    WaitForWindow {
+        title_contains: String,
+        #[serde(with = "millis")]
+        timeout: Duration,
+        #[serde(with = "millis")]
+        poll_interval: Duration,
+    },
+    WaitForKey {
+        key: Key,
+        #[serde(
+            default,
+            skip_serializing_if = "Option::is_none",
+            with = "millis::option"
+        )]
+        timeout: Option<Duration>,
+    },
+    MouseMoveAbs {
+        x: i32,
+        y: i32,
+    },
+    MouseMoveRel {
+        dx: i32,
+        dy: i32,
+    },
+    MousePath {
+        points: Vec<(i32, i32)>,
+        #[serde(with = "millis")]
+        duration_per_segment: Duration,
+        smooth: bool,
+        #[serde(default)]
+        jitter: u32,
+    },
+    MoveSmooth {
+        to: (i32, i32),
+        #[serde(with = "millis")]
+        duration: Duration,
+        easing: Easing,
+    },
+    MouseDrag {
+        button: MouseButton,
+        from: (i32, i32),
+        to: (i32, i32),
+        steps: u32,
+    },
+    Scroll {
+        amount: i32,
+    },
+    ScrollHorizontal {
+        amount: i32,
+    },
+    SmoothScroll {
+        total: i32,
+        #[serde(with = "millis")]
+        duration: Duration,
+        easing: Easing,
+    },
+    WaitForPixel {
+        x: i32,
+        y: i32,
+        color: (u8, u8, u8),
+        tolerance: u8,
+        #[serde(with = "millis")]
+        timeout: Duration,
+        #[serde(with = "millis")]
+        poll_interval: Duration,
+    },
+    PreserveCursor {
+        action: Box<Action>,
+    },
+    WithTimeout {
+        action: Box<Action>,
+        #[serde(with = "millis")]
+        limit: Duration,
+    },
+    IfHeld {
+        key: Key,
+        then: Box<Action>,
+        #[serde(default)]
+        otherwise: Option<Box<Action>>,
+    },
+    SetLock {
+        lock: LockKey,
+        on: bool,
+    },
+    IfLockOn {
+        lock: LockKey,
+        then: Box<Action>,
+        #[serde(default)]
+        otherwise: Option<Box<Action>>,
+    },
+    WeightedChoice {
+        choices: Vec<(f64, Action)>,
+    },
+    ResyncState,
+    DumpState,
+    ReleaseAll,
+    RunCommand {
+        program: String,
+        #[serde(default)]
+        args: Vec<String>,
+    },
+    TypeTemplate {
+        template: String,
+    },
+    #[cfg(feature = "block-input")]
+    BlockInput {
+        #[serde(with = "millis")]
+        duration: Duration,
+    },
+}
+
+impl From<ActionRepr> for Action {
+    fn from(repr: ActionRepr) -> Self {
+        match repr {
+            ActionRepr::PressKey { key } => Action::PressKey(key),
+            ActionRepr::Click { button } => Action::Click(button),
+            ActionRepr::HoldKey { key } => Action::HoldKey(key),
+            ActionRepr::ReleaseKey { key } => Action::ReleaseKey(key),
+            ActionRepr::ToggleKey { key } => Action::ToggleKey(key),
+            ActionRepr::KeyCombo { modifiers, key } => Action::KeyCombo { modifiers, key },
+            ActionRepr::RawScanCode { code, state } => Action::RawScanCode { code, state },
+            ActionRepr::Sequence { actions } => Action::Sequence(actions),
+            ActionRepr::Repeat { inner, count } => Action::Repeat { inner, count },
+            ActionRepr::Loop { actions, count } => Action::Loop { actions, count },
+            ActionRepr::RepeatWhileHeld {
+                actions,
+                interval,
+                max_duration,
+            } => Action::RepeatWhileHeld {
+                actions,
+                interval,
+                max_duration,
+            },
+            ActionRepr::Delay { duration } => Action::Delay(duration),
+            ActionRepr::RandomDelay { min, max } => Action::RandomDelay { min, max },
+            ActionRepr::TypeText { text } => Action::TypeText(text),
+            ActionRepr::PasteText { text, restore } => Action::PasteText { text, restore },
+            ActionRepr::TypeTextHumanized { text, mean, jitter } => {
+                Action::TypeTextHumanized { text, mean, jitter }
+            }
+            ActionRepr::WaitForWindow {
+                title_contains,
+                timeout,
+                poll_interval,
+            } => Action::WaitForWindow {
+                title_contains,
+                timeout,
+                poll_interval,
+            },
+            ActionRepr::WaitForKey { key, timeout } => Action::WaitForKey { key, timeout },
+            ActionRepr::MouseMoveAbs { x, y } => Action::MouseMoveAbs { x, y },
+            ActionRepr::MouseMoveRel { dx, dy } => Action::MouseMoveRel { dx, dy },
+            ActionRepr::MousePath {
+                points,
+                duration_per_segment,
+                smooth,
+                jitter,
+            } => Action::MousePath {
+                points,
+                duration_per_segment,
+                smooth,
+                jitter,
+            },
+            ActionRepr::MoveSmooth {
+                to,
+                duration,
+                easing,
+            } => Action::MoveSmooth {
+                to,
+                duration,
+                easing,
+            },
+            ActionRepr::MouseDrag {
+                button,
+                from,
+                to,
+                steps,
+            } => Action::MouseDrag {
+                button,
+                from,
+                to,
+                steps,
+            },
+            ActionRepr::Scroll { amount } => Action::Scroll { amount },
+            ActionRepr::ScrollHorizontal { amount } => Action::ScrollHorizontal { amount },
+            ActionRepr::SmoothScroll {
+                total,
+                duration,
+                easing,
+            } => Action::SmoothScroll {
+                total,
+                duration,
+                easing,
+            },
+            ActionRepr::WaitForPixel {
+                x,
+                y,
+                color,
+                tolerance,
+                timeout,
+                poll_interval,
+            } => Action::WaitForPixel {
+                x,
+                y,
+                color,
+                tolerance,
+                timeout,
+                poll_interval,
+            },
+            ActionRepr::PreserveCursor { action } => Action::PreserveCursor(action),
+            ActionRepr::WithTimeout { action, limit } => Action::WithTimeout { action, limit },
+            ActionRepr::IfHeld {
+                key,
+                then,
+                otherwise,
+            } => Action::IfHeld {
+                key,
+                then,
+                otherwise,
+            },
+            ActionRepr::SetLock { lock, on } => Action::SetLock { lock, on },
+            ActionRepr::IfLockOn {
+                lock,
+                then,
+                otherwise,
+            } => Action::IfLockOn {
+                lock,
+                then,
+                otherwise,
+            },
+            ActionRepr::WeightedChoice { choices } => Action::WeightedChoice(choices),
+            ActionRepr::ResyncState => Action::ResyncState,
+            ActionRepr::DumpState => Action::DumpState,
+            ActionRepr::ReleaseAll => Action::ReleaseAll,
+            ActionRepr::RunCommand { program, args } => Action::RunCommand { program, args },
+            ActionRepr::TypeTemplate { template } => Action::TypeTemplate(template),
+            #[cfg(feature = "block-input")]
+            ActionRepr::BlockInput { duration } => Action::BlockInput(duration),
+        }
+    }
+}
+
+impl From<Action> for ActionRepr {
+    fn from(action: Action) -> Self {
+        match action {
+            Action::PressKey(key) => ActionRepr::PressKey { key },
+            Action::Click(button) => ActionRepr::Click { button },
+            Action::HoldKey(key) => ActionRepr::HoldKey { key },
+            Action::ReleaseKey(key) => ActionRepr::ReleaseKey { key },
+            Action::ToggleKey(key) => ActionRepr::ToggleKey { key },
+            Action::KeyCombo { modifiers, key } => ActionRepr::KeyCombo { modifiers, key },
+            Action::RawScanCode { code, state } => ActionRepr::RawScanCode { code, state },
+            Action::Sequence(actions) => ActionRepr::Sequence { actions },
+            Action::Repeat { inner, count } => ActionRepr::Repeat { inner, count },
+            Action::Loop { actions, count } => ActionRepr::Loop { actions, count },
+            Action::RepeatWhileHeld {
+                actions,
+                interval,
+                max_duration,
+            } => ActionRepr::RepeatWhileHeld {
+                actions,
+                interval,
+                max_duration,
+            },
+            Action::Delay(duration) => ActionRepr::Delay { duration },
+            Action::RandomDelay { min, max } => ActionRepr::RandomDelay { min, max },
+            Action::TypeText(text) => ActionRepr::TypeText { text },
+            Action::PasteText { text, restore } => ActionRepr::PasteText { text, restore },
+            Action::TypeTextHumanized { text, mean, jitter } => {
+                ActionRepr::TypeTextHumanized { text, mean, jitter }
+            }
+            Action::WaitForWindow {
+                title_contains,
+                timeout,
+                poll_interval,
+            } => ActionRepr::WaitForWindow {
+                title_contains,
+                timeout,
+                poll_interval,
+            },
+            Action::WaitForKey { key, timeout } => ActionRepr::WaitForKey { key, timeout },
+            Action::MouseMoveAbs { x, y } => ActionRepr::MouseMoveAbs { x, y },
+            Action::MouseMoveRel { dx, dy } => ActionRepr::MouseMoveRel { dx, dy },
+            Action::MousePath {
+                points,
+                duration_per_segment,
+                smooth,
+                jitter,
+            } => ActionRepr::MousePath {
+                points,
+                duration_per_segment,
+                smooth,
+                jitter,
+            },
+            Action::MoveSmooth {
+                to,
+                duration,
+                easing,
+            } => ActionRepr::MoveSmooth {
+                to,
+                duration,
+                easing,
+            },
+            Action::MouseDrag {
+                button,
+                from,
+                to,
+                steps,
+            } => ActionRepr::MouseDrag {
+                button,
+                from,
+                to,
+                steps,
+            },
+            Action::Scroll { amount } => ActionRepr::Scroll { amount },
+            Action::ScrollHorizontal { amount } => ActionRepr::ScrollHorizontal { amount },
+            Action::SmoothScroll {
+                total,
+                duration,
+                easing,
+            } => ActionRepr::SmoothScroll {
+                total,
+                duration,
+                easing,
+            },
+            Action::WaitForPixel {
+                x,
+                y,
+                color,
+                tolerance,
+                timeout,
+                poll_interval,
+            } => ActionRepr::WaitForPixel {
+                x,
+                y,
+                color,
+                tolerance,
+                timeout,
+                poll_interval,
+            },
+            Action::PreserveCursor(action) => ActionRepr::PreserveCursor { action },
+            Action::WithTimeout { action, limit } => ActionRepr::WithTimeout { action, limit },
+            Action::IfHeld {
+                key,
+                then,
+                otherwise,
+            } => ActionRepr::IfHeld {
+                key,
+                then,
+                otherwise,
+            },
+            Action::SetLock { lock, on } => ActionRepr::SetLock { lock, on },
+            Action::IfLockOn {
+                lock,
+                then,
+                otherwise,
+            } => ActionRepr::IfLockOn {
+                lock,
+                then,
+                otherwise,
+            },
+            Action::WeightedChoice(choices) => ActionRepr::WeightedChoice { choices },
+            Action::ResyncState => ActionRepr::ResyncState,
+            Action::DumpState => ActionRepr::DumpState,
+            Action::ReleaseAll => ActionRepr::ReleaseAll,
+            Action::RunCommand { program, args } => ActionRepr::RunCommand { program, args },
+            Action::TypeTemplate(template) => ActionRepr::TypeTemplate { template },
+            #[cfg(feature = "block-input")]
+            Action::BlockInput(duration) => ActionRepr::BlockInput { duration },
+        }
+    }
+}
+
+/// `Duration` as whole milliseconds (sub-millisecond parts are dropped)
+mod millis {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::Duration;
+
+    pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(u64::try_from(duration.as_millis()).unwrap_or(u64::MAX))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        u64::deserialize(deserializer).map(Duration::from_millis)
+    }
+
+    pub mod option {
+        use serde::{Deserialize, Deserializer, Serialize, Serializer};
+        use std::time::Duration;
+
+        pub fn serialize<S: Serializer>(
+            duration: &Option<Duration>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            duration
+                .map(|duration| u64::try_from(duration.as_millis()).unwrap_or(u64::MAX))
+                .serialize(serializer)
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Option<Duration>, D::Error> {
+            Option::<u64>::deserialize(deserializer).map(|ms| ms.map(Duration::from_millis))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Action;
+    use input_capture::Key;
+    use serde::{Deserialize, Serialize};
+    use std::time::Duration;
+
+    // TOML has no bare top-level values, so actions sit under a key
+    #[derive(Serialize, Deserialize)]
+    struct Doc {
+        action: Action,
+    }
+
+    fn to_toml(action: Action) -> String {
+        toml::to_string(&Doc { action }).unwrap()
+    }
+
+    #[test]
+    fn nested_sequence_round_trips() {
+        let action = Action::Sequence(vec![
+            Action::PressKey(Key::Enter),
+            Action::RandomDelay {
+                min: Duration::from_millis(20),
+                max: Duration::from_millis(80),
+            },
+            Action::Sequence(vec![Action::TypeText("hi there".into())]),
+        ]);
+        let text = to_toml(action);
+        let Doc { action } = toml::from_str(&text).unwrap();
+        let Action::Sequence(actions) = action else {
+            panic!("{text}");
+        };
+        let [Action::PressKey(Key::Enter), Action::RandomDelay { min, max }, Action::Sequence(inner)] =
+            &actions[..]
+        else {
+            panic!("{text}");
+        };
+        assert_eq!(*min, Duration::from_millis(20));
+        assert_eq!(*max, Duration::from_millis(80));
+        assert!(matches!(&inner[..], [Action::TypeText(text)] if text == "hi there"));
+        // And serializing again gives the same document
+        assert_eq!(to_toml(Action::Sequence(actions)), text);
+    }
+
+    #[test]
+    fn actions_are_tagged_and_durations_are_milliseconds() {
+        let Doc { action } = toml::from_str(
+            r#"
+            action = { type = "Delay", duration = 1500 }
+            "#,
+        )
+        .unwrap();
+        assert!(matches!(action, Action::Delay(d) if d == Duration::from_millis(1500)));
+        let text = to_toml(Action::PressKey(Key::Enter));
+        let value: toml::Table = toml::from_str(&text).unwrap();
+        assert_eq!(value["action"]["type"].as_str(), Some("PressKey"));
+        assert_eq!(value["action"]["key"].as_str(), Some("Enter"));
+        // Sub-millisecond parts are dropped
+        let text = to_toml(Action::Delay(Duration::from_micros(2999)));
+        let value: toml::Table = toml::from_str(&text).unwrap();
+        assert_eq!(value["action"]["duration"].as_integer(), Some(2));
+    }
+}
@@ -0,0 +1,120 @@
+// Event timeline logging for spotting feedback loops.
+//
+// Emitted events are logged here and captured events by the binding
+// engine, both under the `timeline` target, so enabling it (e.g.
+// `RUST_LOG=timeline=trace`) shows one interleaved timeline where an
+// emitted event coming straight back as a captured one stands out.
+
+use crate::{
+    AsyncActionExecutor, ExecutorError, InputKind, InputState, Key, LockKey, Monitor, MouseButton,
+};
+use anyhow::Result;
+
+/// Executor wrapper that logs every emitted event before forwarding it
+pub struct TimelineExecutor<E> {
+    inner: E,
+}
+
+impl<E: AsyncActionExecutor> TimelineExecutor<E> {
+    pub fn new(inner: E) -> Self {
+        Self { inner }
+    }
+
+    pub fn into_inner(self) -> E {
+        self.inner
+    }
+}
+
+impl<E: AsyncActionExecutor> AsyncActionExecutor for TimelineExecutor<E> {
+    async fn simulate_key(&self, key: Key, state: InputState) -> Result<(), ExecutorError> {
+        tracing::trace!(target: "timeline", direction = "emitted", ?key, ?state);
+        self.inner.simulate_key(key, state).await
+    }
+
+    async fn simulate_mouse(
+        &self,
+        button: MouseButton,
+        state: InputState,
+    ) -> Result<(), ExecutorError> {
+        tracing::trace!(target: "timeline", direction = "emitted", ?button, ?state);
+        self.inner.simulate_mouse(button, state).await
+    }
+
+    async fn simulate_batch(
+        &self,
+        events: &[(InputKind, InputState)],
+    ) -> Result<(), ExecutorError> {
+        for (kind, state) in events {
+            tracing::trace!(target: "timeline", direction = "emitted", ?kind, ?state, "batch");
+        }
+        self.inner.simulate_batch(events).await
+    }
+
+    async fn simulate_scancode(&self, code: u16, state: InputState) -> Result<(), ExecutorError> {
+        tracing::trace!(target: "timeline", direction = "emitted", code, ?state, "scancode");
+        self.inner.simulate_scancode(code, state).await
+    }
+
+    async fn send_unicode(&self, c: char) -> Result<(), ExecutorError> {
+        tracing::trace!(target: "timeline", direction = "emitted", ?c, "unicode");
+        self.inner.send_unicode(c).await
+    }
+
+    async fn mouse_move_abs(&self, x: i32, y: i32) -> Result<(), ExecutorError> {
+        tracing::trace!(target: "timeline", direction = "emitted", x, y, "mouse_move_abs");
+        self.inner.mouse_move_abs(x, y).await
+    }
+
+    async fn mouse_move_rel(&self, dx: i32, dy: i32) -> Result<(), ExecutorError> {
+        tracing::trace!(target: "timeline", direction = "emitted", dx, dy, "mouse_move_rel");
+        self.inner.mouse_move_rel(dx, dy).await
+    }
+
+    async fn mouse_move_to_monitor(
+        &self,
+        monitor: usize,
+        x: i32,
+        y: i32,
+    ) -> Result<(), ExecutorError> {
+        tracing::trace!(target: "timeline", direction = "emitted", monitor, x, y, "mouse_move_to_monitor");
+        self.inner.mouse_move_to_monitor(monitor, x, y).await
+    }
+
+    async fn scroll(&self, dx: i32, dy: i32) -> Result<(), ExecutorError> {
+        tracing::trace!(target: "timeline", direction = "emitted", dx, dy, "scroll");
+        self.inner.scroll(dx, dy).await
+    }
+
+    async fn current_position(&self) -> Result<(i32, i32), ExecutorError> {
+        self.inner.current_position().await
+    }
+
+    async fn monitors(&self) -> Result<Vec<Monitor>, ExecutorError> {
+        self.inner.monitors().await
+    }
+
+    async fn window_titles(&self) -> Result<Vec<String>, ExecutorError> {
+        self.inner.window_titles().await
+    }
+
+    async fn pixel_color(&self, x: i32, y: i32) -> Result<(u8, u8, u8), ExecutorError> {
+        self.inner.pixel_color(x, y).await
+    }
+
+    async fn held_keys(&self) -> Result<Vec<Key>, ExecutorError> {
+        self.inner.held_keys().await
+    }
+
+    async fn lock_state(&self, lock: LockKey) -> Result<bool, ExecutorError> {
+        self.inner.lock_state(lock).await
+    }
+
+    async fn set_lock(&self, lock: LockKey, on: bool) -> Result<(), ExecutorError> {
+        tracing::trace!(target: "timeline", direction = "emitted", ?lock, on, "set_lock");
+        self.inner.set_lock(lock, on).await
+    }
+
+    fn block_input(&self, blocked: bool) -> Result<(), ExecutorError> {
+        self.inner.block_input(blocked)
+    }
+}
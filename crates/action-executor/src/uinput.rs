@@ -0,0 +1,164 @@
+// Virtual input device through /dev/uinput.
+//
+// Events enter the kernel below the display server, so this works under X11
+// and Wayland alike and is indistinguishable from a real keyboard and mouse.
+
+use crate::{ActionExecutor, ExecutorError, InputState, Key, MouseButton};
+use anyhow::Result;
+use evdev::uinput::{VirtualDevice, VirtualDeviceBuilder};
+use evdev::{
+    AbsInfo, AbsoluteAxisType, AttributeSet, EventType, InputEvent, RelativeAxisType,
+    UinputAbsSetup,
+};
+use std::io;
+use std::sync::Mutex;
+
+/// Executor writing to a virtual uinput keyboard and mouse
+pub struct UinputExecutor {
+    device: Mutex<VirtualDevice>,
+    // Set when absolute axes were registered
+    screen: Option<(i32, i32)>,
+}
+
+impl UinputExecutor {
+    /// Create the device with every key `Key` can emit, the five mouse
+    /// buttons and relative axes (no absolute moves)
+    pub fn new() -> Result<Self> {
+        Self::create(None)
+    }
+
+    /// Like `new`, but also register absolute axes spanning a screen of
+    /// `width` x `height` pixels so `mouse_move_abs` works
+    pub fn with_screen_size(width: i32, height: i32) -> Result<Self> {
+        if width <= 0 || height <= 0 {
+            anyhow::bail!("invalid screen size {width}x{height}");
+        }
+        Self::create(Some((width, height)))
+    }
+
+    fn create(screen: Option<(i32, i32)>) -> Result<Self> {
+        let mut keys = AttributeSet::<evdev::Key>::new();
+        for key in Key::ALL {
+            keys.insert(evdev::Key::new(key.physical().0));
+        }
+        let buttons = [
+            MouseButton::Left,
+            MouseButton::Right,
+            MouseButton::Middle,
+            MouseButton::Button4,
+            MouseButton::Button5,
+        ];
+        for button in buttons {
+            keys.insert(evdev::Key::new(button_code(button)));
+        }
+
+        let mut axes = AttributeSet::<RelativeAxisType>::new();
+        axes.insert(RelativeAxisType::REL_X);
+        axes.insert(RelativeAxisType::REL_Y);
+        axes.insert(RelativeAxisType::REL_WHEEL);
+        axes.insert(RelativeAxisType::REL_HWHEEL);
+
+        let mut builder = VirtualDeviceBuilder::new()
+            .map_err(open_error)?
+            .name(input_capture::VIRTUAL_DEVICE_NAME)
+            .with_keys(&keys)?
+            .with_relative_axes(&axes)?;
+        if let Some((width, height)) = screen {
+            let axis = |axis, max| UinputAbsSetup::new(axis, AbsInfo::new(0, 0, max, 0, 0, 0));
+            builder = builder
+                .with_absolute_axis(&axis(AbsoluteAxisType::ABS_X, width - 1))?
+                .with_absolute_axis(&axis(AbsoluteAxisType::ABS_Y, height - 1))?;
+        }
+
+        Ok(Self {
+            device: Mutex::new(builder.build()?),
+            screen,
+        })
+    }
+
+    /// Write events followed by a SYN_REPORT
+    fn emit(&self, events: &[InputEvent]) -> Result<(), ExecutorError> {
+        let mut device = self
+            .device
+            .lock()
+            .map_err(|_| ExecutorError::Backend("uinput device lock poisoned".into()))?;
+        device.emit(events)?;
+        Ok(())
+    }
+
+    fn key(&self, code: u16, state: InputState) -> Result<(), ExecutorError> {
+        let value = match state {
+            InputState::Press => 1,
+            InputState::Release => 0,
+        };
+        self.emit(&[InputEvent::new(EventType::KEY, code, value)])
+    }
+}
+
+fn open_error(err: io::Error) -> anyhow::Error {
+    if err.kind() == io::ErrorKind::PermissionDenied {
+        anyhow::anyhow!(
+            "no permission to open /dev/uinput; add a udev rule such as \
+             `KERNEL==\"uinput\", GROUP=\"input\", MODE=\"0660\"` and join the `input` group"
+        )
+    } else {
+        anyhow::Error::new(err).context("failed to open /dev/uinput")
+    }
+}
+
+fn button_code(button: MouseButton) -> u16 {
+    match button {
+        MouseButton::Left => 0x110,
+        MouseButton::Right => 0x111,
+        MouseButton::Middle => 0x112,
+        MouseButton::Button4 => 0x113,
+        MouseButton::Button5 => 0x114,
+    }
+}
+
+fn rel(axis: RelativeAxisType, value: i32) -> InputEvent {
+    InputEvent::new(EventType::RELATIVE, axis.0, value)
+}
+
+impl ActionExecutor for UinputExecutor {
+    fn simulate_key(&self, key: Key, state: InputState) -> Result<(), ExecutorError> {
+        self.key(key.physical().0, state)
+    }
+
+    fn simulate_mouse(&self, button: MouseButton, state: InputState) -> Result<(), ExecutorError> {
+        self.key(button_code(button), state)
+    }
+
+    fn simulate_scancode(&self, code: u16, state: InputState) -> Result<(), ExecutorError> {
+        // Only codes registered at creation reach applications
+        self.key(code, state)
+    }
+
+    fn mouse_move_abs(&self, x: i32, y: i32) -> Result<(), ExecutorError> {
+        let Some((width, height)) = self.screen else {
+            return Err(ExecutorError::Backend(
+                "absolute moves need UinputExecutor::with_screen_size".into(),
+            ));
+        };
+        let x = x.clamp(0, width - 1);
+        let y = y.clamp(0, height - 1);
+        self.emit(&[
+            InputEvent::new(EventType::ABSOLUTE, AbsoluteAxisType::ABS_X.0, x),
+            InputEvent::new(EventType::ABSOLUTE, AbsoluteAxisType::ABS_Y.0, y),
+        ])
+    }
+
+    fn mouse_move_rel(&self, dx: i32, dy: i32) -> Result<(), ExecutorError> {
+        self.emit(&[
+            rel(RelativeAxisType::REL_X, dx),
+            rel(RelativeAxisType::REL_Y, dy),
+        ])
+    }
+
+    fn scroll(&self, dx: i32, dy: i32) -> Result<(), ExecutorError> {
+        self.emit(&[
+            rel(RelativeAxisType::REL_HWHEEL, dx),
+            rel(RelativeAxisType::REL_WHEEL, dy),
+        ])
+    }
+}
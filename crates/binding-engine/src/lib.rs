@@ -1,39 +1,244 @@
-use action_executor::Action;
+use action_executor::{Action, AsyncActionExecutor, ExecutionContext, HeldInput};
 use anyhow::Result;
-use input_capture::{Hotkey, InputEvent};
-use std::collections::HashMap;
+use chrono::{Local, NaiveTime};
+use input_capture::{
+    CapturedEvent, DeviceId, Hotkey, InputCapture, InputEvent, Key, Modifier, ModifierSet,
+    MouseButton, Trigger,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio::task::AbortHandle;
+// tokio's clock, so tests can pause it
+use tokio::time::Instant;
+use tracing::Instrument;
+
+/// Condition a binding must meet to be active
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub enum Condition {
+    /// Active from `from` until `to` in local time; wraps past midnight
+    /// when `from` is later than `to`
+    TimeOfDay { from: NaiveTime, to: NaiveTime },
+}
+
+impl Condition {
+    /// Check the condition against the current local time
+    pub fn is_met(&self) -> bool {
+        self.is_met_at(Local::now().time())
+    }
+
+    pub fn is_met_at(&self, now: NaiveTime) -> bool {
+        match self {
+            Condition::TimeOfDay { from, to } if from <= to => *from <= now && now < *to,
+            Condition::TimeOfDay { from, to } => now >= *from || now < *to,
+        }
+    }
+}
 
 /// Registry mapping hotkeys to actions
 pub struct BindingRegistry {
     bindings: HashMap<Hotkey, Action>,
+    // Press-edge holds the key, release-edge releases it
+    holds: HashMap<Hotkey, Key>,
+    // Bindings executed on their own thread instead of the shared runtime
+    dedicated: HashSet<Hotkey>,
+    // Long-press hotkeys' actions for releasing before the threshold
+    short_presses: HashMap<Hotkey, Action>,
+    conditions: HashMap<Hotkey, Condition>,
+    on_start: Option<Action>,
+    on_stop: Option<Action>,
+}
+
+impl Default for BindingRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl BindingRegistry {
     pub fn new() -> Self {
         Self {
             bindings: HashMap::new(),
+            holds: HashMap::new(),
+            dedicated: HashSet::new(),
+            short_presses: HashMap::new(),
+            conditions: HashMap::new(),
+            on_start: None,
+            on_stop: None,
         }
     }
 
+    /// Set an action to run once when the engine starts
+    pub fn on_start(mut self, action: Action) -> Self {
+        self.on_start = Some(action);
+        self
+    }
+
+    /// Set an action to run once during shutdown, before held keys are released
+    pub fn on_stop(mut self, action: Action) -> Self {
+        self.on_stop = Some(action);
+        self
+    }
+
     /// Add a hotkey → action binding
     pub fn bind(mut self, hotkey: Hotkey, action: Action) -> Self {
         self.bindings.insert(hotkey, action);
         self
     }
 
+    /// Add a hotkey → held key binding (push-to-talk style passthrough)
+    pub fn bind_hold(mut self, hotkey: Hotkey, key: Key) -> Self {
+        self.holds.insert(hotkey, key);
+        self
+    }
+
+    /// Add a binding whose action runs on a dedicated thread, so a slow
+    /// macro can't delay latency-sensitive ones (see `spawn_dedicated`)
+    pub fn bind_dedicated(mut self, hotkey: Hotkey, action: Action) -> Self {
+        self.dedicated.insert(hotkey.clone());
+        self.bind(hotkey, action)
+    }
+
+    /// Add the action for releasing a `Trigger::LongPress` hotkey's trigger
+    /// before its threshold (the long-press action itself is bound with
+    /// `bind`)
+    pub fn bind_short_press(mut self, hotkey: Hotkey, action: Action) -> Self {
+        self.short_presses.insert(hotkey, action);
+        self
+    }
+
+    /// Add a binding that only fires while `condition` is met
+    pub fn bind_when(mut self, hotkey: Hotkey, condition: Condition, action: Action) -> Self {
+        self.conditions.insert(hotkey.clone(), condition);
+        self.bind(hotkey, action)
+    }
+
+    /// Check if a hotkey's activation condition (if any) is currently met
+    pub fn is_active(&self, hotkey: &Hotkey) -> bool {
+        self.conditions.get(hotkey).is_none_or(Condition::is_met)
+    }
+
+    /// Check if a hotkey's action should run on a dedicated thread
+    pub fn is_dedicated(&self, hotkey: &Hotkey) -> bool {
+        self.dedicated.contains(hotkey)
+    }
+
     /// Get action for a hotkey (if registered)
     pub fn get_action(&self, hotkey: &Hotkey) -> Option<&Action> {
         self.bindings.get(hotkey)
     }
 
+    /// Get the key held while a hotkey is held (if registered)
+    pub fn get_hold(&self, hotkey: &Hotkey) -> Option<Key> {
+        self.holds.get(hotkey).copied()
+    }
+
     /// Check if a hotkey is registered
     pub fn is_registered(&self, hotkey: &Hotkey) -> bool {
-        self.bindings.contains_key(hotkey)
+        self.bindings.contains_key(hotkey) || self.holds.contains_key(hotkey)
+    }
+
+    /// Every bound hotkey, including holds and short-press actions, sorted
+    /// by their string form so listings are stable
+    pub fn registered_hotkeys(&self) -> Vec<Hotkey> {
+        let mut hotkeys: Vec<Hotkey> = self
+            .bindings
+            .keys()
+            .chain(self.holds.keys())
+            .chain(self.short_presses.keys())
+            .cloned()
+            .collect();
+        hotkeys.sort_by_cached_key(Hotkey::to_string);
+        hotkeys.dedup();
+        hotkeys
     }
 
     /// Number of registered bindings
     pub fn len(&self) -> usize {
-        self.bindings.len()
+        self.bindings.len() + self.holds.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Highest click count bound for a button under the given modifiers
+    fn max_clicks(&self, modifiers: &[Modifier], button: MouseButton) -> u32 {
+        self.bindings
+            .keys()
+            .filter(|hotkey| hotkey.modifiers() == modifiers)
+            .filter_map(|hotkey| match hotkey.trigger {
+                Trigger::MouseClicks { button: b, count } if b == button => Some(count),
+                _ => None,
+            })
+            .max()
+            .unwrap_or(1)
+    }
+
+    /// Long-press binding for `trigger` under exactly `modifiers`, with its
+    /// threshold
+    fn long_press(&self, modifiers: &[Modifier], trigger: &Trigger) -> Option<(&Hotkey, Duration)> {
+        self.bindings
+            .keys()
+            .chain(self.short_presses.keys())
+            .filter(|hotkey| hotkey.modifiers() == modifiers)
+            .find_map(|hotkey| match &hotkey.trigger {
+                Trigger::LongPress {
+                    trigger: held,
+                    threshold,
+                } if **held == *trigger => Some((hotkey, *threshold)),
+                _ => None,
+            })
+    }
+
+    /// Modifiers of a binding on `trigger` that `held` satisfies, preferring
+    /// the most sided; otherwise the held ones, side-agnostic and sorted
+    fn bound_modifiers(&self, held: ModifierSet, trigger: &Trigger) -> Vec<Modifier> {
+        let sided = |hotkey: &Hotkey| {
+            let count = hotkey.modifiers().iter().filter(|m| m.is_sided()).count();
+            // Ties broken by the modifiers themselves, not map order
+            (count, hotkey.modifiers().to_vec())
+        };
+        self.bindings
+            .keys()
+            .chain(self.holds.keys())
+            .chain(self.short_presses.keys())
+            .filter(|hotkey| fired_by(&hotkey.trigger, trigger) && held.matches(hotkey.modifiers()))
+            .max_by_key(|hotkey| sided(hotkey))
+            .map(|hotkey| hotkey.modifiers().to_vec())
+            .unwrap_or_else(|| held.modifiers().collect())
+    }
+
+    /// Highest tap count bound for `trigger` under any modifiers (0 if none)
+    fn max_taps(&self, trigger: &Trigger) -> u32 {
+        self.bindings
+            .keys()
+            .filter_map(|hotkey| match &hotkey.trigger {
+                Trigger::MultiTap {
+                    trigger: tapped,
+                    count,
+                    ..
+                } if **tapped == *trigger => Some(*count),
+                _ => None,
+            })
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+/// Whether a press of `pressed` can fire a binding on `bound`, directly
+/// or by counting towards it
+fn fired_by(bound: &Trigger, pressed: &Trigger) -> bool {
+    match (bound, pressed) {
+        (Trigger::MultiTap { trigger, .. } | Trigger::LongPress { trigger, .. }, _) => {
+            **trigger == *pressed
+        }
+        (Trigger::MouseClicks { button, .. }, Trigger::MouseButton(pressed)) => button == pressed,
+        _ => bound == pressed,
     }
 }
 
@@ -44,6 +249,12 @@ pub struct StateTracker {
     held_buttons: Vec<input_capture::MouseButton>,
 }
 
+impl Default for StateTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl StateTracker {
     pub fn new() -> Self {
         Self {
@@ -55,18 +266,14 @@ impl StateTracker {
     /// Update state based on incoming event
     pub fn update(&mut self, event: &InputEvent) {
         match event {
-            InputEvent::KeyPress(key) => {
-                if !self.held_keys.contains(key) {
-                    self.held_keys.push(*key);
-                }
+            InputEvent::KeyPress(key) if !self.held_keys.contains(key) => {
+                self.held_keys.push(*key);
             }
             InputEvent::KeyRelease(key) => {
                 self.held_keys.retain(|k| k != key);
             }
-            InputEvent::MousePress(button) => {
-                if !self.held_buttons.contains(button) {
-                    self.held_buttons.push(*button);
-                }
+            InputEvent::MousePress(button) if !self.held_buttons.contains(button) => {
+                self.held_buttons.push(*button);
             }
             InputEvent::MouseRelease(button) => {
                 self.held_buttons.retain(|b| b != button);
@@ -84,12 +291,180 @@ impl StateTracker {
     pub fn is_button_held(&self, button: &input_capture::MouseButton) -> bool {
         self.held_buttons.contains(button)
     }
+
+    /// Replace tracked key state with what the OS actually reports
+    pub fn reconcile(&mut self, held_keys: Vec<input_capture::Key>) {
+        self.held_keys = held_keys;
+    }
+
+    /// Copy of the held keys and buttons, for handing to an execution
+    pub fn snapshot(&self) -> HeldInput {
+        HeldInput {
+            keys: self.held_keys.clone(),
+            buttons: self.held_buttons.clone(),
+        }
+    }
+
+    /// Modifiers currently held, in press order
+    pub fn held_modifiers(&self) -> Vec<Modifier> {
+        self.held_keys
+            .iter()
+            .filter_map(|key| Modifier::from_key(*key))
+            .collect()
+    }
+}
+
+/// Hotkeys sorted by their string form, for stable listings
+fn sorted<'a>(hotkeys: impl Iterator<Item = &'a Hotkey>) -> Vec<Hotkey> {
+    let mut hotkeys: Vec<Hotkey> = hotkeys.cloned().collect();
+    hotkeys.sort_by_cached_key(Hotkey::to_string);
+    hotkeys
+}
+
+/// How many fired bindings `EngineSnapshot::recent` keeps
+const RECENT_FIRED: usize = 16;
+
+/// Events buffered per waiting action before the oldest are dropped
+const EVENT_BACKLOG: usize = 64;
+
+/// Point-in-time dump of engine state for bug reports
+///
+/// Registry lists, holds, remaps and repeats are sorted so dumps of the same
+/// bindings read alike; held keys and buttons are in press order, `recent`
+/// in firing order and `running` in start order.
+#[derive(Debug, Clone, Serialize)]
+pub struct EngineSnapshot {
+    /// Hotkeys bound to an action, including long-press and multi-tap ones
+    pub bindings: Vec<Hotkey>,
+    /// Hold bindings and the key each holds
+    pub holds: Vec<(Hotkey, Key)>,
+    /// Long-press hotkeys with a short-press action
+    pub short_presses: Vec<Hotkey>,
+    /// Bindings run on a dedicated thread
+    pub dedicated: Vec<Hotkey>,
+    /// Bindings with an activation condition, and whether it is met now
+    pub conditions: Vec<(Hotkey, bool)>,
+    pub held_keys: Vec<Key>,
+    pub held_buttons: Vec<MouseButton>,
+    /// Keys held by hold bindings, with the trigger holding each
+    pub active_holds: Vec<(Trigger, Key)>,
+    pub bypass_key: Option<Key>,
+    pub bypassed: bool,
+    /// Keys counting as a modifier in combos
+    pub modifier_remaps: Vec<(Key, Modifier)>,
+    /// Button and click count of an unresolved multi-click
+    pub pending_clicks: Option<(MouseButton, u32)>,
+    /// Trigger and press count of a multi-tap run in progress
+    pub pending_taps: Option<(Trigger, usize)>,
+    /// Long-press hotkey whose trigger is down, and whether it has fired
+    pub pending_long_press: Option<(Hotkey, bool)>,
+    /// Triggers keeping a `RepeatWhileHeld` loop running
+    pub repeats: Vec<Trigger>,
+    /// Most recently fired bindings, oldest first
+    pub recent: Vec<Hotkey>,
+    /// Labels of action tasks still running
+    pub running: Vec<String>,
+}
+
+/// Identifier of an action task tracked by `EventProcessor`
+pub type TaskId = u64;
+
+/// Action task started by the event loop, as listed by `running_actions`
+#[derive(Debug, Clone)]
+pub struct RunningAction {
+    pub id: TaskId,
+    /// Binding label (usually the hotkey) for display in a task manager
+    pub label: String,
+    pub started: Instant,
+}
+
+/// Stops a repeat loop started by `spawn_repeat`
+#[derive(Debug, Clone)]
+pub struct RepeatHandle {
+    held: Arc<AtomicBool>,
+}
+
+impl RepeatHandle {
+    /// Stop the loop before its next iteration
+    pub fn stop(&self) {
+        self.held.store(false, Ordering::Release);
+    }
+
+    /// Check if the loop is still running
+    pub fn is_running(&self) -> bool {
+        self.held.load(Ordering::Acquire)
+    }
+}
+
+/// Clicks of a multi-click button still waiting to be resolved
+struct PendingClicks {
+    modifiers: Vec<Modifier>,
+    device: Option<DeviceId>,
+    button: MouseButton,
+    count: u32,
+    last: Instant,
+}
+
+/// Recent presses of a trigger with a `Trigger::MultiTap` binding
+struct PendingTaps {
+    modifiers: Vec<Modifier>,
+    trigger: Trigger,
+    // Oldest first, at most as many as the highest bound count
+    presses: VecDeque<Instant>,
+    // Still held since the last press, so another press is auto-repeat
+    down: bool,
+}
+
+/// Press of a trigger with a `Trigger::LongPress` binding, not yet released
+struct PendingLongPress {
+    hotkey: Hotkey,
+    trigger: Trigger,
+    threshold: Duration,
+    pressed: Instant,
+    // The long-press action already ran, so the release does nothing
+    fired: bool,
 }
 
 /// Event processor matches events to bindings
+///
+/// Buttons with a `Trigger::MouseClicks` binding use delayed dispatch: a
+/// single click only fires once the double-click window has passed without
+/// another click, so the event loop must call `poll_pending` periodically.
+/// Buttons without such a binding still fire immediately.
+///
+/// `Trigger::MultiTap` bindings don't delay anything: each tap still acts
+/// as a normal press, and the press completing the count fires the
+/// multi-tap binding instead. Counting starts over after one fires, so with
+/// both a double and a triple tap bound for a key only the double fires.
+///
+/// A press of a `Trigger::LongPress` trigger fires nothing itself: its
+/// action runs from `poll_pending` once the threshold has passed, or from
+/// the release if polling was late, and releasing earlier runs the
+/// short-press action instead (see `BindingRegistry::bind_short_press`).
 pub struct EventProcessor {
     registry: BindingRegistry,
     state: StateTracker,
+    // Keys held on behalf of hold bindings, keyed by the trigger holding them
+    active_holds: HashMap<Trigger, Key>,
+    // While held, no binding matches and input behaves as without HandPlusPlus
+    bypass_key: Option<Key>,
+    // Keys that count as a modifier in combos instead of acting as keys
+    modifier_remaps: HashMap<Key, Modifier>,
+    pending_clicks: Option<PendingClicks>,
+    double_click_time: Duration,
+    pending_taps: Option<PendingTaps>,
+    pending_long_press: Option<PendingLongPress>,
+    recent: VecDeque<Hotkey>,
+    tasks: HashMap<TaskId, (RunningAction, AbortHandle)>,
+    next_task_id: TaskId,
+    // `RepeatWhileHeld` loops, keyed by the trigger keeping each alive
+    repeats: HashMap<Trigger, RepeatHandle>,
+    // Every processed event, for actions waiting on input
+    events: broadcast::Sender<InputEvent>,
+    // Modifiers the OS reported with the event being processed
+    os_modifiers: ModifierSet,
+    // Device the event being processed came from, if known
+    device: Option<DeviceId>,
 }
 
 impl EventProcessor {
@@ -97,16 +472,1391 @@ impl EventProcessor {
         Self {
             registry,
             state: StateTracker::new(),
+            active_holds: HashMap::new(),
+            bypass_key: None,
+            modifier_remaps: HashMap::new(),
+            pending_clicks: None,
+            // Windows' default GetDoubleClickTime
+            double_click_time: Duration::from_millis(500),
+            pending_taps: None,
+            pending_long_press: None,
+            recent: VecDeque::with_capacity(RECENT_FIRED),
+            tasks: HashMap::new(),
+            next_task_id: 0,
+            repeats: HashMap::new(),
+            events: broadcast::channel(EVENT_BACKLOG).0,
+            os_modifiers: ModifierSet::empty(),
+            device: None,
+        }
+    }
+
+    /// Set the window within which clicks count towards a multi-click
+    pub fn with_double_click_time(mut self, window: Duration) -> Self {
+        self.double_click_time = window;
+        self
+    }
+
+    /// Set a key that disables all bindings for as long as it is held
+    pub fn with_bypass_key(mut self, key: Key) -> Self {
+        self.bypass_key = Some(key);
+        self
+    }
+
+    /// Treat `key` as an extra `modifier` in every combo (e.g. CapsLock as
+    /// Ctrl); the key no longer triggers bindings of its own
+    pub fn with_modifier_remap(mut self, key: Key, modifier: Modifier) -> Self {
+        self.modifier_remaps.insert(key, modifier);
+        self
+    }
+
+    /// Have `capture` consume the remapped keys, so they lose their own
+    /// function and combos with them are suppressed like their modifier's
+    pub fn register_modifier_remaps(&self, capture: &mut dyn InputCapture) -> Result<()> {
+        for (&key, &modifier) in &self.modifier_remaps {
+            capture.remap_modifier(key, modifier)?;
         }
+        Ok(())
+    }
+
+    /// Check if the bypass key is currently held
+    pub fn is_bypassed(&self) -> bool {
+        self.bypass_key
+            .is_some_and(|key| self.state.is_key_held(&key))
     }
 
     /// Process an input event and return matching action (if any)
     pub fn process_event(&mut self, event: InputEvent) -> Option<Action> {
+        // Pairs with `TimelineExecutor` on the same target
+        tracing::trace!(target: "timeline", direction = "captured", ?event);
+
         // Update state tracker
         self.state.update(&event);
+        // Only fails when no action is waiting
+        let _ = self.events.send(event.clone());
+
+        let action = match event {
+            InputEvent::KeyPress(key) => self.on_press(Trigger::Key(key)),
+            InputEvent::MousePress(button) => self.on_press(Trigger::MouseButton(button)),
+            InputEvent::PhysicalKeyPress(key) => self.on_press(Trigger::PhysicalKey(key)),
+            InputEvent::KeyRelease(key) => self.on_release(Trigger::Key(key)),
+            InputEvent::MouseRelease(button) => self.on_release(Trigger::MouseButton(button)),
+            InputEvent::PhysicalKeyRelease(key) => self.on_release(Trigger::PhysicalKey(key)),
+            InputEvent::MouseMove { .. } | InputEvent::MouseWheel { .. } => None,
+            // Trigger releases may never arrive, so don't leave keys held
+            InputEvent::FocusChanged => {
+                self.stop_repeats();
+                self.release_active_holds()
+            }
+        };
+        self.handle_internal(action)
+    }
+
+    /// Process an event from a capture backend; modifiers the OS reports as
+    /// held count towards combos even if their presses were never seen, and
+    /// device-scoped bindings match events from their device
+    pub fn process_captured(&mut self, captured: CapturedEvent) -> Option<Action> {
+        self.os_modifiers = captured.modifiers;
+        self.device = captured.device_id;
+        let action = self.process_event(captured.event);
+        self.os_modifiers = ModifierSet::empty();
+        self.device = None;
+        action
+    }
+
+    /// Execution context for a binding fired by `hotkey`, seeded with the
+    /// currently held input (for `ReleaseAll` and friends)
+    pub fn context(&self, hotkey: Hotkey) -> ExecutionContext {
+        ExecutionContext::triggered_by(hotkey)
+            .with_held_input(self.state.snapshot())
+            .with_events(self.events.clone())
+    }
+
+    /// Capture everything needed to debug a misbehaving binding
+    ///
+    /// A binding to `Action::DumpState` logs this when it fires.
+    pub fn debug_snapshot(&self) -> EngineSnapshot {
+        let registry = &self.registry;
+        let mut holds: Vec<(Hotkey, Key)> = registry
+            .holds
+            .iter()
+            .map(|(hotkey, key)| (hotkey.clone(), *key))
+            .collect();
+        holds.sort_by_cached_key(|(hotkey, _)| hotkey.to_string());
+        let mut conditions: Vec<(Hotkey, bool)> = registry
+            .conditions
+            .iter()
+            .map(|(hotkey, condition)| (hotkey.clone(), condition.is_met()))
+            .collect();
+        conditions.sort_by_cached_key(|(hotkey, _)| hotkey.to_string());
+        let mut active_holds: Vec<(Trigger, Key)> = self
+            .active_holds
+            .iter()
+            .map(|(trigger, key)| (trigger.clone(), *key))
+            .collect();
+        active_holds.sort_by_cached_key(|(trigger, _)| format!("{trigger:?}"));
+        let mut modifier_remaps: Vec<(Key, Modifier)> = self
+            .modifier_remaps
+            .iter()
+            .map(|(key, modifier)| (*key, *modifier))
+            .collect();
+        modifier_remaps.sort_by_key(|&(key, modifier)| (modifier, key));
+        let mut repeats: Vec<Trigger> = self
+            .repeats
+            .iter()
+            .filter(|(_, handle)| handle.is_running())
+            .map(|(trigger, _)| trigger.clone())
+            .collect();
+        repeats.sort_by_cached_key(|trigger| format!("{trigger:?}"));
+
+        EngineSnapshot {
+            bindings: sorted(registry.bindings.keys()),
+            holds,
+            short_presses: sorted(registry.short_presses.keys()),
+            dedicated: sorted(registry.dedicated.iter()),
+            conditions,
+            held_keys: self.state.held_keys.clone(),
+            held_buttons: self.state.held_buttons.clone(),
+            active_holds,
+            bypass_key: self.bypass_key,
+            bypassed: self.is_bypassed(),
+            modifier_remaps,
+            pending_clicks: self
+                .pending_clicks
+                .as_ref()
+                .map(|pending| (pending.button, pending.count)),
+            pending_taps: self
+                .pending_taps
+                .as_ref()
+                .map(|taps| (taps.trigger.clone(), taps.presses.len())),
+            pending_long_press: self
+                .pending_long_press
+                .as_ref()
+                .map(|pending| (pending.hotkey.clone(), pending.fired)),
+            repeats,
+            recent: self.recent.iter().cloned().collect(),
+            running: self
+                .running_actions()
+                .into_iter()
+                .map(|task| task.label)
+                .collect(),
+        }
+    }
+
+    /// Apply an action the processor handles itself instead of returning it
+    fn handle_internal(&self, action: Option<Action>) -> Option<Action> {
+        match action {
+            Some(Action::DumpState) => {
+                tracing::info!("engine state: {:#?}", self.debug_snapshot());
+                None
+            }
+            action => action,
+        }
+    }
+
+    /// Track a spawned action task so it can be listed and cancelled
+    pub fn track_task(&mut self, label: impl Into<String>, handle: AbortHandle) -> TaskId {
+        self.tasks.retain(|_, (_, handle)| !handle.is_finished());
+
+        let id = self.next_task_id;
+        self.next_task_id += 1;
+        let task = RunningAction {
+            id,
+            label: label.into(),
+            started: Instant::now(),
+        };
+        self.tasks.insert(id, (task, handle));
+        id
+    }
 
-        // TODO: Match event against registered hotkeys
-        // For now, stub implementation
-        None
+    /// Tracked action tasks that have not finished, oldest first
+    pub fn running_actions(&self) -> Vec<RunningAction> {
+        let mut running: Vec<RunningAction> = self
+            .tasks
+            .values()
+            .filter(|(_, handle)| !handle.is_finished())
+            .map(|(task, _)| task.clone())
+            .collect();
+        running.sort_by_key(|task| task.id);
+        running
+    }
+
+    /// Abort a tracked action task; returns false if it was not running
+    pub fn cancel_action(&mut self, id: TaskId) -> bool {
+        match self.tasks.remove(&id) {
+            Some((_, handle)) if !handle.is_finished() => {
+                handle.abort();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Run a `RepeatWhileHeld` action returned by `process_event` for
+    /// `press` until that trigger is released
+    ///
+    /// Returns None if the trigger is already up again or already has a
+    /// loop running.
+    pub fn start_repeat<E: AsyncActionExecutor + Send + Sync + 'static>(
+        &mut self,
+        press: &InputEvent,
+        action: Action,
+        executor: Arc<E>,
+    ) -> Option<RepeatHandle> {
+        let trigger = match press {
+            InputEvent::KeyPress(key) if self.state.is_key_held(key) => Trigger::Key(*key),
+            InputEvent::MousePress(button) if self.state.is_button_held(button) => {
+                Trigger::MouseButton(*button)
+            }
+            InputEvent::PhysicalKeyPress(key) => Trigger::PhysicalKey(*key),
+            _ => return None,
+        };
+        if self
+            .repeats
+            .get(&trigger)
+            .is_some_and(RepeatHandle::is_running)
+        {
+            return None;
+        }
+        let hotkey = self
+            .recent
+            .back()
+            .filter(|hotkey| hotkey.trigger == trigger)
+            .cloned()
+            .unwrap_or_else(|| Hotkey::combo(&[], trigger.clone()));
+        let handle = spawn_repeat(hotkey, action, executor);
+        self.repeats.insert(trigger, handle.clone());
+        Some(handle)
+    }
+
+    /// Stop every running `RepeatWhileHeld` loop
+    pub fn stop_repeats(&mut self) {
+        for (_, handle) in self.repeats.drain() {
+            handle.stop();
+        }
+    }
+
+    /// Fire a pending click binding once its multi-click window has passed,
+    /// or a long-press binding once its threshold has
+    pub fn poll_pending(&mut self) -> Option<Action> {
+        let action = self.resolve_pending();
+        self.handle_internal(action)
+    }
+
+    fn resolve_pending(&mut self) -> Option<Action> {
+        let long_press = self
+            .pending_long_press
+            .as_mut()
+            .filter(|pending| !pending.fired && pending.pressed.elapsed() >= pending.threshold);
+        if let Some(pending) = long_press {
+            pending.fired = true;
+            let hotkey = pending.hotkey.clone();
+            if let Some(action) = self.long_press_action(hotkey) {
+                return Some(action);
+            }
+        }
+
+        let expired = self
+            .pending_clicks
+            .as_ref()
+            .is_some_and(|pending| pending.last.elapsed() > self.double_click_time);
+        if !expired {
+            return None;
+        }
+        self.pending_clicks
+            .take()
+            .and_then(|pending| self.click_action(pending))
+    }
+
+    /// Replace the bindings (e.g. on config reload) without restarting
+    ///
+    /// Tracked key state carries over. Keys held by hold bindings of the old
+    /// config are returned as a release action for the caller to run, since
+    /// their release edge may no longer be bound.
+    pub fn swap_registry(&mut self, registry: BindingRegistry) -> Option<Action> {
+        self.registry = registry;
+        self.pending_clicks = None;
+        self.pending_taps = None;
+        self.pending_long_press = None;
+        self.stop_repeats();
+        self.release_active_holds()
+    }
+
+    /// Re-read held keys from the OS (the event loop's handling of
+    /// `Action::ResyncState`)
+    pub async fn resync(&mut self, executor: &impl AsyncActionExecutor) -> Result<()> {
+        let held = executor.held_keys().await?;
+        tracing::info!(?held, "resynced key state");
+        self.state.reconcile(held);
+        Ok(())
+    }
+
+    /// Run the start hook (call once before processing events)
+    pub async fn start(&self, executor: &impl AsyncActionExecutor) -> Result<()> {
+        match &self.registry.on_start {
+            Some(action) => action.execute(executor).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Run the stop hook, bounded by `limit`, then release held keys
+    ///
+    /// A failing or slow stop hook is logged rather than returned, so the
+    /// key release always happens and shutdown is never blocked by it.
+    pub async fn shutdown(
+        &mut self,
+        executor: &impl AsyncActionExecutor,
+        limit: Duration,
+    ) -> Result<()> {
+        if let Some(action) = &self.registry.on_stop {
+            match tokio::time::timeout(limit, action.execute(executor)).await {
+                Ok(Ok(())) => {}
+                Ok(Err(err)) => tracing::warn!("stop hook failed: {err:#}"),
+                Err(_) => tracing::warn!("stop hook cancelled after {limit:?}"),
+            }
+        }
+
+        self.stop_repeats();
+        if let Some(release) = self.release_active_holds() {
+            release.execute(executor).await?;
+        }
+        Ok(())
+    }
+
+    /// Release every key held by a hold binding (call on focus loss or shutdown)
+    pub fn release_active_holds(&mut self) -> Option<Action> {
+        if self.active_holds.is_empty() {
+            return None;
+        }
+        let releases = self
+            .active_holds
+            .drain()
+            .map(|(_, key)| Action::ReleaseKey(key))
+            .collect();
+        Some(Action::Sequence(releases))
+    }
+
+    fn on_press(&mut self, trigger: Trigger) -> Option<Action> {
+        // Releases still go through, so holds started before the bypass end
+        if self.is_bypassed() {
+            return None;
+        }
+
+        // Remapped keys act only as modifiers, never as triggers
+        if let Trigger::Key(key) = &trigger {
+            if self.modifier_remaps.contains_key(key) {
+                return None;
+            }
+        }
+
+        // A modifier pressed on its own is the trigger, not part of the combo
+        let own_modifier = match &trigger {
+            Trigger::Key(key) => Modifier::from_key(*key),
+            _ => None,
+        };
+        let mut held = self.os_modifiers;
+        for key in &self.state.held_keys {
+            let modifier = self
+                .modifier_remaps
+                .get(key)
+                .copied()
+                .or_else(|| Modifier::sided_from_key(*key));
+            if let Some(modifier) = modifier {
+                held |= modifier.into();
+            }
+        }
+        if let Some(own) = own_modifier {
+            held = held.without(own);
+        }
+        let modifiers = self.registry.bound_modifiers(held, &trigger);
+        if let Some(action) = self.on_tap(&modifiers, &trigger) {
+            return Some(action);
+        }
+        if let Some((hotkey, threshold)) = self.registry.long_press(&modifiers, &trigger) {
+            // Auto-repeat must not restart the timer
+            let repeated = self
+                .pending_long_press
+                .as_ref()
+                .is_some_and(|pending| pending.trigger == trigger);
+            if !repeated {
+                self.pending_long_press = Some(PendingLongPress {
+                    hotkey: hotkey.clone(),
+                    trigger,
+                    threshold,
+                    pressed: Instant::now(),
+                    fired: false,
+                });
+            }
+            return None;
+        }
+        if let Trigger::MouseButton(button) = trigger {
+            if self.registry.max_clicks(&modifiers, button) > 1 {
+                return self.on_click(modifiers, button);
+            }
+        }
+
+        let hotkey = self.most_specific(
+            Hotkey::combo(&modifiers, trigger.clone()),
+            self.device.as_ref(),
+        );
+        if !self.registry.is_active(&hotkey) {
+            return None;
+        }
+
+        if let Some(key) = self.registry.get_hold(&hotkey) {
+            // OS auto-repeat must not press the held key twice
+            if self.active_holds.insert(trigger, key).is_some() {
+                return None;
+            }
+            self.record_fired(hotkey);
+            return Some(Action::HoldKey(key));
+        }
+
+        let action = self.registry.get_action(&hotkey).cloned();
+        if matches!(action, Some(Action::RepeatWhileHeld { .. }))
+            && self
+                .repeats
+                .get(&trigger)
+                .is_some_and(RepeatHandle::is_running)
+        {
+            // Auto-repeat or a second press must not start another loop
+            return None;
+        }
+        if action.is_some() {
+            self.record_fired(hotkey);
+        }
+        action
+    }
+
+    /// Prefer the most specific binding whose extra held keys are all down
+    /// and whose device, if it names one, is `device`
+    fn most_specific(&self, hotkey: Hotkey, device: Option<&DeviceId>) -> Hotkey {
+        self.registry
+            .bindings
+            .keys()
+            .chain(self.registry.holds.keys())
+            .filter(|bound| {
+                (!bound.held().is_empty() || bound.device.is_some())
+                    && bound.modifiers() == hotkey.modifiers()
+                    && bound.trigger == hotkey.trigger
+                    && bound.held().iter().all(|key| self.state.is_key_held(key))
+                    && bound
+                        .device
+                        .as_ref()
+                        .is_none_or(|bound| Some(bound) == device)
+            })
+            .max_by_key(|bound| (bound.device.is_some(), bound.held().len()))
+            .cloned()
+            .unwrap_or(hotkey)
+    }
+
+    /// Count a press towards multi-tap bindings; returns the action of one
+    /// this press completes
+    fn on_tap(&mut self, modifiers: &[Modifier], trigger: &Trigger) -> Option<Action> {
+        let now = Instant::now();
+        // Key presses arrive as a logical and a physical event; a run only
+        // counts (and is only interrupted by) the kind its trigger is
+        let physical = |trigger: &Trigger| matches!(trigger, Trigger::PhysicalKey(_));
+        match &mut self.pending_taps {
+            Some(taps) if physical(&taps.trigger) != physical(trigger) => return None,
+            Some(taps) if taps.trigger == *trigger && taps.modifiers == modifiers => {
+                if taps.down {
+                    return None;
+                }
+                taps.down = true;
+                taps.presses.push_back(now);
+            }
+            // A different key or button ends the run
+            _ => {
+                self.pending_taps = None;
+                if self.registry.max_taps(trigger) == 0 {
+                    return None;
+                }
+                self.pending_taps = Some(PendingTaps {
+                    modifiers: modifiers.to_vec(),
+                    trigger: trigger.clone(),
+                    presses: VecDeque::from([now]),
+                    down: true,
+                });
+            }
+        }
+
+        let taps = self.pending_taps.as_mut()?;
+        let max = self.registry.max_taps(&taps.trigger) as usize;
+        while taps.presses.len() > max {
+            taps.presses.pop_front();
+        }
+        // The last `count` presses must fit in the window, boundary included
+        let hotkey = self
+            .registry
+            .bindings
+            .keys()
+            .filter(|bound| bound.modifiers() == taps.modifiers)
+            .filter_map(|bound| match &bound.trigger {
+                Trigger::MultiTap {
+                    trigger,
+                    count,
+                    within,
+                } if **trigger == taps.trigger => Some((bound, *count as usize, *within)),
+                _ => None,
+            })
+            .filter(|&(_, count, within)| {
+                (1..=taps.presses.len()).contains(&count)
+                    && now - taps.presses[taps.presses.len() - count] <= within
+            })
+            .max_by_key(|&(_, count, _)| count)
+            .map(|(bound, _, _)| bound.clone())?;
+        if !self.registry.is_active(&hotkey) {
+            return None;
+        }
+        taps.presses.clear();
+        let action = self.registry.get_action(&hotkey).cloned();
+        if action.is_some() {
+            self.record_fired(hotkey);
+        }
+        action
+    }
+
+    fn on_click(&mut self, modifiers: Vec<Modifier>, button: MouseButton) -> Option<Action> {
+        let now = Instant::now();
+        let (count, flushed) = match self.pending_clicks.take() {
+            Some(pending)
+                if pending.button == button
+                    && pending.modifiers == modifiers
+                    && pending.device == self.device
+                    && now - pending.last <= self.double_click_time =>
+            {
+                (pending.count + 1, None)
+            }
+            // A different click ends the previous run, which fires as-is
+            other => (1, other.and_then(|pending| self.click_action(pending))),
+        };
+
+        let pending = PendingClicks {
+            modifiers,
+            device: self.device.clone(),
+            button,
+            count,
+            last: now,
+        };
+        if count >= self.registry.max_clicks(&pending.modifiers, button) {
+            // Nothing higher to wait for, so resolve right away
+            return flushed.or_else(|| self.click_action(pending));
+        }
+        self.pending_clicks = Some(pending);
+        flushed
+    }
+
+    fn click_action(&mut self, pending: PendingClicks) -> Option<Action> {
+        let trigger = match pending.count {
+            1 => Trigger::MouseButton(pending.button),
+            count => Trigger::MouseClicks {
+                button: pending.button,
+                count,
+            },
+        };
+        let hotkey = self.most_specific(
+            Hotkey::combo(&pending.modifiers, trigger),
+            pending.device.as_ref(),
+        );
+        let action = self.registry.get_action(&hotkey).cloned();
+        if action.is_some() {
+            self.record_fired(hotkey);
+        }
+        action
+    }
+
+    fn record_fired(&mut self, hotkey: Hotkey) {
+        if self.recent.len() == RECENT_FIRED {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(hotkey);
+    }
+
+    fn long_press_action(&mut self, hotkey: Hotkey) -> Option<Action> {
+        if !self.registry.is_active(&hotkey) {
+            return None;
+        }
+        let action = self.registry.get_action(&hotkey).cloned();
+        if action.is_some() {
+            self.record_fired(hotkey);
+        }
+        action
+    }
+
+    fn on_release(&mut self, trigger: Trigger) -> Option<Action> {
+        let long_press = self
+            .pending_long_press
+            .take_if(|pending| pending.trigger == trigger);
+        if let Some(pending) = long_press.filter(|pending| !pending.fired) {
+            if pending.pressed.elapsed() >= pending.threshold {
+                return self.long_press_action(pending.hotkey);
+            }
+            if !self.registry.is_active(&pending.hotkey) {
+                return None;
+            }
+            return self.registry.short_presses.get(&pending.hotkey).cloned();
+        }
+        if let Some(taps) = &mut self.pending_taps {
+            if taps.trigger == trigger {
+                taps.down = false;
+            }
+        }
+        // Modifiers may already be up, so releases match on the trigger alone
+        if let Some(handle) = self.repeats.remove(&trigger) {
+            handle.stop();
+        }
+        self.active_holds.remove(&trigger).map(Action::ReleaseKey)
+    }
+}
+
+/// Execute a bound action inside a span labelled with its hotkey
+pub async fn dispatch(
+    hotkey: &Hotkey,
+    action: &Action,
+    executor: &impl AsyncActionExecutor,
+) -> Result<()> {
+    dispatch_with(
+        &ExecutionContext::triggered_by(hotkey.clone()),
+        action,
+        executor,
+    )
+    .await
+}
+
+/// Like `dispatch`, with a context built by the caller (usually
+/// `EventProcessor::context`)
+pub async fn dispatch_with(
+    ctx: &ExecutionContext,
+    action: &Action,
+    executor: &impl AsyncActionExecutor,
+) -> Result<()> {
+    action
+        .execute_with(executor, ctx)
+        .instrument(tracing::info_span!("binding", hotkey = ?ctx.trigger))
+        .await
+}
+
+/// Execute a bound action on its own OS thread and single-threaded runtime
+pub fn spawn_dedicated<E: AsyncActionExecutor + Send + Sync + 'static>(
+    hotkey: Hotkey,
+    action: Action,
+    executor: Arc<E>,
+) -> JoinHandle<Result<()>> {
+    std::thread::spawn(move || {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()?;
+        runtime.block_on(dispatch(&hotkey, &action, executor.as_ref()))
+    })
+}
+
+/// Run a `RepeatWhileHeld` action on its own OS thread until the returned
+/// handle is stopped (or the action's `max_duration` passes)
+pub fn spawn_repeat<E: AsyncActionExecutor + Send + Sync + 'static>(
+    hotkey: Hotkey,
+    action: Action,
+    executor: Arc<E>,
+) -> RepeatHandle {
+    let held = Arc::new(AtomicBool::new(true));
+    let handle = RepeatHandle { held: held.clone() };
+    std::thread::spawn(move || {
+        let ctx = ExecutionContext {
+            held: Some(held.clone()),
+            ..ExecutionContext::triggered_by(hotkey.clone())
+        };
+        let result = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .map_err(anyhow::Error::from)
+            .and_then(|runtime| {
+                runtime.block_on(
+                    action
+                        .execute_with(executor.as_ref(), &ctx)
+                        .instrument(tracing::info_span!("binding", hotkey = ?hotkey)),
+                )
+            });
+        held.store(false, Ordering::Release);
+        if let Err(err) = result {
+            tracing::error!("repeat loop failed: {err:#}");
+        }
+    });
+    handle
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use action_executor::{ActionExecutor, ExecutorError, InputState, MockExecutor, RecordedCall};
+    use input_capture::PhysicalKey;
+
+    fn push_to_talk() -> EventProcessor {
+        EventProcessor::new(
+            BindingRegistry::new().bind_hold(Hotkey::mouse(MouseButton::Button4), Key::F12),
+        )
+    }
+
+    /// Run whatever each event returns, as the event loop does
+    async fn feed(
+        processor: &mut EventProcessor,
+        executor: &MockExecutor,
+        events: impl IntoIterator<Item = InputEvent>,
+    ) {
+        for event in events {
+            if let Some(action) = processor.process_event(event) {
+                action.execute(executor).await.unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn repeat_runs_while_held_and_stops_on_release() {
+        let interval = Duration::from_millis(20);
+        let registry = BindingRegistry::new().bind(
+            Hotkey::key(Key::F1),
+            Action::RepeatWhileHeld {
+                actions: vec![Action::PressKey(Key::R)],
+                interval,
+                max_duration: None,
+            },
+        );
+        let mut processor = EventProcessor::new(registry);
+        let executor = Arc::new(MockExecutor::new());
+        let press = InputEvent::KeyPress(Key::F1);
+        let action = processor.process_event(press.clone()).unwrap();
+        let handle = processor
+            .start_repeat(&press, action.clone(), executor.clone())
+            .unwrap();
+        // Auto-repeat doesn't fire the binding again, and a second start
+        // for the same press doesn't start a second loop
+        assert!(processor.process_event(press.clone()).is_none());
+        assert!(processor
+            .start_repeat(&press, action, executor.clone())
+            .is_none());
+
+        // Iterations at 0, 20, .. 100ms, with slack for a loaded machine
+        std::thread::sleep(interval * 5 + interval / 2);
+        processor.process_event(InputEvent::KeyRelease(Key::F1));
+        assert!(!handle.is_running());
+        // An iteration already underway may still finish
+        std::thread::sleep(interval * 2);
+        let taps = executor.calls().len() / 2;
+        assert!((3..=8).contains(&taps), "{taps} iterations");
+        std::thread::sleep(interval * 3);
+        assert_eq!(executor.calls().len() / 2, taps);
+        assert!(executor
+            .calls()
+            .iter()
+            .all(|call| matches!(call, RecordedCall::Key(Key::R, _))));
+    }
+
+    #[test]
+    fn modifier_order_does_not_matter() {
+        let registry = BindingRegistry::new().bind(
+            Hotkey::combo(&[Modifier::Ctrl, Modifier::Shift], Trigger::Key(Key::P)),
+            Action::PressKey(Key::X),
+        );
+        let reversed = Hotkey::new(
+            &[Modifier::Shift, Modifier::Ctrl, Modifier::Shift],
+            Trigger::Key(Key::P),
+            &[],
+            None,
+        );
+        assert_eq!(reversed.modifiers(), [Modifier::Ctrl, Modifier::Shift]);
+        assert!(registry.get_action(&reversed).is_some());
+
+        let mut processor = EventProcessor::new(registry);
+        processor.process_event(InputEvent::KeyPress(Key::Shift));
+        processor.process_event(InputEvent::KeyPress(Key::Ctrl));
+        let action = processor.process_event(InputEvent::KeyPress(Key::P));
+        assert!(
+            matches!(action, Some(Action::PressKey(Key::X))),
+            "{action:?}"
+        );
+    }
+
+    #[test]
+    fn physical_binding_matches_the_position_under_any_layout() {
+        let mut processor = EventProcessor::new(BindingRegistry::new().bind(
+            Hotkey::physical(Key::Q.physical()),
+            Action::PressKey(Key::X),
+        ));
+        // On AZERTY the QWERTY Q position types A
+        assert!(processor
+            .process_event(InputEvent::KeyPress(Key::A))
+            .is_none());
+        let action = processor.process_event(InputEvent::PhysicalKeyPress(PhysicalKey(16)));
+        assert!(
+            matches!(action, Some(Action::PressKey(Key::X))),
+            "{action:?}"
+        );
+        processor.process_event(InputEvent::KeyRelease(Key::A));
+        processor.process_event(InputEvent::PhysicalKeyRelease(PhysicalKey(16)));
+        // The logical Q elsewhere on the board doesn't match
+        assert!(processor
+            .process_event(InputEvent::KeyPress(Key::Q))
+            .is_none());
+        assert!(processor
+            .process_event(InputEvent::PhysicalKeyPress(PhysicalKey(30)))
+            .is_none());
+    }
+
+    #[test]
+    fn device_scoped_bindings_only_match_their_device() {
+        let pad = DeviceId("Macro Pad".into());
+        let mut processor = EventProcessor::new(
+            BindingRegistry::new()
+                .bind(
+                    Hotkey::key(Key::F1).from_device(pad.clone()),
+                    Action::PressKey(Key::X),
+                )
+                .bind(Hotkey::key(Key::F1), Action::PressKey(Key::Y))
+                .bind(
+                    Hotkey::key(Key::F2).from_device(pad.clone()),
+                    Action::PressKey(Key::Z),
+                ),
+        );
+        let mut tap = |trigger, device: Option<&DeviceId>| {
+            let captured = |event| CapturedEvent {
+                event,
+                modifiers: ModifierSet::empty(),
+                device_id: device.cloned(),
+                held_for: None,
+            };
+            let action = processor.process_captured(captured(InputEvent::KeyPress(trigger)));
+            processor.process_captured(captured(InputEvent::KeyRelease(trigger)));
+            match action {
+                Some(Action::PressKey(key)) => Some(key),
+                other => {
+                    assert!(other.is_none(), "{other:?}");
+                    None
+                }
+            }
+        };
+        let keyboard = DeviceId("AT Keyboard".into());
+        assert_eq!(tap(Key::F1, Some(&pad)), Some(Key::X));
+        assert_eq!(tap(Key::F1, Some(&keyboard)), Some(Key::Y));
+        // Backends that can't tell devices apart only hit unscoped bindings
+        assert_eq!(tap(Key::F1, None), Some(Key::Y));
+        assert_eq!(tap(Key::F2, Some(&pad)), Some(Key::Z));
+        assert_eq!(tap(Key::F2, Some(&keyboard)), None);
+        assert_eq!(tap(Key::F2, None), None);
+    }
+
+    /// Press and release `key`, returning what the press fired
+    fn tap_key(processor: &mut EventProcessor, key: Key) -> Option<Action> {
+        let action = processor.process_event(InputEvent::KeyPress(key));
+        processor.process_event(InputEvent::KeyRelease(key));
+        action
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn multi_tap_fires_within_the_window_boundary_included() {
+        let within = Duration::from_millis(300);
+        let mut processor = EventProcessor::new(BindingRegistry::new().bind(
+            Hotkey::multi_tap(Trigger::Key(Key::Shift), 2, within),
+            Action::PressKey(Key::X),
+        ));
+        let fired = |action: Option<Action>| matches!(action, Some(Action::PressKey(Key::X)));
+
+        assert!(!fired(tap_key(&mut processor, Key::Shift)));
+        tokio::time::advance(within).await;
+        assert!(fired(tap_key(&mut processor, Key::Shift)));
+
+        // Just outside the window the second press starts a new run
+        assert!(!fired(tap_key(&mut processor, Key::Shift)));
+        tokio::time::advance(within + Duration::from_millis(1)).await;
+        assert!(!fired(tap_key(&mut processor, Key::Shift)));
+        assert!(fired(tap_key(&mut processor, Key::Shift)));
+
+        // Another key in between resets the count
+        assert!(!fired(tap_key(&mut processor, Key::Shift)));
+        tap_key(&mut processor, Key::A);
+        assert!(!fired(tap_key(&mut processor, Key::Shift)));
+
+        // Auto-repeat while held is not a second tap
+        tokio::time::advance(within * 2).await;
+        assert!(!fired(
+            processor.process_event(InputEvent::KeyPress(Key::Shift))
+        ));
+        assert!(!fired(
+            processor.process_event(InputEvent::KeyPress(Key::Shift))
+        ));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn long_press_fires_once_past_the_threshold_and_short_press_before() {
+        let threshold = Duration::from_secs(1);
+        let hotkey = Hotkey::long_press(Trigger::Key(Key::Escape), threshold);
+        let mut processor = EventProcessor::new(
+            BindingRegistry::new()
+                .bind(hotkey.clone(), Action::PressKey(Key::Q))
+                .bind_short_press(hotkey, Action::PressKey(Key::Escape)),
+        );
+        let press = InputEvent::KeyPress(Key::Escape);
+        let release = InputEvent::KeyRelease(Key::Escape);
+        let pressed = |action: Option<Action>| match action {
+            Some(Action::PressKey(key)) => Some(key),
+            other => {
+                assert!(other.is_none(), "{other:?}");
+                None
+            }
+        };
+
+        // Held across the threshold: fires from polling, and only once
+        assert_eq!(pressed(processor.process_event(press.clone())), None);
+        tokio::time::advance(threshold - Duration::from_millis(1)).await;
+        assert_eq!(pressed(processor.poll_pending()), None);
+        // Auto-repeat doesn't restart the timer
+        assert_eq!(pressed(processor.process_event(press.clone())), None);
+        tokio::time::advance(Duration::from_millis(1)).await;
+        assert_eq!(pressed(processor.poll_pending()), Some(Key::Q));
+        assert_eq!(pressed(processor.process_event(press.clone())), None);
+        assert_eq!(pressed(processor.poll_pending()), None);
+        assert_eq!(pressed(processor.process_event(release.clone())), None);
+
+        // Released before it: the short-press action instead
+        processor.process_event(press.clone());
+        tokio::time::advance(threshold / 2).await;
+        assert_eq!(pressed(processor.poll_pending()), None);
+        assert_eq!(
+            pressed(processor.process_event(release.clone())),
+            Some(Key::Escape)
+        );
+
+        // Polled late: the release fires the long press
+        processor.process_event(press);
+        tokio::time::advance(threshold * 2).await;
+        assert_eq!(pressed(processor.process_event(release)), Some(Key::Q));
+    }
+
+    #[test]
+    fn sided_modifiers_match_their_side_and_unsided_ones_either() {
+        let mut processor = EventProcessor::new(
+            BindingRegistry::new()
+                .bind(
+                    Hotkey::combo(&[Modifier::AltRight], Trigger::Key(Key::A)),
+                    Action::PressKey(Key::X),
+                )
+                .bind(
+                    Hotkey::combo(&[Modifier::Alt], Trigger::Key(Key::B)),
+                    Action::PressKey(Key::Y),
+                ),
+        );
+        let mut with = |modifier: Key, key: Key| {
+            processor.process_event(InputEvent::KeyPress(modifier));
+            let action = tap_key(&mut processor, key);
+            processor.process_event(InputEvent::KeyRelease(modifier));
+            match action {
+                Some(Action::PressKey(key)) => Some(key),
+                _ => None,
+            }
+        };
+        assert_eq!(with(Key::Alt, Key::A), None);
+        assert_eq!(with(Key::AltGr, Key::A), Some(Key::X));
+        assert_eq!(with(Key::Alt, Key::B), Some(Key::Y));
+        assert_eq!(with(Key::AltGr, Key::B), Some(Key::Y));
+    }
+
+    #[test]
+    fn remapped_key_acts_only_as_its_modifier() {
+        let mut processor = EventProcessor::new(
+            BindingRegistry::new()
+                .bind(
+                    Hotkey::combo(&[Modifier::Ctrl], Trigger::Key(Key::A)),
+                    Action::PressKey(Key::X),
+                )
+                .bind(Hotkey::key(Key::CapsLock), Action::PressKey(Key::Y)),
+        )
+        .with_modifier_remap(Key::CapsLock, Modifier::Ctrl);
+        assert!(processor
+            .process_event(InputEvent::KeyPress(Key::CapsLock))
+            .is_none());
+        let action = processor.process_event(InputEvent::KeyPress(Key::A));
+        assert!(
+            matches!(action, Some(Action::PressKey(Key::X))),
+            "{action:?}"
+        );
+        processor.process_event(InputEvent::KeyRelease(Key::A));
+        processor.process_event(InputEvent::KeyRelease(Key::CapsLock));
+        assert!(processor
+            .process_event(InputEvent::KeyPress(Key::A))
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn hold_binding_holds_key_until_trigger_release() {
+        let mut processor = push_to_talk();
+        let executor = MockExecutor::new();
+        let press = InputEvent::MousePress(MouseButton::Button4);
+        // The second press is auto-repeat and must not press again
+        feed(&mut processor, &executor, [press.clone(), press]).await;
+        assert_eq!(
+            processor.debug_snapshot().active_holds,
+            [(Trigger::MouseButton(MouseButton::Button4), Key::F12)]
+        );
+        feed(
+            &mut processor,
+            &executor,
+            [InputEvent::MouseRelease(MouseButton::Button4)],
+        )
+        .await;
+        assert_eq!(
+            executor.calls(),
+            [
+                RecordedCall::Key(Key::F12, InputState::Press),
+                RecordedCall::Key(Key::F12, InputState::Release),
+            ]
+        );
+        assert!(processor.debug_snapshot().active_holds.is_empty());
+    }
+
+    #[tokio::test]
+    async fn focus_change_releases_hold_once() {
+        let mut processor = push_to_talk();
+        let executor = MockExecutor::new();
+        feed(
+            &mut processor,
+            &executor,
+            [
+                InputEvent::MousePress(MouseButton::Button4),
+                InputEvent::FocusChanged,
+                // The trigger's release after the focus change is a no-op
+                InputEvent::MouseRelease(MouseButton::Button4),
+            ],
+        )
+        .await;
+        assert_eq!(
+            executor.calls(),
+            [
+                RecordedCall::Key(Key::F12, InputState::Press),
+                RecordedCall::Key(Key::F12, InputState::Release),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn shutdown_releases_hold() {
+        let mut processor = push_to_talk();
+        let executor = MockExecutor::new();
+        feed(
+            &mut processor,
+            &executor,
+            [InputEvent::MousePress(MouseButton::Button4)],
+        )
+        .await;
+        processor
+            .shutdown(&executor, Duration::from_secs(1))
+            .await
+            .unwrap();
+        assert_eq!(
+            executor.calls().last(),
+            Some(&RecordedCall::Key(Key::F12, InputState::Release))
+        );
+        assert!(processor.release_active_holds().is_none());
+    }
+
+    /// Executor blocking the calling thread on one key, like a backend
+    /// stuck in a syscall
+    struct Blocking {
+        inner: MockExecutor,
+        slow: Key,
+        delay: Duration,
+    }
+
+    impl ActionExecutor for Blocking {
+        fn simulate_key(&self, key: Key, state: InputState) -> Result<(), ExecutorError> {
+            if key == self.slow {
+                std::thread::sleep(self.delay);
+            }
+            ActionExecutor::simulate_key(&self.inner, key, state)
+        }
+
+        fn simulate_mouse(
+            &self,
+            button: MouseButton,
+            state: InputState,
+        ) -> Result<(), ExecutorError> {
+            ActionExecutor::simulate_mouse(&self.inner, button, state)
+        }
+
+        fn mouse_move_abs(&self, x: i32, y: i32) -> Result<(), ExecutorError> {
+            ActionExecutor::mouse_move_abs(&self.inner, x, y)
+        }
+
+        fn mouse_move_rel(&self, dx: i32, dy: i32) -> Result<(), ExecutorError> {
+            ActionExecutor::mouse_move_rel(&self.inner, dx, dy)
+        }
+    }
+
+    // Single-threaded like the event loop, so anything blocking it on this
+    // runtime would hold up the fast binding
+    #[tokio::test(flavor = "current_thread")]
+    async fn dedicated_binding_does_not_delay_a_fast_one() {
+        let executor = Arc::new(Blocking {
+            inner: MockExecutor::new(),
+            slow: Key::F1,
+            delay: Duration::from_millis(300),
+        });
+        let (slow, fast) = (Hotkey::key(Key::F1), Hotkey::key(Key::A));
+        let registry = BindingRegistry::new()
+            .bind_dedicated(slow.clone(), Action::PressKey(Key::F1))
+            .bind(fast.clone(), Action::PressKey(Key::A));
+        assert!(registry.is_dedicated(&slow));
+        assert!(!registry.is_dedicated(&fast));
+
+        let started = Instant::now();
+        let slow_action = registry.get_action(&slow).unwrap().clone();
+        let slow_run = spawn_dedicated(slow, slow_action, executor.clone());
+        let fast_action = registry.get_action(&fast).unwrap();
+        dispatch(&fast, fast_action, executor.as_ref())
+            .await
+            .unwrap();
+        let fast_done = started.elapsed();
+        slow_run.join().unwrap().unwrap();
+
+        // The slow press and release block for 300ms each
+        assert!(fast_done < Duration::from_millis(200), "took {fast_done:?}");
+        assert!(started.elapsed() >= Duration::from_millis(600));
+        assert_eq!(
+            executor.inner.calls(),
+            [
+                RecordedCall::Key(Key::A, InputState::Press),
+                RecordedCall::Key(Key::A, InputState::Release),
+                RecordedCall::Key(Key::F1, InputState::Press),
+                RecordedCall::Key(Key::F1, InputState::Release),
+            ]
+        );
+    }
+
+    #[test]
+    fn registered_hotkeys_lists_each_binding_once() {
+        let long_press = Hotkey::long_press(Trigger::Key(Key::Escape), Duration::from_secs(1));
+        let registry = BindingRegistry::new()
+            .bind(Hotkey::key(Key::F3), Action::PressKey(Key::C))
+            .bind_hold(Hotkey::key(Key::F1), Key::F12)
+            .bind(long_press.clone(), Action::PressKey(Key::Q))
+            .bind_short_press(long_press.clone(), Action::PressKey(Key::Escape));
+        // Sorted by string form: "Escape>1000ms", "F1", "F3"
+        assert_eq!(
+            registry.registered_hotkeys(),
+            [long_press, Hotkey::key(Key::F1), Hotkey::key(Key::F3)]
+        );
+        assert!(BindingRegistry::new().registered_hotkeys().is_empty());
+    }
+
+    #[test]
+    fn snapshot_covers_registry_and_tracker_state() {
+        let long_press = Hotkey::long_press(Trigger::Key(Key::Escape), Duration::from_secs(1));
+        let never = Condition::TimeOfDay {
+            from: NaiveTime::MIN,
+            to: NaiveTime::MIN,
+        };
+        let registry = BindingRegistry::new()
+            .bind(long_press.clone(), Action::PressKey(Key::Q))
+            .bind_short_press(long_press.clone(), Action::PressKey(Key::Escape))
+            .bind_hold(Hotkey::mouse(MouseButton::Button4), Key::F12)
+            .bind_dedicated(Hotkey::key(Key::F2), Action::PressKey(Key::B))
+            .bind_when(Hotkey::key(Key::F3), never, Action::PressKey(Key::C));
+        let mut processor = EventProcessor::new(registry)
+            .with_bypass_key(Key::ScrollLock)
+            .with_modifier_remap(Key::CapsLock, Modifier::Ctrl);
+        processor.process_event(InputEvent::MousePress(MouseButton::Button4));
+        processor.process_event(InputEvent::KeyPress(Key::Escape));
+
+        let snapshot = processor.debug_snapshot();
+        // Sorted by string form: "Escape>1000ms", "F2", "F3"
+        assert_eq!(
+            snapshot.bindings,
+            [
+                long_press.clone(),
+                Hotkey::key(Key::F2),
+                Hotkey::key(Key::F3),
+            ]
+        );
+        assert_eq!(
+            snapshot.holds,
+            [(Hotkey::mouse(MouseButton::Button4), Key::F12)]
+        );
+        assert_eq!(snapshot.short_presses, std::slice::from_ref(&long_press));
+        assert_eq!(snapshot.dedicated, [Hotkey::key(Key::F2)]);
+        assert_eq!(snapshot.conditions, [(Hotkey::key(Key::F3), false)]);
+        assert_eq!(snapshot.held_keys, [Key::Escape]);
+        assert_eq!(snapshot.held_buttons, [MouseButton::Button4]);
+        assert_eq!(
+            snapshot.active_holds,
+            [(Trigger::MouseButton(MouseButton::Button4), Key::F12)]
+        );
+        assert_eq!(snapshot.bypass_key, Some(Key::ScrollLock));
+        assert!(!snapshot.bypassed);
+        assert_eq!(snapshot.modifier_remaps, [(Key::CapsLock, Modifier::Ctrl)]);
+        assert_eq!(snapshot.pending_long_press, Some((long_press, false)));
+        assert_eq!(snapshot.recent, [Hotkey::mouse(MouseButton::Button4)]);
+    }
+
+    #[test]
+    fn dump_state_binding_is_handled_by_the_processor() {
+        let dump = Hotkey::combo(&[Modifier::Ctrl], Trigger::Key(Key::F12));
+        let mut processor =
+            EventProcessor::new(BindingRegistry::new().bind(dump.clone(), Action::DumpState));
+        processor.process_event(InputEvent::KeyPress(Key::Ctrl));
+        // Logged rather than handed to the event loop to execute
+        assert!(processor
+            .process_event(InputEvent::KeyPress(Key::F12))
+            .is_none());
+        assert_eq!(processor.debug_snapshot().recent, [dump]);
+    }
+
+    #[test]
+    fn held_keys_match_in_any_press_order() {
+        let hotkey = Hotkey::key(Key::C).while_held(&[Key::B, Key::A]);
+        let mut processor =
+            EventProcessor::new(BindingRegistry::new().bind(hotkey, Action::PressKey(Key::X)));
+        processor.process_event(InputEvent::KeyPress(Key::A));
+        assert!(processor
+            .process_event(InputEvent::KeyPress(Key::C))
+            .is_none());
+        processor.process_event(InputEvent::KeyRelease(Key::C));
+        processor.process_event(InputEvent::KeyPress(Key::B));
+        assert!(matches!(
+            processor.process_event(InputEvent::KeyPress(Key::C)),
+            Some(Action::PressKey(Key::X))
+        ));
+    }
+
+    /// Deterministic event mix for the soak test (an LCG, as the test only
+    /// needs variety, not quality)
+    struct EventMix(u64);
+
+    impl EventMix {
+        fn next(&mut self) -> InputEvent {
+            const KEYS: [Key; 6] = [Key::Ctrl, Key::Shift, Key::A, Key::B, Key::Escape, Key::F1];
+            const BUTTONS: [MouseButton; 2] = [MouseButton::Left, MouseButton::Button4];
+            self.0 = self
+                .0
+                .wrapping_mul(6_364_136_223_846_793_005)
+                .wrapping_add(1_442_695_040_888_963_407);
+            let roll = (self.0 >> 33) as usize;
+            let key = KEYS[roll / 16 % KEYS.len()];
+            let button = BUTTONS[roll / 16 % BUTTONS.len()];
+            match roll % 16 {
+                0..=4 => InputEvent::KeyPress(key),
+                5..=9 => InputEvent::KeyRelease(key),
+                10 | 11 => InputEvent::MousePress(button),
+                12 | 13 => InputEvent::MouseRelease(button),
+                14 => InputEvent::MouseMove {
+                    x: (roll % 1920) as i32,
+                    y: (roll % 1080) as i32,
+                },
+                _ if roll % 64 == 15 => InputEvent::FocusChanged,
+                _ => InputEvent::MouseWheel { dx: 0, dy: 1 },
+            }
+        }
+    }
+
+    // Action futures aren't `Send`, so they run on a `LocalSet` as in the
+    // event loop. Time is paused, so delays and the wind-down deadline are
+    // virtual; run with `cargo test -- --ignored`.
+    #[tokio::test(flavor = "current_thread", start_paused = true)]
+    #[ignore = "soak"]
+    async fn soak_million_events_stays_bounded() {
+        tokio::task::LocalSet::new().run_until(soak()).await;
+    }
+
+    async fn soak() {
+        const EVENTS: usize = 1_000_000;
+        let ms = Duration::from_millis;
+        let registry = BindingRegistry::new()
+            .bind(
+                Hotkey::combo(&[Modifier::Ctrl], Trigger::Key(Key::A)),
+                Action::PressKey(Key::X),
+            )
+            .bind(
+                Hotkey::key(Key::B),
+                Action::Sequence(vec![Action::Delay(ms(1)), Action::PressKey(Key::Y)]),
+            )
+            .bind(
+                Hotkey::double_click(MouseButton::Left),
+                Action::PressKey(Key::Z),
+            )
+            .bind(
+                Hotkey::multi_tap(Trigger::Key(Key::Shift), 2, ms(300)),
+                Action::PressKey(Key::Q),
+            )
+            .bind(
+                Hotkey::long_press(Trigger::Key(Key::Escape), ms(500)),
+                Action::PressKey(Key::W),
+            )
+            .bind_short_press(
+                Hotkey::long_press(Trigger::Key(Key::Escape), ms(500)),
+                Action::PressKey(Key::E),
+            )
+            .bind(
+                Hotkey::key(Key::F1),
+                Action::RepeatWhileHeld {
+                    actions: vec![Action::PressKey(Key::R)],
+                    interval: ms(5),
+                    max_duration: Some(ms(200)),
+                },
+            )
+            .bind_hold(Hotkey::mouse(MouseButton::Button4), Key::F12);
+        let mut processor = EventProcessor::new(registry);
+        let executor = Arc::new(MockExecutor::new());
+        let mut mix = EventMix(0x5eed);
+        let mut repeats = Vec::new();
+
+        for index in 0..EVENTS {
+            let event = mix.next();
+            let action = match processor.process_event(event.clone()) {
+                Some(action) => Some(action),
+                None if index % 64 == 0 => processor.poll_pending(),
+                None => None,
+            };
+            match action {
+                Some(action @ Action::RepeatWhileHeld { .. }) => {
+                    repeats.extend(processor.start_repeat(&event, action, executor.clone()));
+                }
+                Some(action) => {
+                    let executor = executor.clone();
+                    let label = action.kind();
+                    let task =
+                        tokio::task::spawn_local(async move { action.execute(&*executor).await });
+                    processor.track_task(label, task.abort_handle());
+                }
+                None => {}
+            }
+
+            if index % 1024 == 0 {
+                // Let spawned actions and their delays run, as an event loop
+                // waiting for input would
+                tokio::time::sleep(ms(2)).await;
+                repeats.retain(RepeatHandle::is_running);
+                let snapshot = processor.debug_snapshot();
+                assert!(snapshot.held_keys.len() <= 6, "{:?}", snapshot.held_keys);
+                assert!(snapshot.held_buttons.len() <= 2);
+                assert!(snapshot.active_holds.len() <= 1);
+                assert!(snapshot.repeats.len() <= 1);
+                assert!(snapshot.recent.len() <= RECENT_FIRED);
+                assert!(
+                    processor.tasks.len() <= 4096,
+                    "{} tasks",
+                    processor.tasks.len()
+                );
+                assert!(processor.repeats.len() <= 1);
+                assert!(repeats.len() <= 2, "{} repeat loops", repeats.len());
+            }
+        }
+
+        // Wind down: every task finishes and every repeat loop stops
+        processor.process_event(InputEvent::FocusChanged);
+        processor.stop_repeats();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !processor.running_actions().is_empty()
+            || repeats.iter().any(RepeatHandle::is_running)
+        {
+            assert!(Instant::now() < deadline, "tasks still running");
+            tokio::time::sleep(ms(10)).await;
+        }
+        assert!(processor.debug_snapshot().repeats.is_empty());
+        assert!(!executor.calls().is_empty());
     }
 }
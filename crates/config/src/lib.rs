@@ -6,6 +6,10 @@ use binding_engine::BindingRegistry;
 use input_capture::{Hotkey, Key, Modifier, MouseButton, Trigger};
 use std::time::Duration;
 
+mod load;
+
+pub use load::{load_and_register, load_from_dir};
+
 /// Register all user-defined bindings
 pub fn register_all_bindings() -> BindingRegistry {
     BindingRegistry::new()
@@ -39,9 +43,15 @@ pub mod gaming {
                 Action::Delay(Duration::from_millis(50)),
             ],
             interval: Duration::from_millis(50),
+            max_duration: Some(Duration::from_secs(30)),
         };
         (hotkey, action)
     }
 
+    pub fn push_to_talk_binding() -> (Hotkey, Key) {
+        // Mouse4 held → V held (released together with Mouse4)
+        (Hotkey::mouse(MouseButton::Button4), Key::V)
+    }
+
     // TODO: Add more gaming patterns
 }
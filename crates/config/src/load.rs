@@ -0,0 +1,229 @@
+// File-based configuration: a conf.d-style directory of TOML files,
+// each contributing bindings to a single registry.
+
+use action_executor::Action;
+use anyhow::{bail, Context, Result};
+use binding_engine::{BindingRegistry, Condition};
+use input_capture::{Hotkey, InputCapture};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Bindings contributed by a single config file
+#[derive(Debug, Deserialize)]
+struct ConfigFile {
+    #[serde(default)]
+    bindings: Vec<BindingEntry>,
+    on_start: Option<Action>,
+    on_stop: Option<Action>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BindingEntry {
+    hotkey: Hotkey,
+    action: Action,
+    /// Only fire while this condition holds
+    when: Option<Condition>,
+}
+
+/// Load bindings from every `.toml` file in a directory
+///
+/// Files are merged in alphabetical order and files starting with `_` are
+/// skipped. Binding the same hotkey twice (in one file or across files) is
+/// an error naming both sources.
+pub fn load_from_dir(path: impl AsRef<Path>) -> Result<BindingRegistry> {
+    load(path.as_ref()).map(|(registry, _)| registry)
+}
+
+/// Load bindings like `load_from_dir` and register their hotkeys with
+/// `capture` in one batch, in the order the files list them
+///
+/// A hotkey the backend rejects fails the load; the ones before it stay
+/// registered (see `InputCapture::register_hotkeys`).
+pub fn load_and_register(
+    path: impl AsRef<Path>,
+    capture: &mut dyn InputCapture,
+) -> Result<BindingRegistry> {
+    let (registry, hotkeys) = load(path.as_ref())?;
+    capture.register_hotkeys(&hotkeys)?;
+    Ok(registry)
+}
+
+/// The registry, and its hotkeys in file order
+fn load(path: &Path) -> Result<(BindingRegistry, Vec<Hotkey>)> {
+    let entries = fs::read_dir(path)
+        .with_context(|| format!("reading config directory {}", path.display()))?;
+
+    let mut files = Vec::new();
+    for entry in entries {
+        let file = entry?.path();
+        let enabled = file
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| !name.starts_with('_'));
+        if enabled && file.is_file() && file.extension().is_some_and(|ext| ext == "toml") {
+            files.push(file);
+        }
+    }
+    files.sort();
+
+    let mut sources: HashMap<Hotkey, PathBuf> = HashMap::new();
+    let mut hotkeys = Vec::new();
+    let mut start_source: Option<PathBuf> = None;
+    let mut stop_source: Option<PathBuf> = None;
+    let mut registry = BindingRegistry::new();
+    for file in files {
+        let text =
+            fs::read_to_string(&file).with_context(|| format!("reading {}", file.display()))?;
+        let config: ConfigFile =
+            toml::from_str(&text).with_context(|| format!("parsing {}", file.display()))?;
+
+        if let Some(action) = config.on_start {
+            claim_hook("on_start", &mut start_source, &file)?;
+            action
+                .validate()
+                .with_context(|| format!("invalid on_start in {}", file.display()))?;
+            registry = registry.on_start(action);
+        }
+        if let Some(action) = config.on_stop {
+            claim_hook("on_stop", &mut stop_source, &file)?;
+            action
+                .validate()
+                .with_context(|| format!("invalid on_stop in {}", file.display()))?;
+            registry = registry.on_stop(action);
+        }
+
+        for BindingEntry {
+            hotkey,
+            action,
+            when,
+        } in config.bindings
+        {
+            if let Some(previous) = sources.get(&hotkey) {
+                bail!(
+                    "{hotkey} is bound in both {} and {}",
+                    previous.display(),
+                    file.display()
+                );
+            }
+            action
+                .validate()
+                .with_context(|| format!("invalid action for {hotkey} in {}", file.display()))?;
+            sources.insert(hotkey.clone(), file.clone());
+            hotkeys.push(hotkey.clone());
+            registry = match when {
+                Some(condition) => registry.bind_when(hotkey, condition, action),
+                None => registry.bind(hotkey, action),
+            };
+        }
+    }
+
+    Ok((registry, hotkeys))
+}
+
+/// Record which file defines a lifecycle hook, rejecting a second definition
+fn claim_hook(name: &str, source: &mut Option<PathBuf>, file: &Path) -> Result<()> {
+    if let Some(previous) = source {
+        bail!(
+            "{} is defined in both {} and {}",
+            name,
+            previous.display(),
+            file.display()
+        );
+    }
+    *source = Some(file.to_path_buf());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use input_capture::Key;
+
+    /// Directory of config files, removed again on drop
+    struct ConfigDir(PathBuf);
+
+    impl ConfigDir {
+        fn new(name: &str, files: &[(&str, &str)]) -> Self {
+            let path = std::env::temp_dir()
+                .join(format!("handplusplus-config-{name}-{}", std::process::id()));
+            let _ = fs::remove_dir_all(&path);
+            fs::create_dir_all(&path).unwrap();
+            for (file, text) in files {
+                fs::write(path.join(file), text).unwrap();
+            }
+            Self(path)
+        }
+    }
+
+    impl Drop for ConfigDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn binding(hotkey: &str) -> String {
+        format!(
+            "[[bindings]]\nhotkey = \"{hotkey}\"\naction = {{ type = \"PressKey\", key = \"Enter\" }}\n"
+        )
+    }
+
+    #[test]
+    fn toml_files_merge_in_name_order() {
+        let dir = ConfigDir::new(
+            "merge",
+            &[
+                ("b.toml", &binding("F2")),
+                ("a.toml", &(binding("F3") + &binding("F1"))),
+                // Disabled, or not config files at all
+                ("_off.toml", &binding("F4")),
+                ("notes.txt", &binding("F5")),
+                ("a.toml.bak", &binding("F6")),
+            ],
+        );
+        let (registry, hotkeys) = load(&dir.0).unwrap();
+        let keys = [Key::F3, Key::F1, Key::F2].map(Hotkey::key);
+        assert_eq!(hotkeys, keys);
+        assert_eq!(registry.len(), 3);
+        assert!(keys.iter().all(|hotkey| registry.is_registered(hotkey)));
+    }
+
+    #[test]
+    fn conflicts_across_files_name_the_hotkey_and_both_files() {
+        let dir = ConfigDir::new(
+            "conflict",
+            &[
+                ("a.toml", &binding("Ctrl+A")),
+                ("b.toml", &binding("Ctrl+A")),
+            ],
+        );
+        let Err(err) = load_from_dir(&dir.0) else {
+            panic!("a hotkey bound twice loaded");
+        };
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "Ctrl+A is bound in both {} and {}",
+                dir.0.join("a.toml").display(),
+                dir.0.join("b.toml").display()
+            )
+        );
+    }
+
+    #[test]
+    fn invalid_actions_name_their_hotkey() {
+        let text = "[[bindings]]\nhotkey = \"Ctrl+A\"\naction = { type = \"RandomDelay\", min = 50, max = 10 }\n";
+        let dir = ConfigDir::new("invalid", &[("a.toml", text)]);
+        let Err(err) = load_from_dir(&dir.0) else {
+            panic!("an invalid action loaded");
+        };
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "invalid action for Ctrl+A in {}",
+                dir.0.join("a.toml").display()
+            )
+        );
+    }
+}
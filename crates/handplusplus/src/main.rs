@@ -1,9 +1,10 @@
 use anyhow::Result;
-use tracing::{info, warn};
-use tracing_subscriber;
+use input_capture::CaptureError;
+use std::process::ExitCode;
+use tracing::{error, info, warn};
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> ExitCode {
     // Initialize logging
     tracing_subscriber::fmt()
         .with_env_filter("handplusplus=debug,info")
@@ -12,6 +13,23 @@ async fn main() -> Result<()> {
     info!("🚀 HandPlusPlus starting...");
     info!("Platform: {}", std::env::consts::OS);
 
+    match run().await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            // Permission problems are the common first-run failure, so
+            // print the fix rather than a generic error chain
+            if let Some(CaptureError::PermissionDenied(guidance)) = err.downcast_ref() {
+                error!("❌ HandPlusPlus cannot capture input without permission");
+                error!("👉 To fix this, {}", guidance);
+            } else {
+                error!("❌ {:#}", err);
+            }
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run() -> Result<()> {
     // TODO: Initialize modules
     // 1. Load configuration (bindings)
     // 2. Initialize input capture
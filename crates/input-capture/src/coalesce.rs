@@ -0,0 +1,144 @@
+// Collapsing bursts of mouse movement on the consuming side of an event
+// stream, where a timer can hold the latest position back.
+
+use crate::{CapturedEvent, InputEvent};
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::time::Sleep;
+use tokio_stream::Stream;
+
+/// Stream passing on only the last `MouseMove` of each window
+pub(crate) struct CoalescedMoves<S> {
+    inner: S,
+    window: Duration,
+    // Latest move held back, and the end of the window it is in
+    pending: Option<(CapturedEvent, Pin<Box<Sleep>>)>,
+    // Event that ended a run of moves, passed on right after the move
+    next: Option<CapturedEvent>,
+    ended: bool,
+}
+
+impl<S> CoalescedMoves<S> {
+    pub(crate) fn new(inner: S, window: Duration) -> Self {
+        Self {
+            inner,
+            window,
+            pending: None,
+            next: None,
+            ended: false,
+        }
+    }
+}
+
+impl<S: Stream<Item = CapturedEvent> + Unpin> Stream for CoalescedMoves<S> {
+    type Item = CapturedEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<CapturedEvent>> {
+        if let Some(next) = self.next.take() {
+            return Poll::Ready(Some(next));
+        }
+        while !self.ended {
+            match Pin::new(&mut self.inner).poll_next(cx) {
+                Poll::Ready(Some(captured)) => {
+                    if matches!(captured.event, InputEvent::MouseMove { .. }) {
+                        // The window opens with the first move of a run
+                        let window = self.window;
+                        match &mut self.pending {
+                            Some((pending, _)) => *pending = captured,
+                            None => {
+                                let deadline = Box::pin(tokio::time::sleep(window));
+                                self.pending = Some((captured, deadline));
+                            }
+                        }
+                        continue;
+                    }
+                    // Never reorder a move past a key or button event
+                    return Poll::Ready(Some(match self.pending.take() {
+                        Some((pending, _)) => {
+                            self.next = Some(captured);
+                            pending
+                        }
+                        None => captured,
+                    }));
+                }
+                Poll::Ready(None) => self.ended = true,
+                Poll::Pending => break,
+            }
+        }
+        if self.ended {
+            return Poll::Ready(self.pending.take().map(|(pending, _)| pending));
+        }
+        let Some((_, deadline)) = &mut self.pending else {
+            return Poll::Pending;
+        };
+        match deadline.as_mut().poll(cx) {
+            Poll::Ready(()) => Poll::Ready(self.pending.take().map(|(pending, _)| pending)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{CaptureStreamExt, CapturedEvent, InputEvent, ModifierSet, MouseButton};
+    use std::time::Duration;
+    use tokio::time::Instant;
+    use tokio_stream::StreamExt;
+
+    const WINDOW: Duration = Duration::from_millis(10);
+
+    fn captured(event: InputEvent) -> CapturedEvent {
+        CapturedEvent {
+            event,
+            modifiers: ModifierSet::empty(),
+            device_id: None,
+            held_for: None,
+        }
+    }
+
+    fn moved(x: i32) -> CapturedEvent {
+        captured(InputEvent::MouseMove { x, y: 0 })
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn a_burst_of_moves_before_a_click_is_one_move_then_the_click() {
+        let click = captured(InputEvent::MousePress(MouseButton::Left));
+        let burst = (1..=5).map(moved).chain([click, moved(6)]);
+        let events: Vec<InputEvent> = tokio_stream::iter(burst)
+            .coalesce_moves(WINDOW)
+            .map(|captured| captured.event)
+            .collect()
+            .await;
+        assert_eq!(
+            events,
+            [
+                InputEvent::MouseMove { x: 5, y: 0 },
+                InputEvent::MousePress(MouseButton::Left),
+                InputEvent::MouseMove { x: 6, y: 0 },
+            ]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn moves_are_held_back_for_one_window_only() {
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut stream =
+            tokio_stream::wrappers::UnboundedReceiverStream::new(rx).coalesce_moves(WINDOW);
+        let started = Instant::now();
+        tx.send(moved(1)).unwrap();
+        tx.send(moved(2)).unwrap();
+        assert_eq!(stream.next().await.unwrap().event, moved(2).event);
+        assert_eq!(started.elapsed(), WINDOW);
+        // A move after the window opens a new one
+        tx.send(moved(3)).unwrap();
+        assert_eq!(stream.next().await.unwrap().event, moved(3).event);
+        assert_eq!(started.elapsed(), WINDOW * 2);
+        // Other events are never held back
+        tx.send(captured(InputEvent::MousePress(MouseButton::Left)))
+            .unwrap();
+        stream.next().await.unwrap();
+        assert_eq!(started.elapsed(), WINDOW * 2);
+    }
+}
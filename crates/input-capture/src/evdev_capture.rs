@@ -0,0 +1,574 @@
+// Global capture straight from the /dev/input/event* device nodes.
+//
+// Wayland compositors offer no global hook, but events read here arrive
+// below the display server, so this works under X11 and Wayland alike. The
+// device nodes are normally readable only by root and the `input` group.
+
+use crate::{
+    CaptureError, CaptureOptions, CapturedEvent, DeviceId, EventSink, Hotkey, InputCapture,
+    InputEvent, Key, ModifierSet, MouseButton, PhysicalKey, VIRTUAL_DEVICE_NAME,
+};
+use anyhow::{anyhow, bail, Context, Result};
+use evdev::{Device, EventStream, EventType, RelativeAxisType};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+use tokio::runtime::Handle;
+use tokio::task::JoinHandle;
+use tokio_stream::Stream;
+use tracing::{debug, warn};
+
+/// Capture reading keyboards and mice through evdev
+///
+/// Needs read access to `/dev/input/event*`, usually through membership in
+/// the `input` group; without it construction fails with
+/// `CaptureError::PermissionDenied`. evdev only reports motion deltas, so
+/// `MouseMove` positions are the sum of all motion since capture started,
+/// not screen coordinates. There is no OS modifier state either, so held
+/// modifiers are tracked from the captured keys themselves. Events carry
+/// the name of their device as `device_id`.
+pub struct EvdevCapture {
+    options: CaptureOptions,
+    hotkeys: Vec<Hotkey>,
+    events: EventSink,
+    runtime: Handle,
+    // Shared so motion from several mice moves one pointer, and a
+    // modifier held on one keyboard applies to clicks on a mouse
+    position: Arc<Mutex<(i32, i32)>>,
+    held: Arc<Mutex<ModifierSet>>,
+    // One per device; emptied on stop
+    readers: Vec<Reader>,
+}
+
+/// Task reading one device node
+struct Reader {
+    path: PathBuf,
+    device: DeviceId,
+    // Whether the device should be grabbed; it is released while paused
+    grabbed: bool,
+    task: JoinHandle<()>,
+}
+
+impl EvdevCapture {
+    /// Capture from every keyboard and mouse; must be called inside a
+    /// tokio runtime, which runs the device readers
+    pub fn new(options: CaptureOptions) -> Result<Self> {
+        Self::create(options, None)
+    }
+
+    /// Like `new`, but only capture from devices whose path (such as
+    /// `/dev/input/event3`) or name is listed
+    pub fn with_devices(options: CaptureOptions, devices: &[&str]) -> Result<Self> {
+        Self::create(options, Some(devices))
+    }
+
+    fn create(options: CaptureOptions, filter: Option<&[&str]>) -> Result<Self> {
+        let runtime = Handle::try_current()
+            .map_err(|_| anyhow!("EvdevCapture must be created inside a tokio runtime"))?;
+        let mut capture = Self {
+            options,
+            hotkeys: Vec::new(),
+            events: EventSink::new(options),
+            runtime,
+            position: Arc::new(Mutex::new((0, 0))),
+            held: Arc::new(Mutex::new(ModifierSet::empty())),
+            readers: Vec::new(),
+        };
+        for (path, device) in open_devices()? {
+            let name = device.name().unwrap_or_default().to_owned();
+            // The executor's own device; capturing it would feed actions
+            // back in as input
+            if name == VIRTUAL_DEVICE_NAME {
+                debug!("skipping {} ({name})", path.display());
+                continue;
+            }
+            let wanted = filter.is_none_or(|filter| {
+                filter
+                    .iter()
+                    .any(|&wanted| Path::new(wanted) == path || wanted == name)
+            });
+            if !wanted || !(is_keyboard(&device) || is_mouse(&device)) {
+                continue;
+            }
+            debug!("capturing input from {} ({name})", path.display());
+            let device_id = DeviceId(name);
+            let task = capture.spawn_reader(device, device_id.clone())?;
+            capture.readers.push(Reader {
+                path,
+                device: device_id,
+                grabbed: false,
+                task,
+            });
+        }
+        if capture.readers.is_empty() {
+            bail!("no matching keyboard or mouse under /dev/input");
+        }
+        Ok(capture)
+    }
+
+    pub fn options(&self) -> CaptureOptions {
+        self.options
+    }
+
+    /// Take every captured device named `device` for this capture alone
+    /// (`EVIOCGRAB`), so its input reaches no other application
+    ///
+    /// Keys held on it while grabbing or ungrabbing may stick for other
+    /// applications. Grabs end with `ungrab_device`, `pause`, `stop` or
+    /// dropping the capture, since closing a device releases it.
+    pub fn grab_device(&mut self, device: DeviceId) -> Result<()> {
+        self.set_grabbed(&device, true)
+    }
+
+    /// Give a device grabbed with `grab_device` back to the system
+    pub fn ungrab_device(&mut self, device: DeviceId) -> Result<()> {
+        self.set_grabbed(&device, false)
+    }
+
+    fn set_grabbed(&mut self, device: &DeviceId, grabbed: bool) -> Result<()> {
+        if !self.readers.iter().any(|reader| reader.device == *device) {
+            bail!("no captured input device is named {:?}", device.0);
+        }
+        for index in 0..self.readers.len() {
+            let reader = &mut self.readers[index];
+            if reader.device != *device || reader.grabbed == grabbed {
+                continue;
+            }
+            reader.grabbed = grabbed;
+            if !self.events.is_paused() {
+                self.reopen(index, grabbed)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Replace a reader with one on a fresh descriptor, grabbed or not;
+    /// grabs belong to a descriptor, and closing the old one ends its grab
+    fn reopen(&mut self, index: usize, grab: bool) -> Result<()> {
+        let path = self.readers[index].path.clone();
+        let mut device = Device::open(&path).map_err(CaptureError::from_io)?;
+        if grab {
+            device
+                .grab()
+                .with_context(|| format!("failed to grab {}", path.display()))?;
+        }
+        let task = self.spawn_reader(device, self.readers[index].device.clone())?;
+        std::mem::replace(&mut self.readers[index].task, task).abort();
+        Ok(())
+    }
+
+    fn spawn_reader(&self, device: Device, device_id: DeviceId) -> Result<JoinHandle<()>> {
+        let reader = read_device(
+            device.into_event_stream()?,
+            device_id,
+            self.options,
+            self.position.clone(),
+            self.held.clone(),
+            self.events.clone(),
+        );
+        Ok(self.runtime.spawn(reader))
+    }
+
+    /// Reopen every reader meant to be grabbed, grabbing it or not
+    fn regrab(&mut self, grab: bool) -> Result<()> {
+        for index in 0..self.readers.len() {
+            if self.readers[index].grabbed {
+                self.reopen(index, grab)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl InputCapture for EvdevCapture {
+    /// Devices are read in full, so hotkeys are only recorded here;
+    /// matching happens downstream on the event stream
+    fn register_hotkey(&mut self, hotkey: Hotkey) -> Result<()> {
+        if !self.hotkeys.contains(&hotkey) {
+            self.hotkeys.push(hotkey);
+        }
+        Ok(())
+    }
+
+    fn registered_hotkeys(&self) -> Vec<Hotkey> {
+        self.hotkeys.clone()
+    }
+
+    fn event_stream(&self) -> Box<dyn Stream<Item = CapturedEvent> + Send + Unpin> {
+        self.events.stream()
+    }
+
+    fn dropped_events(&self) -> u64 {
+        self.events.dropped()
+    }
+
+    /// Grabbed devices are released until `resume`
+    fn pause(&mut self) -> Result<()> {
+        if self.events.is_paused() {
+            return Ok(());
+        }
+        self.events.set_paused(true);
+        self.regrab(false)
+    }
+
+    fn resume(&mut self) -> Result<()> {
+        if !self.events.is_paused() {
+            return Ok(());
+        }
+        self.events.set_paused(false);
+        self.regrab(true)
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        // Dropping a reader's stream closes its device, ending any grab
+        for reader in self.readers.drain(..) {
+            reader.task.abort();
+        }
+        Ok(())
+    }
+}
+
+impl Drop for EvdevCapture {
+    fn drop(&mut self) {
+        let _ = self.stop();
+    }
+}
+
+/// Open every event device; fails with the permission error only when
+/// nothing could be opened because of it
+fn open_devices() -> Result<Vec<(PathBuf, Device)>> {
+    let mut devices = Vec::new();
+    let mut denied = None;
+    for entry in std::fs::read_dir("/dev/input").map_err(CaptureError::from_io)? {
+        let path = entry?.path();
+        let is_event = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.starts_with("event"));
+        if !is_event {
+            continue;
+        }
+        match Device::open(&path) {
+            Ok(device) => devices.push((path, device)),
+            Err(err) if err.kind() == io::ErrorKind::PermissionDenied => denied = Some(err),
+            Err(err) => debug!("skipping {}: {err}", path.display()),
+        }
+    }
+    match denied {
+        Some(err) if devices.is_empty() => Err(CaptureError::from_io(err)),
+        _ => Ok(devices),
+    }
+}
+
+fn is_keyboard(device: &Device) -> bool {
+    device
+        .supported_keys()
+        .is_some_and(|keys| keys.contains(evdev::Key::KEY_A))
+}
+
+fn is_mouse(device: &Device) -> bool {
+    let moves = device
+        .supported_relative_axes()
+        .is_some_and(|axes| axes.contains(RelativeAxisType::REL_X));
+    let clicks = device
+        .supported_keys()
+        .is_some_and(|keys| keys.contains(evdev::Key::BTN_LEFT));
+    moves && clicks
+}
+
+/// Forward one device's events until it disappears or the task is aborted
+async fn read_device(
+    mut stream: EventStream,
+    device: DeviceId,
+    options: CaptureOptions,
+    position: Arc<Mutex<(i32, i32)>>,
+    held: Arc<Mutex<ModifierSet>>,
+    events: EventSink,
+) {
+    let mut decoder = Decoder::default();
+    loop {
+        let event = match stream.next_event().await {
+            Ok(event) => event,
+            Err(err) => {
+                // ENODEV when the device is unplugged
+                warn!("stopped reading an input device: {err}");
+                return;
+            }
+        };
+        let decoded = decoder.decode(event.event_type(), event.code(), event.value(), &position);
+        for event in decoded.into_iter().filter(|event| options.accepts(event)) {
+            let modifiers = track_modifiers(&held, &event);
+            events
+                .send_async(CapturedEvent {
+                    event,
+                    modifiers,
+                    device_id: Some(device.clone()),
+                    held_for: None,
+                })
+                .await;
+        }
+    }
+}
+
+/// Modifiers held before `event`, updated for the event's own effect
+fn track_modifiers(held: &Mutex<ModifierSet>, event: &InputEvent) -> ModifierSet {
+    let mut held = held.lock().unwrap_or_else(PoisonError::into_inner);
+    let before = *held;
+    held.track(event);
+    before
+}
+
+/// Per-device state for turning evdev events into `InputEvent`s
+#[derive(Debug, Default)]
+struct Decoder {
+    // Motion and wheel detents since the last SYN_REPORT, so one report
+    // gives at most one move and one wheel event
+    motion: (i32, i32),
+    wheel: (i32, i32),
+}
+
+impl Decoder {
+    fn decode(
+        &mut self,
+        kind: EventType,
+        code: u16,
+        value: i32,
+        position: &Mutex<(i32, i32)>,
+    ) -> Vec<InputEvent> {
+        match kind {
+            EventType::KEY => key_events(code, value),
+            EventType::RELATIVE => {
+                match RelativeAxisType(code) {
+                    RelativeAxisType::REL_X => self.motion.0 += value,
+                    RelativeAxisType::REL_Y => self.motion.1 += value,
+                    // Both are positive up and to the right, like MouseWheel
+                    RelativeAxisType::REL_WHEEL => self.wheel.1 += value,
+                    RelativeAxisType::REL_HWHEEL => self.wheel.0 += value,
+                    _ => {}
+                }
+                Vec::new()
+            }
+            EventType::SYNCHRONIZATION => {
+                let mut events = Vec::new();
+                if self.motion != (0, 0) {
+                    let mut position = position.lock().unwrap_or_else(PoisonError::into_inner);
+                    position.0 += self.motion.0;
+                    position.1 += self.motion.1;
+                    events.push(InputEvent::MouseMove {
+                        x: position.0,
+                        y: position.1,
+                    });
+                }
+                if self.wheel != (0, 0) {
+                    let (dx, dy) = self.wheel;
+                    events.push(InputEvent::MouseWheel { dx, dy });
+                }
+                self.motion = (0, 0);
+                self.wheel = (0, 0);
+                events
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Events for an EV_KEY event; key events are followed by their physical
+/// counterpart
+fn key_events(code: u16, value: i32) -> Vec<InputEvent> {
+    // 0 is a release, 1 a press and 2 an autorepeat, reported as a press
+    // like the other backends do, for the sink to filter
+    let pressed = value != 0;
+    if let Some(button) = mouse_button(code) {
+        return if pressed {
+            vec![InputEvent::MousePress(button)]
+        } else {
+            vec![InputEvent::MouseRelease(button)]
+        };
+    }
+    let physical = PhysicalKey(code);
+    let key = Key::from_physical(physical);
+    let (key, physical) = if pressed {
+        (
+            key.map(InputEvent::KeyPress),
+            InputEvent::PhysicalKeyPress(physical),
+        )
+    } else {
+        (
+            key.map(InputEvent::KeyRelease),
+            InputEvent::PhysicalKeyRelease(physical),
+        )
+    };
+    key.into_iter().chain([physical]).collect()
+}
+
+fn mouse_button(code: u16) -> Option<MouseButton> {
+    let button = match code {
+        0x110 => MouseButton::Left,
+        0x111 => MouseButton::Right,
+        0x112 => MouseButton::Middle,
+        0x113 => MouseButton::Button4,
+        0x114 => MouseButton::Button5,
+        _ => return None,
+    };
+    Some(button)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Size of `struct input_event` with a 64-bit `struct timeval`; the
+    // buffer below is also little-endian
+    #[cfg(all(target_pointer_width = "64", target_endian = "little"))]
+    const INPUT_EVENT_SIZE: usize = 24;
+
+    // Read from a keyboard's and a mouse's event nodes on x86_64: a key
+    // press, a move with a wheel detent, then a click
+    #[cfg(all(target_pointer_width = "64", target_endian = "little"))]
+    #[rustfmt::skip]
+    const CAPTURED: [u8; 9 * INPUT_EVENT_SIZE] = [
+        // MSC_SCAN 0x70004
+        0x40, 0x72, 0x0e, 0x67, 0x00, 0x00, 0x00, 0x00, 0x40, 0xe2, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x04, 0x00, 0x04, 0x00, 0x04, 0x00, 0x07, 0x00,
+        // KEY_A press
+        0x40, 0x72, 0x0e, 0x67, 0x00, 0x00, 0x00, 0x00, 0x40, 0xe2, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x01, 0x00, 0x1e, 0x00, 0x01, 0x00, 0x00, 0x00,
+        // SYN_REPORT
+        0x40, 0x72, 0x0e, 0x67, 0x00, 0x00, 0x00, 0x00, 0x40, 0xe2, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        // REL_X 5
+        0x40, 0x72, 0x0e, 0x67, 0x00, 0x00, 0x00, 0x00, 0xba, 0xff, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x02, 0x00, 0x00, 0x00, 0x05, 0x00, 0x00, 0x00,
+        // REL_Y -3
+        0x40, 0x72, 0x0e, 0x67, 0x00, 0x00, 0x00, 0x00, 0xba, 0xff, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x02, 0x00, 0x01, 0x00, 0xfd, 0xff, 0xff, 0xff,
+        // REL_WHEEL 1
+        0x40, 0x72, 0x0e, 0x67, 0x00, 0x00, 0x00, 0x00, 0xba, 0xff, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x02, 0x00, 0x08, 0x00, 0x01, 0x00, 0x00, 0x00,
+        // SYN_REPORT
+        0x40, 0x72, 0x0e, 0x67, 0x00, 0x00, 0x00, 0x00, 0xba, 0xff, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        // BTN_LEFT press
+        0x40, 0x72, 0x0e, 0x67, 0x00, 0x00, 0x00, 0x00, 0x16, 0x24, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x01, 0x00, 0x10, 0x01, 0x01, 0x00, 0x00, 0x00,
+        // SYN_REPORT
+        0x40, 0x72, 0x0e, 0x67, 0x00, 0x00, 0x00, 0x00, 0x16, 0x24, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ];
+
+    /// Type, code and value of each `input_event` in a buffer, after the
+    /// 16-byte timestamp
+    #[cfg(all(target_pointer_width = "64", target_endian = "little"))]
+    fn parse(buffer: &[u8]) -> Vec<(EventType, u16, i32)> {
+        buffer
+            .chunks_exact(INPUT_EVENT_SIZE)
+            .map(|event| {
+                let kind = u16::from_ne_bytes([event[16], event[17]]);
+                let code = u16::from_ne_bytes([event[18], event[19]]);
+                let value = i32::from_ne_bytes([event[20], event[21], event[22], event[23]]);
+                (EventType(kind), code, value)
+            })
+            .collect()
+    }
+
+    #[cfg(all(target_pointer_width = "64", target_endian = "little"))]
+    #[test]
+    fn captured_buffer_decodes_to_input_events() {
+        let mut decoder = Decoder::default();
+        let position = Mutex::new((100, 100));
+        let events: Vec<InputEvent> = parse(&CAPTURED)
+            .into_iter()
+            .flat_map(|(kind, code, value)| decoder.decode(kind, code, value, &position))
+            .collect();
+        assert_eq!(
+            events,
+            [
+                InputEvent::KeyPress(Key::A),
+                InputEvent::PhysicalKeyPress(PhysicalKey(30)),
+                InputEvent::MouseMove { x: 105, y: 97 },
+                InputEvent::MouseWheel { dx: 0, dy: 1 },
+                InputEvent::MousePress(MouseButton::Left),
+            ]
+        );
+    }
+
+    #[test]
+    fn wheel_axes_combine_into_one_event_per_report() {
+        let mut decoder = Decoder::default();
+        let position = Mutex::new((0, 0));
+        let mut axis = |axis: RelativeAxisType, value| {
+            decoder.decode(EventType::RELATIVE, axis.0, value, &position)
+        };
+        assert!(axis(RelativeAxisType::REL_HWHEEL, -1).is_empty());
+        assert!(axis(RelativeAxisType::REL_WHEEL, 2).is_empty());
+        let report =
+            |decoder: &mut Decoder| decoder.decode(EventType::SYNCHRONIZATION, 0, 0, &position);
+        assert_eq!(
+            report(&mut decoder),
+            [InputEvent::MouseWheel { dx: -1, dy: 2 }]
+        );
+        // Nothing carries over into the next report
+        assert!(report(&mut decoder).is_empty());
+    }
+
+    #[test]
+    fn autorepeat_is_a_press_and_zero_a_release() {
+        assert_eq!(
+            key_events(30, 2),
+            [
+                InputEvent::KeyPress(Key::A),
+                InputEvent::PhysicalKeyPress(PhysicalKey(30)),
+            ]
+        );
+        assert_eq!(
+            key_events(0x113, 0),
+            [InputEvent::MouseRelease(MouseButton::Button4)]
+        );
+    }
+
+    /// Whether another descriptor can take `path`, grabbing and releasing it
+    fn grab_is_free(path: &Path) -> bool {
+        Device::open(path)
+            .and_then(|mut device| {
+                device.grab()?;
+                device.ungrab()
+            })
+            .is_ok()
+    }
+
+    /// Let aborted readers drop, closing their descriptors
+    async fn settle() {
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+
+    // Needs access to /dev/input and briefly takes a real keyboard or
+    // mouse from the rest of the system
+    #[tokio::test(flavor = "multi_thread")]
+    #[ignore = "grabs a real input device"]
+    async fn grabs_are_exclusive_and_end_on_ungrab_stop_and_drop() {
+        let mut capture = EvdevCapture::new(CaptureOptions::default()).unwrap();
+        let path = capture.readers[0].path.clone();
+        let device = capture.readers[0].device.clone();
+        assert!(grab_is_free(&path));
+
+        capture.grab_device(device.clone()).unwrap();
+        settle().await;
+        assert!(!grab_is_free(&path));
+        capture.ungrab_device(device.clone()).unwrap();
+        settle().await;
+        assert!(grab_is_free(&path));
+
+        capture.grab_device(device.clone()).unwrap();
+        capture.stop().unwrap();
+        settle().await;
+        assert!(grab_is_free(&path));
+
+        let mut capture = EvdevCapture::new(CaptureOptions::default()).unwrap();
+        capture.grab_device(device.clone()).unwrap();
+        settle().await;
+        assert!(!grab_is_free(&path));
+        drop(capture);
+        settle().await;
+        assert!(grab_is_free(&path));
+    }
+}
@@ -0,0 +1,547 @@
+// Key positions as evdev codes, the common currency of the injection
+// backends: Windows set-1 scan codes match them for the main block, XTest
+// keycodes are offset by 8, and uinput/libei take them as-is. Keysyms
+// serve the X11 backends, which resolve keys through the server keymap, and
+// macOS has virtual key codes of its own.
+
+use crate::{Key, PhysicalKey, ScanCode};
+
+impl Key {
+    /// Position of this key on a US QWERTY keyboard
+    pub fn physical(self) -> PhysicalKey {
+        let code = match self {
+            Key::Escape => 1,
+            Key::Num1 => 2,
+            Key::Num2 => 3,
+            Key::Num3 => 4,
+            Key::Num4 => 5,
+            Key::Num5 => 6,
+            Key::Num6 => 7,
+            Key::Num7 => 8,
+            Key::Num8 => 9,
+            Key::Num9 => 10,
+            Key::Num0 => 11,
+            Key::Minus => 12,
+            Key::Equals => 13,
+            Key::Backspace => 14,
+            Key::Tab => 15,
+            Key::Q => 16,
+            Key::W => 17,
+            Key::E => 18,
+            Key::R => 19,
+            Key::T => 20,
+            Key::Y => 21,
+            Key::U => 22,
+            Key::I => 23,
+            Key::O => 24,
+            Key::P => 25,
+            Key::LeftBracket => 26,
+            Key::RightBracket => 27,
+            Key::Enter => 28,
+            Key::Ctrl => 29,
+            Key::A => 30,
+            Key::S => 31,
+            Key::D => 32,
+            Key::F => 33,
+            Key::G => 34,
+            Key::H => 35,
+            Key::J => 36,
+            Key::K => 37,
+            Key::L => 38,
+            Key::Semicolon => 39,
+            Key::Quote => 40,
+            Key::Grave => 41,
+            Key::Shift => 42,
+            Key::Backslash => 43,
+            Key::Z => 44,
+            Key::X => 45,
+            Key::C => 46,
+            Key::V => 47,
+            Key::B => 48,
+            Key::N => 49,
+            Key::M => 50,
+            Key::Comma => 51,
+            Key::Period => 52,
+            Key::Slash => 53,
+            Key::ShiftRight => 54,
+            Key::NumpadMultiply => 55,
+            Key::Alt => 56,
+            Key::Space => 57,
+            Key::CapsLock => 58,
+            Key::F1 => 59,
+            Key::F2 => 60,
+            Key::F3 => 61,
+            Key::F4 => 62,
+            Key::F5 => 63,
+            Key::F6 => 64,
+            Key::F7 => 65,
+            Key::F8 => 66,
+            Key::F9 => 67,
+            Key::F10 => 68,
+            Key::NumLock => 69,
+            Key::ScrollLock => 70,
+            Key::Numpad7 => 71,
+            Key::Numpad8 => 72,
+            Key::Numpad9 => 73,
+            Key::NumpadSubtract => 74,
+            Key::Numpad4 => 75,
+            Key::Numpad5 => 76,
+            Key::Numpad6 => 77,
+            Key::NumpadAdd => 78,
+            Key::Numpad1 => 79,
+            Key::Numpad2 => 80,
+            Key::Numpad3 => 81,
+            Key::Numpad0 => 82,
+            Key::NumpadDecimal => 83,
+            Key::IntlBackslash => 86,
+            Key::F11 => 87,
+            Key::F12 => 88,
+            Key::NumpadEnter => 96,
+            Key::CtrlRight => 97,
+            Key::NumpadDivide => 98,
+            Key::PrintScreen => 99,
+            Key::AltGr => 100,
+            Key::Home => 102,
+            Key::Up => 103,
+            Key::PageUp => 104,
+            Key::Left => 105,
+            Key::Right => 106,
+            Key::End => 107,
+            Key::Down => 108,
+            Key::PageDown => 109,
+            Key::Insert => 110,
+            Key::Delete => 111,
+            Key::Pause => 119,
+            Key::Meta => 125,
+            Key::MetaRight => 126,
+        };
+        PhysicalKey(code)
+    }
+
+    /// Key at a position, or None for positions `Key` has no name for
+    pub fn from_physical(code: PhysicalKey) -> Option<Key> {
+        Key::ALL.iter().copied().find(|key| key.physical() == code)
+    }
+
+    /// PC set-1 scan code, as Windows takes it
+    ///
+    /// None for Pause, which sends an E1 sequence no single code covers.
+    pub fn set1_scan_code(self) -> Option<ScanCode> {
+        if self == Key::Pause {
+            return None;
+        }
+        let code = match EXTENDED_SCAN_CODES.iter().find(|&&(key, _)| key == self) {
+            Some(&(_, code)) => 0xE000 | code,
+            // Everything else matches its evdev code
+            None => self.physical().0,
+        };
+        Some(ScanCode(code))
+    }
+
+    pub fn from_set1_scan_code(code: ScanCode) -> Option<Key> {
+        Key::ALL
+            .iter()
+            .copied()
+            .find(|key| key.set1_scan_code() == Some(code))
+    }
+
+    /// Code `Action::RawScanCode` sends for this key on the current
+    /// platform; None on macOS, which has no raw scan code injection
+    pub fn to_scancode(self) -> Option<ScanCode> {
+        if cfg!(windows) {
+            self.set1_scan_code()
+        } else if cfg!(target_os = "macos") {
+            None
+        } else {
+            Some(ScanCode(self.physical().0))
+        }
+    }
+
+    /// Key for a raw scan code of the current platform
+    pub fn from_scancode(code: ScanCode) -> Option<Key> {
+        if cfg!(windows) {
+            Key::from_set1_scan_code(code)
+        } else if cfg!(target_os = "macos") {
+            None
+        } else {
+            Key::from_physical(PhysicalKey(code.0))
+        }
+    }
+
+    /// X11 keysym this key produces on a US layout, or None for keys
+    /// better found by position
+    pub fn keysym(self) -> Option<u32> {
+        let keysym = match self {
+            // Latin-1 keysyms equal their (lowercase) character
+            Key::A => 0x61,
+            Key::B => 0x62,
+            Key::C => 0x63,
+            Key::D => 0x64,
+            Key::E => 0x65,
+            Key::F => 0x66,
+            Key::G => 0x67,
+            Key::H => 0x68,
+            Key::I => 0x69,
+            Key::J => 0x6a,
+            Key::K => 0x6b,
+            Key::L => 0x6c,
+            Key::M => 0x6d,
+            Key::N => 0x6e,
+            Key::O => 0x6f,
+            Key::P => 0x70,
+            Key::Q => 0x71,
+            Key::R => 0x72,
+            Key::S => 0x73,
+            Key::T => 0x74,
+            Key::U => 0x75,
+            Key::V => 0x76,
+            Key::W => 0x77,
+            Key::X => 0x78,
+            Key::Y => 0x79,
+            Key::Z => 0x7a,
+            Key::Num0 => 0x30,
+            Key::Num1 => 0x31,
+            Key::Num2 => 0x32,
+            Key::Num3 => 0x33,
+            Key::Num4 => 0x34,
+            Key::Num5 => 0x35,
+            Key::Num6 => 0x36,
+            Key::Num7 => 0x37,
+            Key::Num8 => 0x38,
+            Key::Num9 => 0x39,
+            Key::Space => 0x20,
+            Key::Quote => 0x27,
+            Key::Comma => 0x2c,
+            Key::Minus => 0x2d,
+            Key::Period => 0x2e,
+            Key::Slash => 0x2f,
+            Key::Semicolon => 0x3b,
+            Key::Equals => 0x3d,
+            Key::LeftBracket => 0x5b,
+            Key::Backslash => 0x5c,
+            Key::RightBracket => 0x5d,
+            Key::Grave => 0x60,
+            Key::Ctrl => 0xffe3,
+            Key::Shift => 0xffe1,
+            Key::Alt => 0xffe9,
+            Key::Meta => 0xffeb,
+            Key::AltGr => 0xfe03,
+            Key::CtrlRight => 0xffe4,
+            Key::ShiftRight => 0xffe2,
+            Key::MetaRight => 0xffec,
+            Key::F1 => 0xffbe,
+            Key::F2 => 0xffbf,
+            Key::F3 => 0xffc0,
+            Key::F4 => 0xffc1,
+            Key::F5 => 0xffc2,
+            Key::F6 => 0xffc3,
+            Key::F7 => 0xffc4,
+            Key::F8 => 0xffc5,
+            Key::F9 => 0xffc6,
+            Key::F10 => 0xffc7,
+            Key::F11 => 0xffc8,
+            Key::F12 => 0xffc9,
+            Key::Enter => 0xff0d,
+            Key::Escape => 0xff1b,
+            Key::Tab => 0xff09,
+            Key::Backspace => 0xff08,
+            Key::CapsLock => 0xffe5,
+            Key::Up => 0xff52,
+            Key::Down => 0xff54,
+            Key::Left => 0xff51,
+            Key::Right => 0xff53,
+            Key::Home => 0xff50,
+            Key::End => 0xff57,
+            Key::PageUp => 0xff55,
+            Key::PageDown => 0xff56,
+            Key::Insert => 0xff63,
+            Key::Delete => 0xffff,
+            Key::PrintScreen => 0xff61,
+            Key::ScrollLock => 0xff14,
+            Key::Pause => 0xff13,
+            Key::NumLock => 0xff7f,
+            Key::Numpad0 => 0xffb0,
+            Key::Numpad1 => 0xffb1,
+            Key::Numpad2 => 0xffb2,
+            Key::Numpad3 => 0xffb3,
+            Key::Numpad4 => 0xffb4,
+            Key::Numpad5 => 0xffb5,
+            Key::Numpad6 => 0xffb6,
+            Key::Numpad7 => 0xffb7,
+            Key::Numpad8 => 0xffb8,
+            Key::Numpad9 => 0xffb9,
+            Key::NumpadAdd => 0xffab,
+            Key::NumpadSubtract => 0xffad,
+            Key::NumpadMultiply => 0xffaa,
+            Key::NumpadDivide => 0xffaf,
+            Key::NumpadDecimal => 0xffae,
+            Key::NumpadEnter => 0xff8d,
+            // IntlBackslash's less/greater also sit on the comma key
+            _ => return None,
+        };
+        Some(keysym)
+    }
+
+    /// macOS ANSI virtual key code (these name positions too)
+    pub fn mac_key_code(self) -> Option<u16> {
+        let code = match self {
+            Key::A => 0x00,
+            Key::S => 0x01,
+            Key::D => 0x02,
+            Key::F => 0x03,
+            Key::H => 0x04,
+            Key::G => 0x05,
+            Key::Z => 0x06,
+            Key::X => 0x07,
+            Key::C => 0x08,
+            Key::V => 0x09,
+            Key::B => 0x0B,
+            Key::Q => 0x0C,
+            Key::W => 0x0D,
+            Key::E => 0x0E,
+            Key::R => 0x0F,
+            Key::Y => 0x10,
+            Key::T => 0x11,
+            Key::Num1 => 0x12,
+            Key::Num2 => 0x13,
+            Key::Num3 => 0x14,
+            Key::Num4 => 0x15,
+            Key::Num6 => 0x16,
+            Key::Num5 => 0x17,
+            Key::Equals => 0x18,
+            Key::Num9 => 0x19,
+            Key::Num7 => 0x1A,
+            Key::Minus => 0x1B,
+            Key::Num8 => 0x1C,
+            Key::Num0 => 0x1D,
+            Key::RightBracket => 0x1E,
+            Key::O => 0x1F,
+            Key::U => 0x20,
+            Key::LeftBracket => 0x21,
+            Key::I => 0x22,
+            Key::P => 0x23,
+            Key::Enter => 0x24,
+            Key::L => 0x25,
+            Key::J => 0x26,
+            Key::Quote => 0x27,
+            Key::K => 0x28,
+            Key::Semicolon => 0x29,
+            Key::Backslash => 0x2A,
+            Key::Comma => 0x2B,
+            Key::Slash => 0x2C,
+            Key::N => 0x2D,
+            Key::M => 0x2E,
+            Key::Period => 0x2F,
+            Key::Tab => 0x30,
+            Key::Space => 0x31,
+            Key::Grave => 0x32,
+            Key::Backspace => 0x33,
+            Key::Escape => 0x35,
+            Key::Meta => 0x37,
+            Key::Shift => 0x38,
+            Key::CapsLock => 0x39,
+            Key::Alt => 0x3A,
+            Key::Ctrl => 0x3B,
+            // Right Option is the third-level key on international layouts
+            Key::AltGr => 0x3D,
+            Key::CtrlRight => 0x3E,
+            Key::ShiftRight => 0x3C,
+            Key::MetaRight => 0x36,
+            Key::F1 => 0x7A,
+            Key::F2 => 0x78,
+            Key::F3 => 0x63,
+            Key::F4 => 0x76,
+            Key::F5 => 0x60,
+            Key::F6 => 0x61,
+            Key::F7 => 0x62,
+            Key::F8 => 0x64,
+            Key::F9 => 0x65,
+            Key::F10 => 0x6D,
+            Key::F11 => 0x67,
+            Key::F12 => 0x6F,
+            Key::IntlBackslash => 0x0A,
+            // Mac keyboards have Help, F13-F15 and Clear where PC ones have
+            // Insert, PrintScreen/ScrollLock/Pause and NumLock
+            Key::Up => 0x7E,
+            Key::Down => 0x7D,
+            Key::Left => 0x7B,
+            Key::Right => 0x7C,
+            Key::Home => 0x73,
+            Key::End => 0x77,
+            Key::PageUp => 0x74,
+            Key::PageDown => 0x79,
+            Key::Insert => 0x72,
+            Key::Delete => 0x75,
+            Key::PrintScreen => 0x69,
+            Key::ScrollLock => 0x6B,
+            Key::Pause => 0x71,
+            Key::NumLock => 0x47,
+            Key::Numpad0 => 0x52,
+            Key::Numpad1 => 0x53,
+            Key::Numpad2 => 0x54,
+            Key::Numpad3 => 0x55,
+            Key::Numpad4 => 0x56,
+            Key::Numpad5 => 0x57,
+            Key::Numpad6 => 0x58,
+            Key::Numpad7 => 0x59,
+            Key::Numpad8 => 0x5B,
+            Key::Numpad9 => 0x5C,
+            Key::NumpadAdd => 0x45,
+            Key::NumpadSubtract => 0x4E,
+            Key::NumpadMultiply => 0x43,
+            Key::NumpadDivide => 0x4B,
+            Key::NumpadDecimal => 0x41,
+            Key::NumpadEnter => 0x4C,
+        };
+        Some(code)
+    }
+
+    /// Key at a macOS virtual key code, the inverse of `mac_key_code`
+    pub fn from_mac_key_code(code: u16) -> Option<Key> {
+        Key::ALL
+            .iter()
+            .copied()
+            .find(|key| key.mac_key_code() == Some(code))
+    }
+
+    /// Key producing a keysym, the inverse of `keysym`
+    pub fn from_keysym(keysym: u32) -> Option<Key> {
+        Key::ALL
+            .iter()
+            .copied()
+            .find(|key| key.keysym() == Some(keysym))
+    }
+}
+
+/// Keys whose set-1 scan code takes the E0 prefix, and the byte after it
+const EXTENDED_SCAN_CODES: [(Key, u16); 18] = [
+    (Key::AltGr, 0x38),
+    (Key::Meta, 0x5B),
+    (Key::CtrlRight, 0x1D),
+    (Key::MetaRight, 0x5C),
+    (Key::NumpadEnter, 0x1C),
+    (Key::NumpadDivide, 0x35),
+    // Windows reports NumLock as E0 45; a bare 45 is Pause
+    (Key::NumLock, 0x45),
+    (Key::PrintScreen, 0x37),
+    (Key::Home, 0x47),
+    (Key::Up, 0x48),
+    (Key::PageUp, 0x49),
+    (Key::Left, 0x4B),
+    (Key::Right, 0x4D),
+    (Key::End, 0x4F),
+    (Key::Down, 0x50),
+    (Key::PageDown, 0x51),
+    (Key::Insert, 0x52),
+    (Key::Delete, 0x53),
+];
+
+impl From<Key> for PhysicalKey {
+    fn from(key: Key) -> Self {
+        key.physical()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn every_key_has_a_distinct_position() {
+        let mut seen = HashSet::new();
+        for &key in Key::ALL {
+            let code = key.physical();
+            // XTest keycodes are evdev codes plus 8 and must fit a byte
+            assert!(code.0 > 0 && code.0 + 8 <= 255, "{key:?} -> {code:?}");
+            assert!(seen.insert(code), "{key:?} shares {code:?}");
+            assert_eq!(Key::from_physical(code), Some(key));
+        }
+    }
+
+    #[test]
+    fn well_known_positions_convert_both_ways() {
+        let known = [
+            (Key::Escape, 1),
+            (Key::Q, 16),
+            (Key::A, 30),
+            (Key::Enter, 28),
+            (Key::Space, 57),
+            // Sides of a modifier are separate positions
+            (Key::Shift, 42),
+            (Key::ShiftRight, 54),
+            (Key::Ctrl, 29),
+            (Key::CtrlRight, 97),
+        ];
+        for (key, code) in known {
+            assert_eq!(PhysicalKey::from(key), PhysicalKey(code));
+            assert_eq!(Key::from_physical(PhysicalKey(code)), Some(key));
+        }
+        assert_eq!(Key::from_physical(PhysicalKey(0)), None);
+        assert_eq!(Key::from_physical(PhysicalKey(0x2ff)), None);
+    }
+
+    #[test]
+    fn set1_scan_codes_are_distinct_and_round_trip() {
+        let known = [
+            (Key::A, 0x1E),
+            (Key::Shift, 0x2A),
+            (Key::ShiftRight, 0x36),
+            // Sides and the navigation block differ by the E0 prefix
+            (Key::Ctrl, 0x1D),
+            (Key::CtrlRight, 0xE01D),
+            (Key::Up, 0xE048),
+            (Key::Numpad8, 0x48),
+        ];
+        for (key, code) in known {
+            assert_eq!(key.set1_scan_code(), Some(ScanCode(code)));
+        }
+        assert_eq!(Key::Pause.set1_scan_code(), None);
+        let mut seen = HashSet::new();
+        for &key in Key::ALL.iter().filter(|&&key| key != Key::Pause) {
+            let code = key.set1_scan_code().unwrap();
+            assert!(seen.insert(code), "{key:?} shares {code:?}");
+            assert_eq!(Key::from_set1_scan_code(code), Some(key));
+        }
+        assert_eq!(Key::from_set1_scan_code(ScanCode(0xE030)), None);
+    }
+
+    #[test]
+    fn platform_scan_codes_round_trip() {
+        for &key in Key::ALL {
+            if let Some(code) = key.to_scancode() {
+                assert_eq!(Key::from_scancode(code), Some(key), "{code:?}");
+            }
+        }
+        #[cfg(target_os = "linux")]
+        assert_eq!(Key::CtrlRight.to_scancode(), Some(ScanCode(97)));
+        #[cfg(windows)]
+        assert_eq!(Key::CtrlRight.to_scancode(), Some(ScanCode(0xE01D)));
+    }
+
+    #[test]
+    fn every_key_has_a_mac_key_code() {
+        let mut seen = HashSet::new();
+        for &key in Key::ALL {
+            let code = key
+                .mac_key_code()
+                .unwrap_or_else(|| panic!("{key:?} has no macOS key code"));
+            assert!(seen.insert(code), "{key:?} shares mac code {code:#x}");
+            assert_eq!(Key::from_mac_key_code(code), Some(key));
+        }
+    }
+
+    #[test]
+    fn keysyms_are_distinct_and_round_trip() {
+        let mut seen = HashSet::new();
+        for &key in Key::ALL {
+            // Keys without a keysym fall back to their position on X11
+            let Some(keysym) = key.keysym() else {
+                assert_eq!(key, Key::IntlBackslash, "{key:?} has no keysym");
+                continue;
+            };
+            assert!(seen.insert(keysym), "{key:?} shares keysym {keysym:#x}");
+            assert_eq!(Key::from_keysym(keysym), Some(key));
+        }
+    }
+}
@@ -0,0 +1,675 @@
+// Canonical key, button and hotkey names shared by Display, FromStr and
+// serde. Names are platform-independent so configs move between OSes
+// unchanged.
+
+use crate::{normalized, DeviceId, Hotkey, Key, Modifier, MouseButton, PhysicalKey, Trigger};
+use serde::de::{self, value::MapAccessDeserializer, MapAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::BTreeSet;
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Error returned when a key name is not in the canonical table
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("unknown key name: {0:?}")]
+pub struct UnknownKeyName(pub String);
+
+/// Error returned when a string is not a valid hotkey
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ParseHotkeyError {
+    #[error("hotkey {0:?} has no trigger key or button")]
+    MissingTrigger(String),
+
+    #[error("{token:?} in hotkey {hotkey:?} is not a modifier, key or mouse button")]
+    UnknownToken { hotkey: String, token: String },
+}
+
+impl Key {
+    /// Every key variant, in declaration order
+    pub const ALL: &'static [Key] = &[
+        Key::A,
+        Key::B,
+        Key::C,
+        Key::D,
+        Key::E,
+        Key::F,
+        Key::G,
+        Key::H,
+        Key::I,
+        Key::J,
+        Key::K,
+        Key::L,
+        Key::M,
+        Key::N,
+        Key::O,
+        Key::P,
+        Key::Q,
+        Key::R,
+        Key::S,
+        Key::T,
+        Key::U,
+        Key::V,
+        Key::W,
+        Key::X,
+        Key::Y,
+        Key::Z,
+        Key::Num0,
+        Key::Num1,
+        Key::Num2,
+        Key::Num3,
+        Key::Num4,
+        Key::Num5,
+        Key::Num6,
+        Key::Num7,
+        Key::Num8,
+        Key::Num9,
+        Key::Ctrl,
+        Key::Shift,
+        Key::Alt,
+        Key::Meta,
+        Key::AltGr,
+        Key::CtrlRight,
+        Key::ShiftRight,
+        Key::MetaRight,
+        Key::F1,
+        Key::F2,
+        Key::F3,
+        Key::F4,
+        Key::F5,
+        Key::F6,
+        Key::F7,
+        Key::F8,
+        Key::F9,
+        Key::F10,
+        Key::F11,
+        Key::F12,
+        Key::Enter,
+        Key::Escape,
+        Key::Space,
+        Key::Tab,
+        Key::Backspace,
+        Key::CapsLock,
+        Key::Grave,
+        Key::Minus,
+        Key::Equals,
+        Key::LeftBracket,
+        Key::RightBracket,
+        Key::Backslash,
+        Key::Semicolon,
+        Key::Quote,
+        Key::Comma,
+        Key::Period,
+        Key::Slash,
+        Key::IntlBackslash,
+        Key::Up,
+        Key::Down,
+        Key::Left,
+        Key::Right,
+        Key::Home,
+        Key::End,
+        Key::PageUp,
+        Key::PageDown,
+        Key::Insert,
+        Key::Delete,
+        Key::PrintScreen,
+        Key::ScrollLock,
+        Key::Pause,
+        Key::NumLock,
+        Key::Numpad0,
+        Key::Numpad1,
+        Key::Numpad2,
+        Key::Numpad3,
+        Key::Numpad4,
+        Key::Numpad5,
+        Key::Numpad6,
+        Key::Numpad7,
+        Key::Numpad8,
+        Key::Numpad9,
+        Key::NumpadAdd,
+        Key::NumpadSubtract,
+        Key::NumpadMultiply,
+        Key::NumpadDivide,
+        Key::NumpadDecimal,
+        Key::NumpadEnter,
+    ];
+
+    /// Canonical name of this key (exhaustive, so every variant has one)
+    pub fn name(self) -> &'static str {
+        match self {
+            Key::A => "A",
+            Key::B => "B",
+            Key::C => "C",
+            Key::D => "D",
+            Key::E => "E",
+            Key::F => "F",
+            Key::G => "G",
+            Key::H => "H",
+            Key::I => "I",
+            Key::J => "J",
+            Key::K => "K",
+            Key::L => "L",
+            Key::M => "M",
+            Key::N => "N",
+            Key::O => "O",
+            Key::P => "P",
+            Key::Q => "Q",
+            Key::R => "R",
+            Key::S => "S",
+            Key::T => "T",
+            Key::U => "U",
+            Key::V => "V",
+            Key::W => "W",
+            Key::X => "X",
+            Key::Y => "Y",
+            Key::Z => "Z",
+            Key::Num0 => "0",
+            Key::Num1 => "1",
+            Key::Num2 => "2",
+            Key::Num3 => "3",
+            Key::Num4 => "4",
+            Key::Num5 => "5",
+            Key::Num6 => "6",
+            Key::Num7 => "7",
+            Key::Num8 => "8",
+            Key::Num9 => "9",
+            Key::Ctrl => "Ctrl",
+            Key::Shift => "Shift",
+            Key::Alt => "Alt",
+            Key::Meta => "Meta",
+            Key::AltGr => "AltGr",
+            Key::CtrlRight => "CtrlRight",
+            Key::ShiftRight => "ShiftRight",
+            Key::MetaRight => "MetaRight",
+            Key::F1 => "F1",
+            Key::F2 => "F2",
+            Key::F3 => "F3",
+            Key::F4 => "F4",
+            Key::F5 => "F5",
+            Key::F6 => "F6",
+            Key::F7 => "F7",
+            Key::F8 => "F8",
+            Key::F9 => "F9",
+            Key::F10 => "F10",
+            Key::F11 => "F11",
+            Key::F12 => "F12",
+            Key::Enter => "Enter",
+            Key::Escape => "Escape",
+            Key::Space => "Space",
+            Key::Tab => "Tab",
+            Key::Backspace => "Backspace",
+            Key::CapsLock => "CapsLock",
+            Key::Grave => "Grave",
+            Key::Minus => "Minus",
+            Key::Equals => "Equals",
+            Key::LeftBracket => "LeftBracket",
+            Key::RightBracket => "RightBracket",
+            Key::Backslash => "Backslash",
+            Key::Semicolon => "Semicolon",
+            Key::Quote => "Quote",
+            Key::Comma => "Comma",
+            Key::Period => "Period",
+            Key::Slash => "Slash",
+            Key::IntlBackslash => "IntlBackslash",
+            Key::Up => "Up",
+            Key::Down => "Down",
+            Key::Left => "Left",
+            Key::Right => "Right",
+            Key::Home => "Home",
+            Key::End => "End",
+            Key::PageUp => "PageUp",
+            Key::PageDown => "PageDown",
+            Key::Insert => "Insert",
+            Key::Delete => "Delete",
+            Key::PrintScreen => "PrintScreen",
+            Key::ScrollLock => "ScrollLock",
+            Key::Pause => "Pause",
+            Key::NumLock => "NumLock",
+            Key::Numpad0 => "Numpad0",
+            Key::Numpad1 => "Numpad1",
+            Key::Numpad2 => "Numpad2",
+            Key::Numpad3 => "Numpad3",
+            Key::Numpad4 => "Numpad4",
+            Key::Numpad5 => "Numpad5",
+            Key::Numpad6 => "Numpad6",
+            Key::Numpad7 => "Numpad7",
+            Key::Numpad8 => "Numpad8",
+            Key::Numpad9 => "Numpad9",
+            Key::NumpadAdd => "NumpadAdd",
+            Key::NumpadSubtract => "NumpadSubtract",
+            Key::NumpadMultiply => "NumpadMultiply",
+            Key::NumpadDivide => "NumpadDivide",
+            Key::NumpadDecimal => "NumpadDecimal",
+            Key::NumpadEnter => "NumpadEnter",
+        }
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Key {
+    type Err = UnknownKeyName;
+
+    /// Parse a canonical key name (case-insensitive)
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Key::ALL
+            .iter()
+            .copied()
+            .find(|key| key.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| UnknownKeyName(s.to_string()))
+    }
+}
+
+impl Serialize for Key {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+impl<'de> Deserialize<'de> for Key {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        name.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl MouseButton {
+    /// Every button variant, in declaration order
+    pub const ALL: &'static [MouseButton] = &[
+        MouseButton::Left,
+        MouseButton::Right,
+        MouseButton::Middle,
+        MouseButton::Button4,
+        MouseButton::Button5,
+    ];
+
+    /// Canonical name of this button, as used in hotkey strings
+    pub fn name(self) -> &'static str {
+        match self {
+            MouseButton::Left => "MouseLeft",
+            MouseButton::Right => "MouseRight",
+            MouseButton::Middle => "MouseMiddle",
+            MouseButton::Button4 => "Mouse4",
+            MouseButton::Button5 => "Mouse5",
+        }
+    }
+}
+
+impl fmt::Display for MouseButton {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl Modifier {
+    /// Every modifier variant, in declaration order
+    pub const ALL: &'static [Modifier] = &[
+        Modifier::Ctrl,
+        Modifier::Shift,
+        Modifier::Alt,
+        Modifier::Meta,
+        Modifier::CtrlLeft,
+        Modifier::CtrlRight,
+        Modifier::ShiftLeft,
+        Modifier::ShiftRight,
+        Modifier::AltLeft,
+        Modifier::AltRight,
+        Modifier::MetaLeft,
+        Modifier::MetaRight,
+    ];
+
+    /// Canonical name of this modifier, as used in hotkey strings
+    pub fn name(self) -> &'static str {
+        match self {
+            Modifier::Ctrl => "Ctrl",
+            Modifier::Shift => "Shift",
+            Modifier::Alt => "Alt",
+            Modifier::Meta => "Meta",
+            Modifier::CtrlLeft => "CtrlLeft",
+            Modifier::CtrlRight => "CtrlRight",
+            Modifier::ShiftLeft => "ShiftLeft",
+            Modifier::ShiftRight => "ShiftRight",
+            Modifier::AltLeft => "AltLeft",
+            Modifier::AltRight => "AltRight",
+            Modifier::MetaLeft => "MetaLeft",
+            Modifier::MetaRight => "MetaRight",
+        }
+    }
+}
+
+impl fmt::Display for Modifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl fmt::Display for Hotkey {
+    /// Modifiers and held keys, then the trigger, joined by `+`; a device
+    /// filter follows as `@name`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for modifier in &self.modifiers {
+            write!(f, "{modifier}+")?;
+        }
+        for key in &self.held {
+            write!(f, "{key}+")?;
+        }
+        write!(f, "{}", TriggerName(&self.trigger))?;
+        if let Some(DeviceId(device)) = &self.device {
+            write!(f, "@{device}")?;
+        }
+        Ok(())
+    }
+}
+
+/// A trigger as written in hotkey strings
+struct TriggerName<'a>(&'a Trigger);
+
+impl fmt::Display for TriggerName<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Trigger::Key(key) => write!(f, "{key}"),
+            Trigger::MouseButton(button) => write!(f, "{button}"),
+            Trigger::PhysicalKey(PhysicalKey(code)) => write!(f, "Physical{code}"),
+            Trigger::MouseClicks { button, count } => write!(f, "{button}*{count}"),
+            Trigger::MultiTap {
+                trigger,
+                count,
+                within,
+            } => write!(
+                f,
+                "{}*{count}/{}ms",
+                TriggerName(trigger),
+                within.as_millis()
+            ),
+            Trigger::LongPress { trigger, threshold } => {
+                write!(f, "{}>{}ms", TriggerName(trigger), threshold.as_millis())
+            }
+        }
+    }
+}
+
+impl FromStr for Hotkey {
+    type Err = ParseHotkeyError;
+
+    /// Parse the `Display` form (case-insensitive, spaces around `+`
+    /// allowed): "Ctrl+Shift+P", "AltRight+E", "Mouse4", "MouseLeft*2",
+    /// "Physical16", "Shift*2/300ms", "Escape>500ms", "A+B@Macro Pad"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let unknown = |token: &str| ParseHotkeyError::UnknownToken {
+            hotkey: s.to_string(),
+            token: token.to_string(),
+        };
+        // Names never contain '@', so the first one starts the device
+        let (combo, device) = match s.split_once('@') {
+            Some((combo, device)) => (combo, Some(DeviceId(device.trim().to_string()))),
+            None => (s, None),
+        };
+        let mut tokens: Vec<&str> = combo.split('+').map(str::trim).collect();
+        let trigger = match tokens.pop() {
+            Some(token) if !token.is_empty() => token,
+            _ => return Err(ParseHotkeyError::MissingTrigger(s.to_string())),
+        };
+
+        let mut hotkey =
+            Hotkey::combo(&[], parse_trigger(trigger).ok_or_else(|| unknown(trigger))?);
+        for token in tokens {
+            // Sided modifiers first, as "CtrlRight" also names a key
+            let sided = Modifier::ALL
+                .iter()
+                .find(|modifier| modifier.name().eq_ignore_ascii_case(token));
+            if let Some(&modifier) = sided {
+                hotkey.modifiers.push(modifier);
+                continue;
+            }
+            let key: Key = token.parse().map_err(|_| unknown(token))?;
+            match Modifier::from_key(key) {
+                Some(modifier) => hotkey.modifiers.push(modifier),
+                None => {
+                    hotkey.held.insert(key);
+                }
+            }
+        }
+        hotkey.modifiers = normalized(hotkey.modifiers);
+        hotkey.device = device;
+        Ok(hotkey)
+    }
+}
+
+fn parse_trigger(token: &str) -> Option<Trigger> {
+    if let Some((held, threshold)) = token.rsplit_once('>') {
+        return Some(Trigger::LongPress {
+            trigger: Box::new(parse_trigger(held.trim())?),
+            threshold: parse_millis(threshold)?,
+        });
+    }
+    if let Some((repeated, count)) = token.rsplit_once('*') {
+        // Taps carry their window ("*2/300ms"), clicks use the system's
+        if let Some((count, within)) = count.split_once('/') {
+            return Some(Trigger::MultiTap {
+                trigger: Box::new(parse_trigger(repeated.trim())?),
+                count: count.trim().parse().ok()?,
+                within: parse_millis(within)?,
+            });
+        }
+        return Some(Trigger::MouseClicks {
+            button: parse_button(repeated.trim())?,
+            count: count.trim().parse().ok()?,
+        });
+    }
+    if let Some(button) = parse_button(token) {
+        return Some(Trigger::MouseButton(button));
+    }
+    let physical = token
+        .get(.."Physical".len())
+        .filter(|prefix| prefix.eq_ignore_ascii_case("Physical"));
+    if physical.is_some() {
+        let code = token["Physical".len()..].parse().ok()?;
+        return Some(Trigger::PhysicalKey(PhysicalKey(code)));
+    }
+    token.parse().ok().map(Trigger::Key)
+}
+
+/// A duration written as "300ms"
+fn parse_millis(token: &str) -> Option<Duration> {
+    let token = token.trim().to_ascii_lowercase();
+    let millis = token.strip_suffix("ms")?.trim().parse().ok()?;
+    Some(Duration::from_millis(millis))
+}
+
+fn parse_button(token: &str) -> Option<MouseButton> {
+    MouseButton::ALL
+        .iter()
+        .copied()
+        .find(|button| button.name().eq_ignore_ascii_case(token))
+}
+
+impl Serialize for Hotkey {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Struct form of a hotkey, for configs that spell out its fields
+#[derive(Deserialize)]
+struct HotkeyFields {
+    modifiers: Vec<Modifier>,
+    trigger: Trigger,
+    #[serde(default)]
+    held: BTreeSet<Key>,
+    #[serde(default)]
+    device: Option<DeviceId>,
+}
+
+struct HotkeyVisitor;
+
+impl<'de> Visitor<'de> for HotkeyVisitor {
+    type Value = Hotkey;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a hotkey string like \"Ctrl+Shift+P\" or a hotkey table")
+    }
+
+    fn visit_str<E: de::Error>(self, text: &str) -> Result<Hotkey, E> {
+        text.parse().map_err(E::custom)
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Hotkey, A::Error> {
+        let fields = HotkeyFields::deserialize(MapAccessDeserializer::new(map))?;
+        Ok(Hotkey {
+            modifiers: normalized(fields.modifiers),
+            trigger: fields.trigger,
+            held: fields.held,
+            device: fields.device,
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for Hotkey {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(HotkeyVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::de::value::{Error as ValueError, StrDeserializer};
+    use serde::de::IntoDeserializer;
+    use std::collections::HashSet;
+
+    fn deserialize_key(name: &str) -> Result<Key, ValueError> {
+        let deserializer: StrDeserializer<ValueError> = name.into_deserializer();
+        Key::deserialize(deserializer)
+    }
+
+    #[test]
+    fn all_lists_every_key_once_in_declaration_order() {
+        for (index, &key) in Key::ALL.iter().enumerate() {
+            assert_eq!(key as usize, index, "{key:?} is out of place in Key::ALL");
+        }
+        // NumpadEnter is the last variant declared
+        assert_eq!(Key::ALL.len(), Key::NumpadEnter as usize + 1);
+    }
+
+    #[test]
+    fn every_key_has_exactly_one_name() {
+        let mut seen = HashSet::new();
+        for &key in Key::ALL {
+            let name = key.name();
+            assert!(
+                seen.insert(name.to_ascii_lowercase()),
+                "{key:?} shares the name {name:?}"
+            );
+            assert_eq!(key.to_string(), name);
+            assert_eq!(name.parse(), Ok(key));
+            assert_eq!(name.to_ascii_lowercase().parse(), Ok(key));
+            assert_eq!(name.to_ascii_uppercase().parse(), Ok(key));
+        }
+    }
+
+    #[test]
+    fn key_names_do_not_collide_with_button_names() {
+        for &button in MouseButton::ALL {
+            assert_eq!(
+                button.name().parse::<Key>(),
+                Err(UnknownKeyName(button.name().to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn keys_deserialize_from_their_names() {
+        for &key in Key::ALL {
+            assert_eq!(deserialize_key(key.name()).unwrap(), key);
+        }
+        assert_eq!(deserialize_key("pageup").unwrap(), Key::PageUp);
+        let err = deserialize_key("VK_PRIOR").unwrap_err();
+        assert_eq!(err.to_string(), "unknown key name: \"VK_PRIOR\"");
+    }
+
+    #[test]
+    fn held_keys_are_a_set() {
+        let ab: Hotkey = "A+B+C".parse().unwrap();
+        let ba: Hotkey = "B+A+A+C".parse().unwrap();
+        assert_eq!(ab, ba);
+        assert_eq!(ab, Hotkey::key(Key::C).while_held(&[Key::B, Key::A]));
+        // Displayed in a fixed order whatever order they were given in
+        assert_eq!(ba.to_string(), "A+B+C");
+    }
+
+    #[test]
+    fn parses_a_combo_in_any_case_and_modifier_order() {
+        let expected = Hotkey::combo(&[Modifier::Ctrl, Modifier::Shift], Trigger::Key(Key::P));
+        assert_eq!("Ctrl+Shift+P".parse(), Ok(expected.clone()));
+        assert_eq!("shift + CTRL + p".parse(), Ok(expected));
+        assert_eq!(
+            "AltRight+E".parse(),
+            Ok(Hotkey::combo(&[Modifier::AltRight], Trigger::Key(Key::E)))
+        );
+    }
+
+    #[test]
+    fn parses_a_bare_key_and_a_mouse_button() {
+        assert_eq!("F5".parse(), Ok(Hotkey::key(Key::F5)));
+        assert_eq!("mouse4".parse(), Ok(Hotkey::mouse(MouseButton::Button4)));
+        assert_eq!(
+            "Ctrl+MouseLeft".parse(),
+            Ok(Hotkey::combo(
+                &[Modifier::Ctrl],
+                Trigger::MouseButton(MouseButton::Left)
+            ))
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_tokens_and_missing_triggers() {
+        assert_eq!(
+            "Ctrl+Hyper+P".parse::<Hotkey>(),
+            Err(ParseHotkeyError::UnknownToken {
+                hotkey: "Ctrl+Hyper+P".to_string(),
+                token: "Hyper".to_string(),
+            })
+        );
+        let err = "Ctrl+Mouse9".parse::<Hotkey>().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "\"Mouse9\" in hotkey \"Ctrl+Mouse9\" is not a modifier, key or mouse button"
+        );
+        assert_eq!(
+            "Ctrl+".parse::<Hotkey>(),
+            Err(ParseHotkeyError::MissingTrigger("Ctrl+".to_string()))
+        );
+        assert_eq!(
+            "".parse::<Hotkey>(),
+            Err(ParseHotkeyError::MissingTrigger(String::new()))
+        );
+    }
+
+    #[test]
+    fn display_round_trips_through_from_str() {
+        let ms = Duration::from_millis;
+        let hotkeys = [
+            Hotkey::combo(&[Modifier::Shift, Modifier::Ctrl], Trigger::Key(Key::P)),
+            Hotkey::key(Key::PageUp),
+            Hotkey::mouse(MouseButton::Button5),
+            Hotkey::combo(&[Modifier::MetaLeft], Trigger::PhysicalKey(PhysicalKey(16))),
+            Hotkey::double_click(MouseButton::Left),
+            Hotkey::multi_tap(Trigger::Key(Key::Shift), 2, ms(300)),
+            Hotkey::long_press(Trigger::Key(Key::Escape), ms(500)),
+            Hotkey::key(Key::B).while_held(&[Key::A]),
+            Hotkey::key(Key::A).from_device(DeviceId("Macro Pad".to_string())),
+        ];
+        for hotkey in hotkeys {
+            let text = hotkey.to_string();
+            assert_eq!(text.parse(), Ok(hotkey), "{text}");
+        }
+        assert_eq!(
+            Hotkey::combo(&[Modifier::Shift, Modifier::Ctrl], Trigger::Key(Key::P)).to_string(),
+            "Ctrl+Shift+P"
+        );
+    }
+}
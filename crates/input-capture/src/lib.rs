@@ -1,18 +1,82 @@
 use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
 use tokio_stream::Stream;
 
+mod coalesce;
+#[cfg(target_os = "linux")]
+mod evdev_capture;
+mod key_codes;
+mod key_names;
+#[cfg(any(windows, all(test, target_os = "linux")))]
+mod suppress;
+
+pub use key_names::{ParseHotkeyError, UnknownKeyName};
+
 /// Platform-independent input event
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum InputEvent {
     KeyPress(Key),
     KeyRelease(Key),
+    // Reported right after the logical key event for the same keystroke
+    PhysicalKeyPress(PhysicalKey),
+    PhysicalKeyRelease(PhysicalKey),
     MousePress(MouseButton),
     MouseRelease(MouseButton),
-    MouseMove { x: i32, y: i32 },
+    MouseMove {
+        x: i32,
+        y: i32,
+    },
+    /// Wheel detents; positive `dx` scrolls right and positive `dy` up
+    MouseWheel {
+        dx: i32,
+        dy: i32,
+    },
+    /// The foreground window changed; releases for held keys may be lost
+    ///
+    /// Reported on Windows and on X11 under an EWMH window manager; the
+    /// evdev and macOS captures never report it.
+    FocusChanged,
+}
+
+/// Event from a capture backend, with the modifiers the OS reported as held
+/// when it fired
+///
+/// Held modifiers come from the OS rather than from earlier events, so a
+/// modifier pressed before capture started still counts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CapturedEvent {
+    pub event: InputEvent,
+    pub modifiers: ModifierSet,
+    /// Device the event came from; None for backends that can't tell
+    /// devices apart
+    pub device_id: Option<DeviceId>,
+    /// How long the key or button was down, on releases whose press was
+    /// captured; None for everything else
+    pub held_for: Option<Duration>,
 }
 
-/// Keyboard keys (subset for demonstration)
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// `dwExtraInfo` the Windows executor stamps on everything it sends, so
+/// the hooks can drop HandPlusPlus's own output ("HPP+")
+pub const INJECTED_EXTRA_INFO: usize = 0x4850_502B;
+
+/// Name of the uinput device the executor injects through on Linux, which
+/// evdev capture skips
+pub const VIRTUAL_DEVICE_NAME: &str = "HandPlusPlus virtual input";
+
+/// Input event stamped with its offset from the start of a recording
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimedEvent {
+    pub event: InputEvent,
+    pub at: Duration,
+}
+
+/// Keyboard keys, named by their position on a US QWERTY keyboard
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[non_exhaustive]
 pub enum Key {
     // Letters
     A,
@@ -54,11 +118,15 @@ pub enum Key {
     Num8,
     Num9,
 
-    // Modifiers
+    // Modifiers; the unsuffixed ones are the left-hand keys
     Ctrl,
     Shift,
     Alt,
-    Meta, // Meta = Win/Super
+    Meta,  // Meta = Win/Super
+    AltGr, // Right Alt, the level-3 shift on international layouts
+    CtrlRight,
+    ShiftRight,
+    MetaRight,
 
     // Function keys
     F1,
@@ -80,10 +148,84 @@ pub enum Key {
     Space,
     Tab,
     Backspace,
+    CapsLock,
+
+    // Punctuation, named by their US QWERTY position
+    Grave,
+    Minus,
+    Equals,
+    LeftBracket,
+    RightBracket,
+    Backslash,
+    Semicolon,
+    Quote,
+    Comma,
+    Period,
+    Slash,
+    IntlBackslash, // Extra key left of Z on ISO keyboards
+
+    // Navigation cluster and arrows
+    Up,
+    Down,
+    Left,
+    Right,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    Insert,
+    Delete,
+
+    // Above the navigation cluster
+    PrintScreen,
+    ScrollLock,
+    Pause,
+
+    // Numeric keypad
+    NumLock,
+    Numpad0,
+    Numpad1,
+    Numpad2,
+    Numpad3,
+    Numpad4,
+    Numpad5,
+    Numpad6,
+    Numpad7,
+    Numpad8,
+    Numpad9,
+    NumpadAdd,
+    NumpadSubtract,
+    NumpadMultiply,
+    NumpadDivide,
+    NumpadDecimal,
+    NumpadEnter,
 }
 
+/// Layout-independent key position, as an evdev key code
+///
+/// For the main block these match PC set-1 scan codes, so Windows scan
+/// codes translate directly (e.g. `PhysicalKey(16)` is the QWERTY `Q`
+/// position on both platforms, even under AZERTY).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct PhysicalKey(pub u16);
+
+/// Hardware scan code as a platform's raw injection takes it
+///
+/// Set-1 on Windows, with an E0 prefix in the high byte (`0xE048` is Up);
+/// evdev codes on Linux, the same numbers as `PhysicalKey`. See
+/// `Key::to_scancode`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ScanCode(pub u16);
+
+/// Input device an event came from, by the name the OS reports for it
+///
+/// Names survive replugging and reboots, unlike device paths or handles;
+/// identical devices share one.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct DeviceId(pub String);
+
 /// Mouse buttons
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum MouseButton {
     Left,
     Right,
@@ -92,33 +234,279 @@ pub enum MouseButton {
     Button5, // Side button (forward)
 }
 
-/// Modifiers for hotkey combinations
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// Modifiers for hotkey combinations, ordered as declared
+///
+/// The unsuffixed modifiers match either side; the sided ones only match
+/// while that side is held and the other is not.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub enum Modifier {
     Ctrl,
     Shift,
     Alt,
     Meta,
+    CtrlLeft,
+    CtrlRight,
+    ShiftLeft,
+    ShiftRight,
+    AltLeft,
+    AltRight,
+    MetaLeft,
+    MetaRight,
+}
+
+impl Modifier {
+    /// Side-agnostic modifier contributed by a key (if it is a modifier
+    /// key); AltGr is left out, since it only acts as Alt on some layouts
+    pub fn from_key(key: Key) -> Option<Self> {
+        match key {
+            Key::Ctrl | Key::CtrlRight => Some(Modifier::Ctrl),
+            Key::Shift | Key::ShiftRight => Some(Modifier::Shift),
+            Key::Alt => Some(Modifier::Alt),
+            Key::Meta | Key::MetaRight => Some(Modifier::Meta),
+            _ => None,
+        }
+    }
+
+    /// Sided modifier for a modifier key, with AltGr as the right Alt
+    pub fn sided_from_key(key: Key) -> Option<Self> {
+        match key {
+            Key::Ctrl => Some(Modifier::CtrlLeft),
+            Key::CtrlRight => Some(Modifier::CtrlRight),
+            Key::Shift => Some(Modifier::ShiftLeft),
+            Key::ShiftRight => Some(Modifier::ShiftRight),
+            Key::Alt => Some(Modifier::AltLeft),
+            Key::AltGr => Some(Modifier::AltRight),
+            Key::Meta => Some(Modifier::MetaLeft),
+            Key::MetaRight => Some(Modifier::MetaRight),
+            _ => None,
+        }
+    }
+
+    /// Key that holds this modifier down; the left one unless sided right
+    pub fn key(self) -> Key {
+        match self {
+            Modifier::Ctrl | Modifier::CtrlLeft => Key::Ctrl,
+            Modifier::Shift | Modifier::ShiftLeft => Key::Shift,
+            Modifier::Alt | Modifier::AltLeft => Key::Alt,
+            Modifier::Meta | Modifier::MetaLeft => Key::Meta,
+            Modifier::CtrlRight => Key::CtrlRight,
+            Modifier::ShiftRight => Key::ShiftRight,
+            Modifier::AltRight => Key::AltGr,
+            Modifier::MetaRight => Key::MetaRight,
+        }
+    }
+
+    /// The side-agnostic modifier this one is a side of (or itself)
+    pub fn unsided(self) -> Self {
+        match self {
+            Modifier::CtrlLeft | Modifier::CtrlRight => Modifier::Ctrl,
+            Modifier::ShiftLeft | Modifier::ShiftRight => Modifier::Shift,
+            Modifier::AltLeft | Modifier::AltRight => Modifier::Alt,
+            Modifier::MetaLeft | Modifier::MetaRight => Modifier::Meta,
+            unsided => unsided,
+        }
+    }
+
+    /// Whether this names a specific side
+    pub fn is_sided(self) -> bool {
+        self.unsided() != self
+    }
+}
+
+bitflags::bitflags! {
+    /// Set of modifiers held at the same time
+    ///
+    /// The side-agnostic flags are set whenever either side is held; the
+    /// sided ones only where the backend can tell which side it is.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+    pub struct ModifierSet: u16 {
+        const CTRL = 1;
+        const SHIFT = 1 << 1;
+        const ALT = 1 << 2;
+        const META = 1 << 3;
+        const CTRL_LEFT = 1 << 4;
+        const CTRL_RIGHT = 1 << 5;
+        const SHIFT_LEFT = 1 << 6;
+        const SHIFT_RIGHT = 1 << 7;
+        const ALT_LEFT = 1 << 8;
+        const ALT_RIGHT = 1 << 9;
+        const META_LEFT = 1 << 10;
+        const META_RIGHT = 1 << 11;
+    }
+}
+
+impl ModifierSet {
+    // Each side-agnostic flag with both of its sides
+    const SIDES: [(ModifierSet, ModifierSet); 4] = [
+        (
+            ModifierSet::CTRL,
+            ModifierSet::CTRL_LEFT.union(ModifierSet::CTRL_RIGHT),
+        ),
+        (
+            ModifierSet::SHIFT,
+            ModifierSet::SHIFT_LEFT.union(ModifierSet::SHIFT_RIGHT),
+        ),
+        (
+            ModifierSet::ALT,
+            ModifierSet::ALT_LEFT.union(ModifierSet::ALT_RIGHT),
+        ),
+        (
+            ModifierSet::META,
+            ModifierSet::META_LEFT.union(ModifierSet::META_RIGHT),
+        ),
+    ];
+
+    /// Side-agnostic modifiers in the set, in `Modifier` declaration order
+    pub fn modifiers(self) -> impl Iterator<Item = Modifier> {
+        [
+            Modifier::Ctrl,
+            Modifier::Shift,
+            Modifier::Alt,
+            Modifier::Meta,
+        ]
+        .into_iter()
+        .filter(move |&modifier| self.contains(modifier.into()))
+    }
+
+    /// Whether these held modifiers satisfy a hotkey's: the same ones are
+    /// held, and each sided one on its side alone
+    pub fn matches(self, modifiers: &[Modifier]) -> bool {
+        let wanted: ModifierSet = modifiers.iter().copied().collect();
+        ModifierSet::SIDES.iter().all(|&(unsided, sides)| {
+            let held = self & (unsided | sides);
+            let wanted = wanted & (unsided | sides);
+            if wanted.intersects(sides) {
+                held == wanted
+            } else {
+                held.contains(unsided) == wanted.contains(unsided)
+            }
+        })
+    }
+
+    /// These modifiers without `modifier`, on either side
+    pub fn without(self, modifier: Modifier) -> ModifierSet {
+        let unsided = ModifierSet::from(modifier.unsided());
+        ModifierSet::SIDES
+            .iter()
+            .filter(|&&(flag, _)| flag == unsided)
+            .fold(self, |set, &(flag, sides)| set - flag - sides)
+    }
+
+    /// Update for a modifier key going down or up, tracking its side
+    #[cfg(target_os = "linux")]
+    pub(crate) fn track(&mut self, event: &InputEvent) {
+        let (key, pressed) = match *event {
+            InputEvent::KeyPress(key) => (key, true),
+            InputEvent::KeyRelease(key) => (key, false),
+            _ => return,
+        };
+        let Some(modifier) = Modifier::sided_from_key(key) else {
+            return;
+        };
+        self.set(modifier.into(), pressed);
+        // The side-agnostic flag stays while the other side is held
+        for (unsided, sides) in ModifierSet::SIDES {
+            self.set(unsided, self.intersects(sides));
+        }
+    }
+}
+
+impl From<Modifier> for ModifierSet {
+    fn from(modifier: Modifier) -> Self {
+        let side = match modifier {
+            Modifier::CtrlLeft => ModifierSet::CTRL_LEFT,
+            Modifier::CtrlRight => ModifierSet::CTRL_RIGHT,
+            Modifier::ShiftLeft => ModifierSet::SHIFT_LEFT,
+            Modifier::ShiftRight => ModifierSet::SHIFT_RIGHT,
+            Modifier::AltLeft => ModifierSet::ALT_LEFT,
+            Modifier::AltRight => ModifierSet::ALT_RIGHT,
+            Modifier::MetaLeft => ModifierSet::META_LEFT,
+            Modifier::MetaRight => ModifierSet::META_RIGHT,
+            _ => ModifierSet::empty(),
+        };
+        let unsided = match modifier.unsided() {
+            Modifier::Ctrl => ModifierSet::CTRL,
+            Modifier::Shift => ModifierSet::SHIFT,
+            Modifier::Alt => ModifierSet::ALT,
+            _ => ModifierSet::META,
+        };
+        unsided | side
+    }
+}
+
+impl FromIterator<Modifier> for ModifierSet {
+    fn from_iter<I: IntoIterator<Item = Modifier>>(modifiers: I) -> Self {
+        modifiers
+            .into_iter()
+            .fold(ModifierSet::empty(), |set, modifier| set | modifier.into())
+    }
 }
 
 /// Hotkey definition (trigger + optional modifiers)
+///
+/// Serializes as its `Display` string (e.g. "Ctrl+Shift+P"); either that or
+/// the struct's fields deserialize.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Hotkey {
-    pub modifiers: Vec<Modifier>,
+    // Sorted and without duplicates, so equal combos hash equal; private so
+    // only the constructors, parsing and deserialization can set it
+    modifiers: Vec<Modifier>,
     pub trigger: Trigger,
+    // A set, so listing held keys in another order is the same hotkey
+    held: BTreeSet<Key>,
+    /// Only match events from this device; None matches any device
+    pub device: Option<DeviceId>,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Trigger {
     Key(Key),
     MouseButton(MouseButton),
+    PhysicalKey(PhysicalKey),
+    /// `count` clicks of a button in quick succession (2 = double-click)
+    MouseClicks {
+        button: MouseButton,
+        count: u32,
+    },
+    /// `count` presses of `trigger` with at most `within` from the first to
+    /// the last (e.g. double-tap Shift)
+    MultiTap {
+        trigger: Box<Trigger>,
+        count: u32,
+        #[serde(with = "millis")]
+        within: Duration,
+    },
+    /// `trigger` held for at least `threshold`
+    LongPress {
+        trigger: Box<Trigger>,
+        #[serde(with = "millis")]
+        threshold: Duration,
+    },
 }
 
 impl Hotkey {
+    /// Every part at once; modifiers and held keys may be given in any
+    /// order, and repeats are dropped
+    pub fn new(
+        modifiers: &[Modifier],
+        trigger: Trigger,
+        held: &[Key],
+        device: Option<DeviceId>,
+    ) -> Self {
+        Self {
+            modifiers: normalized(modifiers.to_vec()),
+            trigger,
+            held: held.iter().copied().collect(),
+            device,
+        }
+    }
+
     pub fn key(key: Key) -> Self {
         Self {
             modifiers: Vec::new(),
             trigger: Trigger::Key(key),
+            held: BTreeSet::new(),
+            device: None,
         }
     }
 
@@ -126,15 +514,381 @@ impl Hotkey {
         Self {
             modifiers: Vec::new(),
             trigger: Trigger::MouseButton(button),
+            held: BTreeSet::new(),
+            device: None,
+        }
+    }
+
+    pub fn physical(key: PhysicalKey) -> Self {
+        Self {
+            modifiers: Vec::new(),
+            trigger: Trigger::PhysicalKey(key),
+            held: BTreeSet::new(),
+            device: None,
+        }
+    }
+
+    /// `count` presses of `trigger` within `within`
+    pub fn multi_tap(trigger: Trigger, count: u32, within: Duration) -> Self {
+        Self {
+            modifiers: Vec::new(),
+            trigger: Trigger::MultiTap {
+                trigger: Box::new(trigger),
+                count,
+                within,
+            },
+            held: BTreeSet::new(),
+            device: None,
+        }
+    }
+
+    /// `trigger` held for at least `threshold`
+    pub fn long_press(trigger: Trigger, threshold: Duration) -> Self {
+        Self {
+            modifiers: Vec::new(),
+            trigger: Trigger::LongPress {
+                trigger: Box::new(trigger),
+                threshold,
+            },
+            held: BTreeSet::new(),
+            device: None,
+        }
+    }
+
+    pub fn double_click(button: MouseButton) -> Self {
+        Self {
+            modifiers: Vec::new(),
+            trigger: Trigger::MouseClicks { button, count: 2 },
+            held: BTreeSet::new(),
+            device: None,
         }
     }
 
+    /// Modifiers may be given in any order, and repeats are dropped
     pub fn combo(modifiers: &[Modifier], trigger: Trigger) -> Self {
         Self {
-            modifiers: modifiers.to_vec(),
+            modifiers: normalized(modifiers.to_vec()),
             trigger,
+            held: BTreeSet::new(),
+            device: None,
         }
     }
+
+    /// Only match while all of `keys` are held (e.g. "while A and B, C")
+    pub fn while_held(mut self, keys: &[Key]) -> Self {
+        self.held = keys.iter().copied().collect();
+        self
+    }
+
+    /// Only match events from `device` (e.g. a macro pad next to the main
+    /// keyboard)
+    pub fn from_device(mut self, device: DeviceId) -> Self {
+        self.device = Some(device);
+        self
+    }
+
+    /// Modifiers that must be held, in `Modifier` order without repeats
+    pub fn modifiers(&self) -> &[Modifier] {
+        &self.modifiers
+    }
+
+    /// Non-modifier keys that must already be held for the hotkey to match
+    pub fn held(&self) -> &BTreeSet<Key> {
+        &self.held
+    }
+}
+
+/// Modifiers in `Modifier` order without repeats, as `Hotkey` keeps them
+fn normalized(mut modifiers: Vec<Modifier>) -> Vec<Modifier> {
+    modifiers.sort();
+    modifiers.dedup();
+    modifiers
+}
+
+/// `Duration` as whole milliseconds, as configs write it
+mod millis {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::Duration;
+
+    pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(u64::try_from(duration.as_millis()).unwrap_or(u64::MAX))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        u64::deserialize(deserializer).map(Duration::from_millis)
+    }
+}
+
+/// Capture options fixed at hook installation time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CaptureOptions {
+    /// Emit `MouseMove` events (disable for keyboard-only configs)
+    pub mouse_move: bool,
+    /// Emit the presses the OS repeats while a key is held; off, a held key
+    /// gives exactly one press and one release
+    pub key_repeat: bool,
+    /// Events a stream may fall behind by before `overflow` applies
+    pub backlog: usize,
+    pub overflow: Overflow,
+}
+
+impl Default for CaptureOptions {
+    fn default() -> Self {
+        Self {
+            mouse_move: true,
+            key_repeat: false,
+            backlog: 256,
+            overflow: Overflow::DropOldest,
+        }
+    }
+}
+
+impl CaptureOptions {
+    /// Options for configs that never bind on mouse movement
+    pub fn keyboard_only() -> Self {
+        Self {
+            mouse_move: false,
+            ..Self::default()
+        }
+    }
+
+    /// Also emit OS auto-repeat presses
+    pub fn with_key_repeat(mut self) -> Self {
+        self.key_repeat = true;
+        self
+    }
+
+    /// Let streams fall `backlog` events behind (at least 1), then apply
+    /// `overflow`
+    pub fn with_backlog(mut self, backlog: usize, overflow: Overflow) -> Self {
+        self.backlog = backlog;
+        self.overflow = overflow;
+        self
+    }
+
+    /// Check whether an event should be forwarded to the event stream
+    pub fn accepts(&self, event: &InputEvent) -> bool {
+        match event {
+            InputEvent::MouseMove { .. } => self.mouse_move,
+            _ => true,
+        }
+    }
+}
+
+/// What a capture does with new events while a stream is a full backlog
+/// behind; events dropped either way are counted by
+/// `InputCapture::dropped_events`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Overflow {
+    /// Hold capture back until the slowest stream catches up
+    ///
+    /// This stalls the thread delivering input: on Windows that delays all
+    /// input system-wide, and hooks that stall too long get removed.
+    Block,
+    /// Skip the oldest events the lagging stream hasn't read
+    DropOldest,
+    /// Discard new events until the slowest stream catches up
+    DropNewest,
+}
+
+/// Capture failures callers may want to handle specifically
+#[derive(Debug, thiserror::Error)]
+pub enum CaptureError {
+    /// The OS refused access to global input; carries how to fix it
+    #[error("permission denied for global input capture: {0}")]
+    PermissionDenied(&'static str),
+
+    /// Another program already registered or grabbed this global hotkey
+    /// (Windows `RegisterHotKey` backend, suppressed X11 hotkeys); callers
+    /// may skip it or abort startup
+    #[error("hotkey {0:?} is already registered by another application")]
+    HotkeyAlreadyOwned(Hotkey),
+}
+
+impl CaptureError {
+    /// Permission error with the fix for the current platform
+    pub fn permission_denied() -> Self {
+        #[cfg(target_os = "linux")]
+        let guidance =
+            "add your user to the `input` group (`sudo usermod -aG input $USER`) and log in again";
+        #[cfg(target_os = "macos")]
+        let guidance = "enable HandPlusPlus under System Settings → Privacy & Security → Accessibility and Input Monitoring";
+        #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+        let guidance = "run HandPlusPlus with permission to install global input hooks";
+        CaptureError::PermissionDenied(guidance)
+    }
+
+    /// Map an OS error from opening a device or hook, keeping permission
+    /// failures distinguishable from everything else
+    pub fn from_io(err: std::io::Error) -> anyhow::Error {
+        if err.kind() == std::io::ErrorKind::PermissionDenied {
+            CaptureError::permission_denied().into()
+        } else {
+            err.into()
+        }
+    }
+}
+
+/// How often `Overflow::Block` checks whether the slowest stream caught up
+const BLOCK_POLL: Duration = Duration::from_millis(1);
+
+/// When each key and button still down went down, by device and press
+type PressTimes = HashMap<(Option<DeviceId>, InputEvent), Instant>;
+
+/// Sending side of a backend's event channel, applying its overflow policy
+#[derive(Debug, Clone)]
+struct EventSink {
+    events: tokio::sync::broadcast::Sender<CapturedEvent>,
+    backlog: usize,
+    overflow: Overflow,
+    key_repeat: bool,
+    dropped: Arc<AtomicU64>,
+    // Checked before every send; shared with the capture that owns it
+    paused: Arc<AtomicBool>,
+    down: Arc<Mutex<PressTimes>>,
+}
+
+impl EventSink {
+    fn new(options: CaptureOptions) -> Self {
+        let backlog = options.backlog.max(1);
+        Self {
+            events: tokio::sync::broadcast::channel(backlog).0,
+            backlog,
+            overflow: options.overflow,
+            key_repeat: options.key_repeat,
+            dropped: Arc::default(),
+            paused: Arc::default(),
+            down: Arc::default(),
+        }
+    }
+
+    /// Stream over the channel; each call gets its own receiver
+    fn stream(&self) -> Box<dyn Stream<Item = CapturedEvent> + Send + Unpin> {
+        use tokio_stream::wrappers::{errors::BroadcastStreamRecvError, BroadcastStream};
+        use tokio_stream::StreamExt;
+
+        let dropped = self.dropped.clone();
+        Box::new(BroadcastStream::new(self.events.subscribe()).filter_map(
+            move |event| match event {
+                Ok(event) => Some(event),
+                // Only under DropOldest; lagging skips the gap for this
+                // receiver alone
+                Err(BroadcastStreamRecvError::Lagged(skipped)) => {
+                    dropped.fetch_add(skipped, Ordering::Relaxed);
+                    None
+                }
+            },
+        ))
+    }
+
+    fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, Ordering::Relaxed);
+    }
+
+    fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    /// Whether the slowest stream is a full backlog behind
+    fn is_full(&self) -> bool {
+        self.events.len() >= self.backlog
+    }
+
+    /// Fill in `held_for` on a release from its press, and drop repeated
+    /// presses unless `key_repeat` is set
+    ///
+    /// Repeats are presses of something already down, which covers every
+    /// platform alike; a lost release costs one press, as the next release
+    /// clears it. Tracked while paused too, so presses from before a pause
+    /// still time their release.
+    fn track(&self, mut captured: CapturedEvent) -> Option<CapturedEvent> {
+        let (press, pressed) = match captured.event {
+            InputEvent::KeyPress(key) => (InputEvent::KeyPress(key), true),
+            InputEvent::KeyRelease(key) => (InputEvent::KeyPress(key), false),
+            InputEvent::PhysicalKeyPress(key) => (InputEvent::PhysicalKeyPress(key), true),
+            InputEvent::PhysicalKeyRelease(key) => (InputEvent::PhysicalKeyPress(key), false),
+            InputEvent::MousePress(button) => (InputEvent::MousePress(button), true),
+            InputEvent::MouseRelease(button) => (InputEvent::MousePress(button), false),
+            _ => return Some(captured),
+        };
+        let press = (captured.device_id.clone(), press);
+        let mut down = self.down.lock().unwrap_or_else(PoisonError::into_inner);
+        if pressed {
+            // Auto-repeat keeps the time of the first press
+            let repeated = down.contains_key(&press);
+            down.entry(press).or_insert_with(Instant::now);
+            if repeated && !self.key_repeat {
+                return None;
+            }
+        } else {
+            captured.held_for = down.remove(&press).map(|at| at.elapsed());
+        }
+        Some(captured)
+    }
+
+    /// Send from a thread that may block
+    fn send(&self, event: CapturedEvent) {
+        let Some(event) = self.track(event) else {
+            return;
+        };
+        if self.overflow == Overflow::Block && !self.is_paused() {
+            while self.is_full() {
+                std::thread::sleep(BLOCK_POLL);
+            }
+        }
+        self.deliver(event);
+    }
+
+    /// Send from an async task, which waits instead of blocking its worker
+    #[cfg(target_os = "linux")]
+    async fn send_async(&self, event: CapturedEvent) {
+        let Some(event) = self.track(event) else {
+            return;
+        };
+        if self.overflow == Overflow::Block && !self.is_paused() {
+            while self.is_full() {
+                tokio::time::sleep(BLOCK_POLL).await;
+            }
+        }
+        self.deliver(event);
+    }
+
+    fn deliver(&self, event: CapturedEvent) {
+        // Discarded while paused, but not lost to overflow
+        if self.is_paused() {
+            return;
+        }
+        if self.overflow == Overflow::DropNewest && self.is_full() {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        // No subscribers yet is not an error
+        let _ = self.events.send(event);
+    }
+}
+
+/// Reject hotkeys a hook or grab can't consume without also swallowing
+/// input meant for other applications or devices
+#[cfg(any(windows, target_os = "linux"))]
+fn check_suppressible(hotkey: &Hotkey) -> Result<()> {
+    if !hotkey.held.is_empty() {
+        anyhow::bail!("hotkeys with held keys cannot be suppressed: {hotkey:?}");
+    }
+    if !matches!(
+        hotkey.trigger,
+        Trigger::Key(_) | Trigger::MouseButton(_) | Trigger::PhysicalKey(_)
+    ) {
+        anyhow::bail!("only single-press hotkeys can be suppressed: {hotkey:?}");
+    }
+    // The hooking backends don't know which device an event came from
+    if hotkey.device.is_some() {
+        anyhow::bail!("device-scoped hotkeys cannot be suppressed: {hotkey:?}");
+    }
+    Ok(())
 }
 
 /// Platform abstraction for global input capture
@@ -142,13 +896,88 @@ pub trait InputCapture: Send + Sync {
     /// Register a global hotkey
     fn register_hotkey(&mut self, hotkey: Hotkey) -> Result<()>;
 
-    /// Stream of input events
-    fn event_stream(&self) -> Box<dyn Stream<Item = InputEvent> + Send + Unpin>;
+    /// Register hotkeys in order, stopping at the first that fails
+    ///
+    /// The error names that hotkey and keeps the backend's error (such as
+    /// `CaptureError::HotkeyAlreadyOwned`) for downcasting. Hotkeys before
+    /// it stay registered and the rest are not attempted, so a caller can
+    /// retry from the failed one or give up with `stop`.
+    fn register_hotkeys(&mut self, hotkeys: &[Hotkey]) -> Result<()> {
+        for (index, hotkey) in hotkeys.iter().enumerate() {
+            self.register_hotkey(hotkey.clone()).map_err(|err| {
+                err.context(format!(
+                    "failed to register hotkey {hotkey} ({index} of {} registered)",
+                    hotkeys.len()
+                ))
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Register a hotkey whose events are consumed before they reach the
+    /// focused application; the event stream still reports them
+    fn register_hotkey_suppressed(&mut self, hotkey: Hotkey) -> Result<()> {
+        anyhow::bail!("this capture backend cannot suppress hotkeys ({hotkey:?})")
+    }
+
+    /// Consume every event of `key` so it loses its own function (such as
+    /// CapsLock toggling), counting it as `modifier` for suppressed hotkeys
+    /// while it is down; the event stream still reports it
+    fn remap_modifier(&mut self, key: Key, modifier: Modifier) -> Result<()> {
+        anyhow::bail!("this capture backend cannot remap {key:?} to {modifier:?}")
+    }
+
+    /// Hotkeys registered so far, suppressed or not, in registration order
+    fn registered_hotkeys(&self) -> Vec<Hotkey>;
+
+    /// Stream of input events, each with the modifiers held when it fired
+    fn event_stream(&self) -> Box<dyn Stream<Item = CapturedEvent> + Send + Unpin>;
+
+    /// Events lost so far to `CaptureOptions::overflow`, across all streams
+    fn dropped_events(&self) -> u64;
+
+    /// Stop delivering events and suppressing hotkeys, keeping the hooks
+    /// and registrations in place; events while paused are discarded
+    fn pause(&mut self) -> Result<()>;
+
+    /// Undo `pause`
+    fn resume(&mut self) -> Result<()>;
 
     /// Stop capturing input
     fn stop(&mut self) -> Result<()>;
 }
 
+/// Filters for an `event_stream`, so a consumer can drop uninteresting
+/// events before doing any work on them
+///
+/// They wrap the stream without boxing; only `coalesce_moves` holds an
+/// event back.
+pub trait CaptureStreamExt: Stream<Item = CapturedEvent> + Send + Unpin + Sized {
+    /// Only pass events whose input event `keep` accepts (e.g. key presses)
+    fn filtered<F>(self, keep: F) -> impl Stream<Item = CapturedEvent> + Send + Unpin
+    where
+        F: Fn(&InputEvent) -> bool + Send,
+    {
+        tokio_stream::StreamExt::filter(self, move |captured: &CapturedEvent| keep(&captured.event))
+    }
+
+    /// Only pass events known to come from `device`
+    fn only_device(self, device: DeviceId) -> impl Stream<Item = CapturedEvent> + Send + Unpin {
+        tokio_stream::StreamExt::filter(self, move |captured: &CapturedEvent| {
+            captured.device_id.as_ref() == Some(&device)
+        })
+    }
+
+    /// Collapse each run of `MouseMove` events into its last, passed on
+    /// `window` after the run's first move; any other event ends the run
+    /// early and follows the move. Must be polled inside a tokio runtime.
+    fn coalesce_moves(self, window: Duration) -> impl Stream<Item = CapturedEvent> + Send + Unpin {
+        coalesce::CoalescedMoves::new(self, window)
+    }
+}
+
+impl<S: Stream<Item = CapturedEvent> + Send + Unpin> CaptureStreamExt for S {}
+
 // Platform-specific implementations
 #[cfg(windows)]
 pub mod platform {
@@ -157,27 +986,462 @@ pub mod platform {
 
 #[cfg(target_os = "linux")]
 pub mod platform {
+    pub use super::evdev_capture::EvdevCapture;
     pub use super::linux_impl::X11Capture as PlatformCapture;
 }
 
-// Stub implementations (to be completed)
+#[cfg(target_os = "macos")]
+pub mod platform {
+    pub use super::macos_impl::MacCapture as PlatformCapture;
+}
+
 #[cfg(windows)]
 mod windows_impl {
     use super::*;
+    use crate::suppress::Suppressor;
+    use anyhow::{anyhow, Context};
+    use std::cell::{Cell, RefCell};
+    use std::sync::{mpsc, Mutex, PoisonError};
+    use std::thread::JoinHandle;
+    use tracing::warn;
+    use windows::Win32::Foundation::{HWND, LPARAM, LRESULT, WPARAM};
+    use windows::Win32::System::LibraryLoader::GetModuleHandleW;
+    use windows::Win32::System::Threading::GetCurrentThreadId;
+    use windows::Win32::UI::Accessibility::{SetWinEventHook, UnhookWinEvent, HWINEVENTHOOK};
+    use windows::Win32::UI::Input::KeyboardAndMouse::{
+        GetAsyncKeyState, VIRTUAL_KEY, VK_LCONTROL, VK_LMENU, VK_LSHIFT, VK_LWIN, VK_PAUSE,
+        VK_RCONTROL, VK_RMENU, VK_RSHIFT, VK_RWIN,
+    };
+    use windows::Win32::UI::WindowsAndMessaging::{
+        CallNextHookEx, GetMessageW, PeekMessageW, PostThreadMessageW, SetWindowsHookExW,
+        UnhookWindowsHookEx, EVENT_SYSTEM_FOREGROUND, HHOOK, KBDLLHOOKSTRUCT, LLKHF_EXTENDED, MSG,
+        MSLLHOOKSTRUCT, PM_NOREMOVE, WHEEL_DELTA, WH_KEYBOARD_LL, WH_MOUSE_LL,
+        WINEVENT_OUTOFCONTEXT, WM_KEYDOWN, WM_LBUTTONDOWN, WM_LBUTTONUP, WM_MBUTTONDOWN,
+        WM_MBUTTONUP, WM_MOUSEHWHEEL, WM_MOUSEMOVE, WM_MOUSEWHEEL, WM_QUIT, WM_RBUTTONDOWN,
+        WM_RBUTTONUP, WM_SYSKEYDOWN, WM_USER, WM_XBUTTONDOWN, WM_XBUTTONUP,
+    };
+
+    // High word of `mouseData` for WM_XBUTTONDOWN/UP
+    const XBUTTON1: u32 = 0x0001;
+
+    thread_local! {
+        // Hook procedures get no user data; they run on the thread that
+        // installed them, so that thread parks what they need here
+        static SINK: RefCell<Option<Sink>> = const { RefCell::new(None) };
+
+        // High-resolution wheels report fractions of a detent, carried
+        // over until they add up to a whole one
+        static WHEEL_REMAINDER: Cell<(i32, i32)> = const { Cell::new((0, 0)) };
+    }
 
-    pub struct WindowsCapture;
+    /// What the hook procedures need from the capture
+    struct Sink {
+        events: EventSink,
+        options: CaptureOptions,
+        suppressor: Arc<Mutex<Suppressor>>,
+    }
+
+    /// Low-level keyboard and mouse hooks on a dedicated message-loop thread
+    pub struct WindowsCapture {
+        options: CaptureOptions,
+        hotkeys: Vec<Hotkey>,
+        events: EventSink,
+        // Shared with the hook thread, which locks it for every event
+        suppressor: Arc<Mutex<Suppressor>>,
+        // Hook thread id (for posting WM_QUIT) and handle; None once stopped
+        hook_thread: Option<(u32, JoinHandle<()>)>,
+    }
+
+    impl WindowsCapture {
+        /// `WH_MOUSE_LL` is still needed for buttons, so moves are dropped
+        /// in the hook callback before they reach the channel
+        pub fn new(options: CaptureOptions) -> Result<Self> {
+            let events = EventSink::new(options);
+            let suppressor = Arc::<Mutex<Suppressor>>::default();
+            let sink = Sink {
+                events: events.clone(),
+                options,
+                suppressor: suppressor.clone(),
+            };
+            let (ready_tx, ready_rx) = mpsc::channel();
+            let handle = std::thread::Builder::new()
+                .name("input-hook".into())
+                .spawn(move || hook_thread(sink, ready_tx))?;
+
+            match ready_rx.recv() {
+                Ok(Ok(thread_id)) => Ok(Self {
+                    options,
+                    hotkeys: Vec::new(),
+                    events,
+                    suppressor,
+                    hook_thread: Some((thread_id, handle)),
+                }),
+                Ok(Err(err)) => {
+                    let _ = handle.join();
+                    Err(err)
+                }
+                Err(_) => Err(anyhow!("input hook thread exited during setup")),
+            }
+        }
+
+        pub fn options(&self) -> CaptureOptions {
+            self.options
+        }
+    }
 
     impl InputCapture for WindowsCapture {
-        fn register_hotkey(&mut self, _hotkey: Hotkey) -> Result<()> {
-            todo!("Implement using SetWindowsHookEx")
+        /// Low-level hooks see every event, so hotkeys are only recorded
+        /// here; matching happens downstream on the event stream
+        fn register_hotkey(&mut self, hotkey: Hotkey) -> Result<()> {
+            if !self.hotkeys.contains(&hotkey) {
+                self.hotkeys.push(hotkey);
+            }
+            Ok(())
+        }
+
+        /// The hook returns nonzero for the trigger's press, repeats and
+        /// release, which keeps them from every window
+        fn register_hotkey_suppressed(&mut self, hotkey: Hotkey) -> Result<()> {
+            self.suppressor
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .add(hotkey.clone())?;
+            self.register_hotkey(hotkey)
+        }
+
+        fn remap_modifier(&mut self, key: Key, modifier: Modifier) -> Result<()> {
+            self.suppressor
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .add_modifier_remap(key, modifier);
+            Ok(())
+        }
+
+        fn registered_hotkeys(&self) -> Vec<Hotkey> {
+            self.hotkeys.clone()
         }
 
-        fn event_stream(&self) -> Box<dyn Stream<Item = InputEvent> + Send + Unpin> {
-            todo!("Implement event stream from Windows hooks")
+        fn event_stream(&self) -> Box<dyn Stream<Item = CapturedEvent> + Send + Unpin> {
+            self.events.stream()
+        }
+
+        fn dropped_events(&self) -> u64 {
+            self.events.dropped()
+        }
+
+        /// The hooks stay installed and pass everything on untouched
+        fn pause(&mut self) -> Result<()> {
+            self.events.set_paused(true);
+            // Releases are no longer seen, so forget consumed presses
+            self.suppressor
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .release_all();
+            Ok(())
+        }
+
+        fn resume(&mut self) -> Result<()> {
+            self.events.set_paused(false);
+            Ok(())
         }
 
         fn stop(&mut self) -> Result<()> {
-            todo!("Unhook Windows hooks")
+            let Some((thread_id, handle)) = self.hook_thread.take() else {
+                return Ok(());
+            };
+            unsafe { PostThreadMessageW(thread_id, WM_QUIT, WPARAM(0), LPARAM(0)) }
+                .context("failed to signal the input hook thread")?;
+            handle
+                .join()
+                .map_err(|_| anyhow!("input hook thread panicked"))
+        }
+    }
+
+    impl Drop for WindowsCapture {
+        fn drop(&mut self) {
+            if let Err(err) = self.stop() {
+                warn!("stopping input capture: {err:#}");
+            }
+        }
+    }
+
+    /// Install both hooks and pump messages until `WM_QUIT`; low-level
+    /// hooks are only called while their thread is in a message loop
+    fn hook_thread(sink: Sink, ready: mpsc::Sender<Result<u32>>) {
+        SINK.with(|slot| *slot.borrow_mut() = Some(sink));
+
+        let mut msg = MSG::default();
+        // Create the message queue before reporting our id, so a stop()
+        // racing with startup can't post WM_QUIT into the void
+        unsafe {
+            let _ = PeekMessageW(&mut msg, None, WM_USER, WM_USER, PM_NOREMOVE);
+        }
+        let hooks = match install_hooks() {
+            Ok(hooks) => hooks,
+            Err(err) => {
+                let _ = ready.send(Err(err));
+                return;
+            }
+        };
+        // Out-of-context win events are delivered through this thread's
+        // message loop, like the hooks; capture works without them
+        let foreground = unsafe {
+            SetWinEventHook(
+                EVENT_SYSTEM_FOREGROUND,
+                EVENT_SYSTEM_FOREGROUND,
+                None,
+                Some(foreground_proc),
+                0,
+                0,
+                WINEVENT_OUTOFCONTEXT,
+            )
+        };
+        if foreground.is_invalid() {
+            warn!("SetWinEventHook failed; focus changes will not be reported");
+        }
+        let _ = ready.send(Ok(unsafe { GetCurrentThreadId() }));
+
+        // 0 is WM_QUIT and -1 an error; both end the loop
+        while unsafe { GetMessageW(&mut msg, None, 0, 0) }.0 > 0 {}
+
+        if !foreground.is_invalid() && !unsafe { UnhookWinEvent(foreground) }.as_bool() {
+            warn!("UnhookWinEvent failed");
+        }
+        for hook in hooks {
+            if let Err(err) = unsafe { UnhookWindowsHookEx(hook) } {
+                warn!("UnhookWindowsHookEx failed: {err}");
+            }
+        }
+        SINK.with(|sink| sink.borrow_mut().take());
+    }
+
+    fn install_hooks() -> Result<[HHOOK; 2]> {
+        let module = unsafe { GetModuleHandleW(None) }?;
+        let keyboard = unsafe {
+            SetWindowsHookExW(WH_KEYBOARD_LL, Some(keyboard_proc), Some(module.into()), 0)
+        }
+        .context("failed to install the keyboard hook")?;
+        let mouse =
+            unsafe { SetWindowsHookExW(WH_MOUSE_LL, Some(mouse_proc), Some(module.into()), 0) };
+        match mouse {
+            Ok(mouse) => Ok([keyboard, mouse]),
+            Err(err) => {
+                let _ = unsafe { UnhookWindowsHookEx(keyboard) };
+                Err(err).context("failed to install the mouse hook")
+            }
+        }
+    }
+
+    /// A window came to the foreground
+    unsafe extern "system" fn foreground_proc(
+        _hook: HWINEVENTHOOK,
+        _event: u32,
+        _hwnd: HWND,
+        _object: i32,
+        _child: i32,
+        _thread: u32,
+        _time: u32,
+    ) {
+        // Win events can't be consumed, so the verdict is unused
+        forward([InputEvent::FocusChanged]);
+    }
+
+    /// Forward the events decoded from one hooked OS event, if the hook
+    /// thread is running; true when that OS event should be consumed
+    fn forward(events: impl IntoIterator<Item = InputEvent>) -> bool {
+        SINK.with(|sink| {
+            let Some(sink) = &*sink.borrow() else {
+                return false;
+            };
+            let modifiers = held_modifiers();
+            let events: Vec<_> = events.into_iter().collect();
+            // Paused, nothing is consumed, but events still go to the sink
+            // so it tracks what is held
+            let suppress = !sink.events.is_paused()
+                && sink
+                    .suppressor
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .should_suppress(&events, modifiers);
+            for event in events
+                .into_iter()
+                .filter(|event| sink.options.accepts(event))
+            {
+                sink.events.send(CapturedEvent {
+                    event,
+                    modifiers,
+                    device_id: None,
+                    held_for: None,
+                });
+            }
+            suppress
+        })
+    }
+
+    /// Modifiers down before the event being hooked, which the async key
+    /// state does not include yet
+    fn held_modifiers() -> ModifierSet {
+        // The high bit of GetAsyncKeyState is set while the key is down
+        let down = |vk: VIRTUAL_KEY| unsafe { GetAsyncKeyState(i32::from(vk.0)) } < 0;
+        let mut modifiers = ModifierSet::empty();
+        for (vk, modifier) in [
+            (VK_LCONTROL, Modifier::CtrlLeft),
+            (VK_RCONTROL, Modifier::CtrlRight),
+            (VK_LSHIFT, Modifier::ShiftLeft),
+            (VK_RSHIFT, Modifier::ShiftRight),
+            (VK_LMENU, Modifier::AltLeft),
+            (VK_RMENU, Modifier::AltRight),
+            (VK_LWIN, Modifier::MetaLeft),
+            (VK_RWIN, Modifier::MetaRight),
+        ] {
+            if down(vk) {
+                modifiers |= modifier.into();
+            }
+        }
+        modifiers
+    }
+
+    unsafe extern "system" fn keyboard_proc(code: i32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+        // Negative codes must be passed on untouched
+        if code >= 0 {
+            let info = &*(lparam.0 as *const KBDLLHOOKSTRUCT);
+            if info.dwExtraInfo == INJECTED_EXTRA_INFO {
+                dropped_injected(info.vkCode);
+                return CallNextHookEx(None, code, wparam, lparam);
+            }
+            let pressed = matches!(wparam.0 as u32, WM_KEYDOWN | WM_SYSKEYDOWN);
+            let extended = info.flags.contains(LLKHF_EXTENDED);
+            let key = key_from_scan(info.vkCode, info.scanCode, extended);
+            // Extended scan codes are not evdev codes, so only known keys
+            // get a position for them
+            let physical = match key {
+                Some(key) => Some(key.physical()),
+                None if !extended => Some(PhysicalKey(info.scanCode as u16)),
+                None => None,
+            };
+
+            let (logical, physical) = if pressed {
+                (
+                    key.map(InputEvent::KeyPress),
+                    physical.map(InputEvent::PhysicalKeyPress),
+                )
+            } else {
+                (
+                    key.map(InputEvent::KeyRelease),
+                    physical.map(InputEvent::PhysicalKeyRelease),
+                )
+            };
+            if forward(logical.into_iter().chain(physical)) {
+                // Nonzero keeps the event from later hooks and every window
+                return LRESULT(1);
+            }
+        }
+        CallNextHookEx(None, code, wparam, lparam)
+    }
+
+    unsafe extern "system" fn mouse_proc(code: i32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+        if code >= 0 {
+            let info = &*(lparam.0 as *const MSLLHOOKSTRUCT);
+            if info.dwExtraInfo == INJECTED_EXTRA_INFO {
+                dropped_injected(wparam.0 as u32);
+                return CallNextHookEx(None, code, wparam, lparam);
+            }
+            let xbutton = || match info.mouseData >> 16 {
+                XBUTTON1 => MouseButton::Button4,
+                _ => MouseButton::Button5,
+            };
+            let event = match wparam.0 as u32 {
+                WM_MOUSEMOVE => Some(InputEvent::MouseMove {
+                    x: info.pt.x,
+                    y: info.pt.y,
+                }),
+                WM_LBUTTONDOWN => Some(InputEvent::MousePress(MouseButton::Left)),
+                WM_LBUTTONUP => Some(InputEvent::MouseRelease(MouseButton::Left)),
+                WM_RBUTTONDOWN => Some(InputEvent::MousePress(MouseButton::Right)),
+                WM_RBUTTONUP => Some(InputEvent::MouseRelease(MouseButton::Right)),
+                WM_MBUTTONDOWN => Some(InputEvent::MousePress(MouseButton::Middle)),
+                WM_MBUTTONUP => Some(InputEvent::MouseRelease(MouseButton::Middle)),
+                WM_XBUTTONDOWN => Some(InputEvent::MousePress(xbutton())),
+                WM_XBUTTONUP => Some(InputEvent::MouseRelease(xbutton())),
+                // The delta is signed and positive away from the user
+                // (up) or to the right
+                WM_MOUSEWHEEL => wheel(0, (info.mouseData >> 16) as i16 as i32),
+                WM_MOUSEHWHEEL => wheel((info.mouseData >> 16) as i16 as i32, 0),
+                _ => None,
+            };
+            if forward(event) {
+                return LRESULT(1);
+            }
+        }
+        CallNextHookEx(None, code, wparam, lparam)
+    }
+
+    /// Our own output comes back through the hooks; it is passed on to
+    /// windows but never captured, so bindings can't trigger themselves
+    fn dropped_injected(code: u32) {
+        // Pairs with the captured and emitted events on the same target
+        tracing::trace!(target: "timeline", direction = "dropped injected", code);
+    }
+
+    /// Whole detents in a wheel delta plus any carried-over fraction
+    fn wheel(dx: i32, dy: i32) -> Option<InputEvent> {
+        let detent = WHEEL_DELTA as i32;
+        let (dx, dy) = WHEEL_REMAINDER.with(|remainder| {
+            let (rx, ry) = remainder.get();
+            let (dx, dy) = (rx + dx, ry + dy);
+            remainder.set((dx % detent, dy % detent));
+            (dx / detent, dy / detent)
+        });
+        (dx != 0 || dy != 0).then_some(InputEvent::MouseWheel { dx, dy })
+    }
+
+    /// Key for a hook's scan code
+    fn key_from_scan(vk: u32, scan: u32, extended: bool) -> Option<Key> {
+        // Pause arrives as a bare 0x45, the scan code of NumLock's position
+        if vk == u32::from(VK_PAUSE.0) {
+            return Some(Key::Pause);
+        }
+        let scan = scan as u16;
+        Key::from_set1_scan_code(ScanCode(if extended { 0xE000 | scan } else { scan }))
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn extended_scan_codes_give_the_right_side() {
+            let sides = [
+                (0x1D, Key::Ctrl, Key::CtrlRight),
+                (0x38, Key::Alt, Key::AltGr),
+            ];
+            for (scan, left, right) in sides {
+                assert_eq!(key_from_scan(0, scan, false), Some(left));
+                assert_eq!(key_from_scan(0, scan, true), Some(right));
+            }
+            // Shift sides have scan codes of their own
+            assert_eq!(key_from_scan(0, 0x2A, false), Some(Key::Shift));
+            assert_eq!(key_from_scan(0, 0x36, false), Some(Key::ShiftRight));
+        }
+
+        #[test]
+        fn wheel_deltas_become_whole_detents() {
+            let detent = WHEEL_DELTA as i32;
+            assert_eq!(
+                wheel(0, detent),
+                Some(InputEvent::MouseWheel { dx: 0, dy: 1 })
+            );
+            assert_eq!(
+                wheel(-2 * detent, 0),
+                Some(InputEvent::MouseWheel { dx: -2, dy: 0 })
+            );
+            // Precision touchpads send fractions; they add up to a detent
+            assert_eq!(wheel(0, detent / 2), None);
+            assert_eq!(
+                wheel(0, detent / 2),
+                Some(InputEvent::MouseWheel { dx: 0, dy: 1 })
+            );
+            assert_eq!(wheel(0, 0), None);
         }
     }
 }
@@ -185,20 +1449,1481 @@ mod windows_impl {
 #[cfg(target_os = "linux")]
 mod linux_impl {
     use super::*;
+    use anyhow::{anyhow, bail, Context};
+    use std::collections::{HashMap, VecDeque};
+    use std::thread::JoinHandle;
+    use tracing::warn;
+    use x11rb::connection::{Connection, RequestConnection};
+    use x11rb::errors::ReplyError;
+    use x11rb::protocol::record::{self, ConnectionExt as _};
+    use x11rb::protocol::xproto::{
+        AtomEnum, ButtonIndex, ButtonPressEvent, ChangeWindowAttributesAux, ClientMessageEvent,
+        ConnectionExt as _, CreateWindowAux, EventMask, Grab, GrabMode, KeyButMask, KeyPressEvent,
+        ModMask, MotionNotifyEvent, Window, WindowClass, BUTTON_PRESS_EVENT, BUTTON_RELEASE_EVENT,
+        KEY_PRESS_EVENT, KEY_RELEASE_EVENT, MOTION_NOTIFY_EVENT,
+    };
+    use x11rb::protocol::xtest;
+    use x11rb::protocol::ErrorKind;
+    use x11rb::protocol::Event;
+    use x11rb::rust_connection::RustConnection;
+    use x11rb::wrapper::ConnectionExt as _;
+    use x11rb::x11_utils::TryParse;
+
+    // Reply categories of RecordEnableContext (missing from the XML)
+    const RECORD_FROM_SERVER: u8 = 0;
+    const RECORD_FROM_CLIENT: u8 = 1;
+    const RECORD_END_OF_DATA: u8 = 5;
 
-    pub struct X11Capture;
+    // Recorded device events are bare 32-byte wire events
+    const EVENT_SIZE: usize = 32;
+
+    /// XRecord-based capture; XRecord only observes, so nothing is taken
+    /// away from other clients except suppressed hotkeys, which are grabbed
+    ///
+    /// Like `X11Executor`, keys resolve through the server's keymap (by
+    /// keysym), read at construction.
+    pub struct X11Capture {
+        options: CaptureOptions,
+        hotkeys: Vec<Hotkey>,
+        // Grabbed on the root window, except while paused
+        suppressed: Vec<Hotkey>,
+        remapped: Vec<Key>,
+        events: EventSink,
+        // Record contexts are controlled from one connection and read from
+        // another, which the record thread owns; it also drains the events
+        // that grabs deliver to this one
+        ctrl: Arc<RustConnection>,
+        root: Window,
+        context: record::Context,
+        keys: HashMap<u8, Key>,
+        record_thread: Option<JoinHandle<()>>,
+        focus: Option<FocusWatcher>,
+    }
+
+    impl X11Capture {
+        /// Motion events are left out of the record range when disabled
+        pub fn new(options: CaptureOptions) -> Result<Self> {
+            let (ctrl, screen) = x11rb::connect(None).context("cannot connect to the X server")?;
+            let (data, _) = x11rb::connect(None).context("cannot connect to the X server")?;
+            if ctrl
+                .extension_information(record::X11_EXTENSION_NAME)?
+                .is_none()
+            {
+                bail!("the X server does not support the RECORD extension");
+            }
+            ctrl.record_query_version(1, 13)?.reply()?;
+            let keys = key_map(&ctrl)?;
+            let root = ctrl.setup().roots[screen].root;
+
+            let last = if options.mouse_move {
+                MOTION_NOTIFY_EVENT
+            } else {
+                BUTTON_RELEASE_EVENT
+            };
+            let mut range = record::Range {
+                device_events: record::Range8 {
+                    first: KEY_PRESS_EVENT,
+                    last,
+                },
+                ..Default::default()
+            };
+            // XTest input (ours included) is recorded like real input; the
+            // FakeInput requests behind it are recorded to tell them apart
+            let xtest_opcode = ctrl
+                .extension_information(xtest::X11_EXTENSION_NAME)?
+                .map(|xtest| xtest.major_opcode);
+            if let Some(opcode) = xtest_opcode {
+                range.ext_requests = record::ExtRange {
+                    major: record::Range8 {
+                        first: opcode,
+                        last: opcode,
+                    },
+                    minor: record::Range16 {
+                        first: xtest::FAKE_INPUT_REQUEST.into(),
+                        last: xtest::FAKE_INPUT_REQUEST.into(),
+                    },
+                };
+            }
+            let context = ctrl.generate_id()?;
+            ctrl.record_create_context(context, 0, &[record::CS::ALL_CLIENTS.into()], &[range])?
+                .check()
+                .context("failed to create the record context")?;
+
+            let ctrl = Arc::new(ctrl);
+            let events = EventSink::new(options);
+            let sender = events.clone();
+            let thread_keys = keys.clone();
+            let thread_ctrl = ctrl.clone();
+            let record_thread = std::thread::Builder::new()
+                .name("input-record".into())
+                .spawn(move || {
+                    let fakes = FakeInputs::new(xtest_opcode);
+                    let recorded =
+                        record_events(&data, &thread_ctrl, context, &thread_keys, &sender, fakes);
+                    if let Err(err) = recorded {
+                        warn!("input recording stopped: {err:#}");
+                    }
+                })?;
+            // Capture works without focus events
+            let focus = FocusWatcher::start(root, events.clone())
+                .inspect_err(|err| warn!("focus changes will not be reported: {err:#}"))
+                .ok();
+
+            Ok(Self {
+                options,
+                hotkeys: Vec::new(),
+                suppressed: Vec::new(),
+                remapped: Vec::new(),
+                events,
+                ctrl,
+                root,
+                context,
+                keys,
+                record_thread: Some(record_thread),
+                focus,
+            })
+        }
+
+        pub fn options(&self) -> CaptureOptions {
+            self.options
+        }
+
+        fn ungrab_all(&self) -> Result<()> {
+            self.ctrl.ungrab_key(Grab::ANY, self.root, ModMask::ANY)?;
+            self.ctrl
+                .ungrab_button(ButtonIndex::ANY, self.root, ModMask::ANY)?;
+            Ok(())
+        }
+
+        /// Grab a suppressed hotkey's trigger with its exact modifiers
+        fn grab(&self, hotkey: &Hotkey) -> Result<()> {
+            let modifiers = hotkey
+                .modifiers
+                .iter()
+                .fold(ModMask::from(0u16), |mask, &modifier| {
+                    mask | mod_mask(modifier)
+                });
+            let keycodes: Vec<u8> = match hotkey.trigger {
+                Trigger::Key(key) => self.keycodes(key),
+                // Range checked by register_hotkey
+                Trigger::PhysicalKey(code) => x11_keycode(code).into_iter().collect(),
+                _ => Vec::new(),
+            };
+
+            // Grabs need the exact modifier state, so also grab with
+            // CapsLock and NumLock (Mod2) on
+            for locks in [
+                ModMask::from(0u16),
+                ModMask::LOCK,
+                ModMask::M2,
+                ModMask::LOCK | ModMask::M2,
+            ] {
+                let modifiers = modifiers | locks;
+                let mut cookies = Vec::new();
+                if let Trigger::MouseButton(button) = hotkey.trigger {
+                    cookies.push(self.ctrl.grab_button(
+                        false,
+                        self.root,
+                        EventMask::BUTTON_PRESS | EventMask::BUTTON_RELEASE,
+                        GrabMode::ASYNC,
+                        GrabMode::ASYNC,
+                        x11rb::NONE,
+                        x11rb::NONE,
+                        ButtonIndex::from(button_number(button)),
+                        modifiers,
+                    )?);
+                }
+                for &keycode in &keycodes {
+                    cookies.push(self.ctrl.grab_key(
+                        false,
+                        self.root,
+                        modifiers,
+                        keycode,
+                        GrabMode::ASYNC,
+                        GrabMode::ASYNC,
+                    )?);
+                }
+                for cookie in cookies {
+                    cookie.check().map_err(|err| match err {
+                        ReplyError::X11Error(ref x11) if x11.error_kind == ErrorKind::Access => {
+                            CaptureError::HotkeyAlreadyOwned(hotkey.clone()).into()
+                        }
+                        err => anyhow::Error::from(err),
+                    })?;
+                }
+            }
+            Ok(())
+        }
+
+        /// Grab a remapped key under every modifier state
+        fn grab_any(&self, key: Key) -> Result<()> {
+            let keycodes = self.keycodes(key);
+            if keycodes.is_empty() {
+                bail!("no key on the X keyboard mapping produces {key:?}");
+            }
+            for keycode in keycodes {
+                self.ctrl
+                    .grab_key(
+                        false,
+                        self.root,
+                        ModMask::ANY,
+                        keycode,
+                        GrabMode::ASYNC,
+                        GrabMode::ASYNC,
+                    )?
+                    .check()
+                    .map_err(|err| match err {
+                        ReplyError::X11Error(ref x11) if x11.error_kind == ErrorKind::Access => {
+                            CaptureError::HotkeyAlreadyOwned(Hotkey::key(key)).into()
+                        }
+                        err => anyhow::Error::from(err),
+                    })?;
+            }
+            Ok(())
+        }
+
+        fn keycodes(&self, key: Key) -> Vec<u8> {
+            self.keys
+                .iter()
+                .filter(|&(_, &mapped)| mapped == key)
+                .map(|(&keycode, _)| keycode)
+                .collect()
+        }
+    }
 
     impl InputCapture for X11Capture {
-        fn register_hotkey(&mut self, _hotkey: Hotkey) -> Result<()> {
-            todo!("Implement using X11 XInput or XRecord")
+        /// Fails for keys nothing on the current keymap produces, since
+        /// such a hotkey could never fire
+        fn register_hotkey(&mut self, hotkey: Hotkey) -> Result<()> {
+            let trigger = match hotkey.trigger {
+                Trigger::Key(key) => Some(key),
+                _ => None,
+            };
+            let keys = hotkey.modifiers.iter().map(|modifier| modifier.key());
+            for key in keys.chain(trigger).chain(hotkey.held.iter().copied()) {
+                if !self.keys.values().any(|&mapped| mapped == key) {
+                    bail!("no key on the X keyboard mapping produces {key:?}");
+                }
+            }
+            if let Trigger::PhysicalKey(code) = hotkey.trigger {
+                x11_keycode(code).with_context(|| format!("{code:?} has no X keycode"))?;
+            }
+            if !self.hotkeys.contains(&hotkey) {
+                self.hotkeys.push(hotkey);
+            }
+            Ok(())
+        }
+
+        /// Grabs the trigger on the root window, so the server delivers it
+        /// to this client instead of the focused one; XRecord still sees it
+        fn register_hotkey_suppressed(&mut self, hotkey: Hotkey) -> Result<()> {
+            check_suppressible(&hotkey)?;
+            // Grabs match the modifier state, which has no sides
+            if hotkey.modifiers.iter().any(|modifier| modifier.is_sided()) {
+                bail!("hotkeys with sided modifiers cannot be grabbed on X11: {hotkey:?}");
+            }
+            let known = self.hotkeys.contains(&hotkey);
+            self.register_hotkey(hotkey.clone())?;
+            if !self.events.is_paused() {
+                if let Err(err) = self.grab(&hotkey) {
+                    // Left registered, it would fire without being suppressed
+                    if !known {
+                        self.hotkeys.retain(|registered| *registered != hotkey);
+                    }
+                    return Err(err);
+                }
+            }
+            if !self.suppressed.contains(&hotkey) {
+                self.suppressed.push(hotkey);
+            }
+            Ok(())
+        }
+
+        /// Grabs the key under any modifiers. While it is down the server
+        /// sends every key to this client, so combos with it never reach
+        /// the focused window either. The server still applies the key's
+        /// own keymap action, so a remapped CapsLock still toggles the lock.
+        fn remap_modifier(&mut self, key: Key, _modifier: Modifier) -> Result<()> {
+            if !self.events.is_paused() {
+                self.grab_any(key)?;
+            }
+            if !self.remapped.contains(&key) {
+                self.remapped.push(key);
+            }
+            Ok(())
+        }
+
+        fn registered_hotkeys(&self) -> Vec<Hotkey> {
+            self.hotkeys.clone()
+        }
+
+        fn event_stream(&self) -> Box<dyn Stream<Item = CapturedEvent> + Send + Unpin> {
+            self.events.stream()
+        }
+
+        fn dropped_events(&self) -> u64 {
+            self.events.dropped()
+        }
+
+        /// Suppressed hotkeys are ungrabbed, so they reach the focused
+        /// window again
+        fn pause(&mut self) -> Result<()> {
+            self.events.set_paused(true);
+            self.ungrab_all()?;
+            self.ctrl.flush()?;
+            Ok(())
+        }
+
+        /// Fails if another client grabbed a suppressed hotkey meanwhile;
+        /// events are delivered again either way
+        fn resume(&mut self) -> Result<()> {
+            self.events.set_paused(false);
+            for hotkey in &self.suppressed {
+                self.grab(hotkey)?;
+            }
+            for &key in &self.remapped {
+                self.grab_any(key)?;
+            }
+            Ok(())
+        }
+
+        fn stop(&mut self) -> Result<()> {
+            let Some(record_thread) = self.record_thread.take() else {
+                return Ok(());
+            };
+            if let Some(focus) = self.focus.take() {
+                focus.stop()?;
+            }
+            self.ungrab_all()?;
+            // Disabling ends the enable reply stream on the data connection
+            self.ctrl.record_disable_context(self.context)?;
+            self.ctrl.sync()?;
+            record_thread
+                .join()
+                .map_err(|_| anyhow!("input record thread panicked"))?;
+            self.ctrl.record_free_context(self.context)?;
+            self.ctrl.flush()?;
+            Ok(())
+        }
+    }
+
+    impl Drop for X11Capture {
+        fn drop(&mut self) {
+            if let Err(err) = self.stop() {
+                warn!("stopping input capture: {err:#}");
+            }
+        }
+    }
+
+    /// Reports focus changes from the root window's `_NET_ACTIVE_WINDOW`,
+    /// which EWMH window managers keep up to date; without one, none are
+    /// reported
+    struct FocusWatcher {
+        conn: Arc<RustConnection>,
+        // Unmapped window whose ClientMessage ends the thread
+        wake: Window,
+        thread: JoinHandle<()>,
+    }
+
+    impl FocusWatcher {
+        fn start(root: Window, events: EventSink) -> Result<Self> {
+            let (conn, _) = x11rb::connect(None).context("cannot connect to the X server")?;
+            let active = conn
+                .intern_atom(false, b"_NET_ACTIVE_WINDOW")?
+                .reply()?
+                .atom;
+            // Other clients may select on the root too; each gets its own
+            // copy of the event
+            let attributes =
+                ChangeWindowAttributesAux::new().event_mask(EventMask::PROPERTY_CHANGE);
+            conn.change_window_attributes(root, &attributes)?
+                .check()
+                .context("cannot watch the root window's properties")?;
+            let wake = conn.generate_id()?;
+            conn.create_window(
+                0,
+                wake,
+                root,
+                0,
+                0,
+                1,
+                1,
+                0,
+                WindowClass::INPUT_ONLY,
+                x11rb::COPY_FROM_PARENT,
+                &CreateWindowAux::new(),
+            )?
+            .check()?;
+
+            let conn = Arc::new(conn);
+            let thread_conn = conn.clone();
+            let thread = std::thread::Builder::new()
+                .name("input-focus".into())
+                .spawn(move || {
+                    if let Err(err) = watch_focus(&thread_conn, root, active, wake, &events) {
+                        warn!("focus watching stopped: {err:#}");
+                    }
+                })?;
+            Ok(Self { conn, wake, thread })
+        }
+
+        fn stop(self) -> Result<()> {
+            // Sent events with an empty mask go to the window's creator
+            let message = ClientMessageEvent::new(32, self.wake, AtomEnum::NONE, [0u32; 5]);
+            self.conn
+                .send_event(false, self.wake, EventMask::NO_EVENT, message)?;
+            self.conn.flush()?;
+            self.thread
+                .join()
+                .map_err(|_| anyhow!("focus watching thread panicked"))?;
+            self.conn.destroy_window(self.wake)?;
+            self.conn.flush()?;
+            Ok(())
+        }
+    }
+
+    fn watch_focus(
+        conn: &RustConnection,
+        root: Window,
+        active: u32,
+        wake: Window,
+        events: &EventSink,
+    ) -> Result<()> {
+        let active_window = || -> Result<Option<u32>> {
+            let reply = conn
+                .get_property(false, root, active, AtomEnum::WINDOW, 0, 1)?
+                .reply()?;
+            Ok(reply.value32().and_then(|mut value| value.next()))
+        };
+        // Window managers may rewrite the property without a change
+        let mut focused = active_window()?;
+        loop {
+            match conn.wait_for_event()? {
+                Event::PropertyNotify(notify) if notify.window == root && notify.atom == active => {
+                    let now = active_window()?;
+                    if now == focused {
+                        continue;
+                    }
+                    focused = now;
+                    let state = conn.query_pointer(root)?.reply()?.mask;
+                    events.send(CapturedEvent {
+                        event: InputEvent::FocusChanged,
+                        modifiers: held_modifiers(state),
+                        device_id: None,
+                        held_for: None,
+                    });
+                }
+                Event::ClientMessage(message) if message.window == wake => return Ok(()),
+                _ => {}
+            }
+        }
+    }
+
+    /// Key for each keycode: the key whose keysym is at its base level, or
+    /// the key at its position for keysyms `Key` has no name for
+    fn key_map(conn: &RustConnection) -> Result<HashMap<u8, Key>> {
+        let setup = conn.setup();
+        let (min, max) = (setup.min_keycode, setup.max_keycode);
+        let mapping = conn.get_keyboard_mapping(min, max - min + 1)?.reply()?;
+        let per_keycode = usize::from(mapping.keysyms_per_keycode).max(1);
+        let mut keys = HashMap::new();
+        for (keycode, keysyms) in (min..=max).zip(mapping.keysyms.chunks(per_keycode)) {
+            let key = keysyms
+                .first()
+                .and_then(|&keysym| Key::from_keysym(keysym))
+                .or_else(|| Key::from_physical(physical(keycode)));
+            if let Some(key) = key {
+                keys.insert(keycode, key);
+            }
+        }
+        Ok(keys)
+    }
+
+    /// Keycodes are evdev codes offset by 8
+    fn physical(keycode: u8) -> PhysicalKey {
+        PhysicalKey(u16::from(keycode).saturating_sub(8))
+    }
+
+    /// The keycode for an evdev code; None past the last X keycode
+    fn x11_keycode(key: PhysicalKey) -> Option<u8> {
+        key.0
+            .checked_add(8)
+            .and_then(|code| u8::try_from(code).ok())
+    }
+
+    /// Read recorded events until the context is disabled
+    fn record_events(
+        data: &RustConnection,
+        ctrl: &RustConnection,
+        context: record::Context,
+        keys: &HashMap<u8, Key>,
+        events: &EventSink,
+        mut fakes: FakeInputs,
+    ) -> Result<()> {
+        // Modifier keys seen going down, for their sides
+        let mut sides = ModifierSet::empty();
+        for reply in data.record_enable_context(context)? {
+            let reply = reply?;
+            match reply.category {
+                RECORD_FROM_CLIENT if !reply.client_swapped => fakes.note(&reply.data),
+                RECORD_FROM_SERVER if !reply.client_swapped => {
+                    for chunk in reply.data.chunks_exact(EVENT_SIZE) {
+                        if fakes.take(chunk) {
+                            // Pairs with the captured and emitted events
+                            tracing::trace!(
+                                target: "timeline",
+                                direction = "dropped injected",
+                                kind = chunk[0],
+                                detail = chunk[1]
+                            );
+                            continue;
+                        }
+                        let (decoded, state) = decode(chunk, keys)?;
+                        let modifiers = held_modifiers(state) | sides;
+                        for event in decoded {
+                            sides.track(&event);
+                            events.send(CapturedEvent {
+                                event,
+                                modifiers,
+                                device_id: None,
+                                held_for: None,
+                            });
+                        }
+                    }
+                }
+                RECORD_END_OF_DATA => break,
+                _ => {}
+            }
+            // Grabbed events are recorded too, so the copies delivered to
+            // the control connection are only discarded
+            while ctrl.poll_for_event()?.is_some() {}
+        }
+        Ok(())
+    }
+
+    // Fakes kept waiting for their event, should some never produce one
+    const MAX_PENDING_FAKES: usize = 64;
+
+    /// XTest FakeInput requests recorded whose device event hasn't come yet
+    ///
+    /// A fake device event carries no mark of its own, but its request is
+    /// recorded just before it; matching the two keeps XTest input, ours
+    /// included, out of the capture.
+    struct FakeInputs {
+        // None without the XTEST extension, when nothing is faked
+        opcode: Option<u8>,
+        // Event type and detail of each request, oldest first
+        pending: VecDeque<(u8, u8)>,
+    }
+
+    impl FakeInputs {
+        fn new(opcode: Option<u8>) -> Self {
+            Self {
+                opcode,
+                pending: VecDeque::new(),
+            }
+        }
+
+        /// Note the FakeInput requests among recorded client requests
+        fn note(&mut self, mut data: &[u8]) {
+            let Some(opcode) = self.opcode else {
+                return;
+            };
+            while data.len() >= 8 {
+                // Lengths count 4-byte units; 0 means a BIG-REQUESTS length
+                let len = usize::from(u16::from_ne_bytes([data[2], data[3]])) * 4;
+                if len < 8 || len > data.len() {
+                    break;
+                }
+                if data[0] == opcode && data[1] == xtest::FAKE_INPUT_REQUEST {
+                    if self.pending.len() == MAX_PENDING_FAKES {
+                        self.pending.pop_front();
+                    }
+                    self.pending.push_back((data[4], data[5]));
+                }
+                data = &data[len..];
+            }
+        }
+
+        /// Whether a recorded device event is the result of a noted fake,
+        /// which is then forgotten
+        fn take(&mut self, raw: &[u8]) -> bool {
+            let kind = raw[0] & 0x7f;
+            // Faked motion's detail says whether it was relative instead
+            let matches = |&(fake_kind, detail): &(u8, u8)| {
+                fake_kind == kind && (kind == MOTION_NOTIFY_EVENT || detail == raw[1])
+            };
+            match self.pending.iter().position(matches) {
+                Some(index) => {
+                    self.pending.remove(index);
+                    true
+                }
+                None => false,
+            }
+        }
+    }
+
+    /// Input events for one wire event, and the modifier and button state
+    /// from before it; key events are followed by their physical counterpart
+    fn decode(raw: &[u8], keys: &HashMap<u8, Key>) -> Result<(Vec<InputEvent>, KeyButMask)> {
+        // The top bit marks events sent with SendEvent
+        let decoded = match raw[0] & 0x7f {
+            kind @ (KEY_PRESS_EVENT | KEY_RELEASE_EVENT) => {
+                let (event, _) = KeyPressEvent::try_parse(raw)?;
+                let key = keys.get(&event.detail).copied();
+                let code = physical(event.detail);
+                let (key, code) = if kind == KEY_PRESS_EVENT {
+                    (
+                        key.map(InputEvent::KeyPress),
+                        InputEvent::PhysicalKeyPress(code),
+                    )
+                } else {
+                    (
+                        key.map(InputEvent::KeyRelease),
+                        InputEvent::PhysicalKeyRelease(code),
+                    )
+                };
+                (key.into_iter().chain([code]).collect(), event.state)
+            }
+            kind @ (BUTTON_PRESS_EVENT | BUTTON_RELEASE_EVENT) => {
+                let (event, _) = ButtonPressEvent::try_parse(raw)?;
+                let pressed = kind == BUTTON_PRESS_EVENT;
+                let button = match event.detail {
+                    1 => Some(MouseButton::Left),
+                    2 => Some(MouseButton::Middle),
+                    3 => Some(MouseButton::Right),
+                    8 => Some(MouseButton::Button4),
+                    9 => Some(MouseButton::Button5),
+                    _ => None,
+                };
+                // 4-7 are one wheel detent each: up, down, left, right;
+                // their releases carry nothing new
+                let wheel = match event.detail {
+                    4 => Some((0, 1)),
+                    5 => Some((0, -1)),
+                    6 => Some((-1, 0)),
+                    7 => Some((1, 0)),
+                    _ => None,
+                };
+                let decoded = match (button, wheel) {
+                    (Some(button), _) if pressed => vec![InputEvent::MousePress(button)],
+                    (Some(button), _) => vec![InputEvent::MouseRelease(button)],
+                    (None, Some((dx, dy))) if pressed => vec![InputEvent::MouseWheel { dx, dy }],
+                    _ => Vec::new(),
+                };
+                (decoded, event.state)
+            }
+            MOTION_NOTIFY_EVENT => {
+                let (event, _) = MotionNotifyEvent::try_parse(raw)?;
+                let moved = InputEvent::MouseMove {
+                    x: event.root_x.into(),
+                    y: event.root_y.into(),
+                };
+                (vec![moved], event.state)
+            }
+            _ => (Vec::new(), KeyButMask::default()),
+        };
+        Ok(decoded)
+    }
+
+    /// Core button number; the inverse of `decode`'s mapping
+    fn button_number(button: MouseButton) -> u8 {
+        match button {
+            MouseButton::Left => 1,
+            MouseButton::Middle => 2,
+            MouseButton::Right => 3,
+            MouseButton::Button4 => 8,
+            MouseButton::Button5 => 9,
+        }
+    }
+
+    /// Modifier mask for grabs, with the same Mod1/Mod4 assumption as
+    /// `held_modifiers`
+    fn mod_mask(modifier: Modifier) -> ModMask {
+        match modifier.unsided() {
+            Modifier::Ctrl => ModMask::CONTROL,
+            Modifier::Shift => ModMask::SHIFT,
+            Modifier::Alt => ModMask::M1,
+            _ => ModMask::M4,
+        }
+    }
+
+    /// Modifiers in a core event's state, taking Alt and Super to be on
+    /// their usual Mod1 and Mod4; the state doesn't say which side is held
+    fn held_modifiers(state: KeyButMask) -> ModifierSet {
+        let mut modifiers = ModifierSet::empty();
+        modifiers.set(ModifierSet::CTRL, state.contains(KeyButMask::CONTROL));
+        modifiers.set(ModifierSet::SHIFT, state.contains(KeyButMask::SHIFT));
+        modifiers.set(ModifierSet::ALT, state.contains(KeyButMask::MOD1));
+        modifiers.set(ModifierSet::META, state.contains(KeyButMask::MOD4));
+        modifiers
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        const XTEST: u8 = 132;
+
+        /// FakeInput request as recorded, in native byte order
+        fn fake_input(kind: u8, detail: u8) -> Vec<u8> {
+            let mut request = vec![0; 36];
+            request[0] = XTEST;
+            request[1] = xtest::FAKE_INPUT_REQUEST;
+            request[2..4].copy_from_slice(&9u16.to_ne_bytes());
+            request[4] = kind;
+            request[5] = detail;
+            request
+        }
+
+        fn device_event(kind: u8, detail: u8) -> [u8; EVENT_SIZE] {
+            let mut event = [0; EVENT_SIZE];
+            event[0] = kind;
+            event[1] = detail;
+            event
+        }
+
+        #[test]
+        fn faked_events_are_taken_once() {
+            let mut fakes = FakeInputs::new(Some(XTEST));
+            let mut recorded = fake_input(KEY_PRESS_EVENT, 38);
+            recorded.extend(fake_input(MOTION_NOTIFY_EVENT, 1));
+            fakes.note(&recorded);
+
+            // Real input with another detail goes through
+            assert!(!fakes.take(&device_event(KEY_PRESS_EVENT, 39)));
+            assert!(fakes.take(&device_event(KEY_PRESS_EVENT, 38)));
+            assert!(!fakes.take(&device_event(KEY_PRESS_EVENT, 38)));
+            // Motion notifies have detail 0 whatever the fake's was
+            assert!(fakes.take(&device_event(MOTION_NOTIFY_EVENT, 0)));
+            assert!(fakes.pending.is_empty());
+        }
+
+        #[test]
+        fn other_requests_and_missing_xtest_are_ignored() {
+            let mut request = fake_input(KEY_PRESS_EVENT, 38);
+            request[1] = xtest::FAKE_INPUT_REQUEST + 1;
+            let mut fakes = FakeInputs::new(Some(XTEST));
+            fakes.note(&request);
+            assert!(!fakes.take(&device_event(KEY_PRESS_EVENT, 38)));
+
+            let mut fakes = FakeInputs::new(None);
+            fakes.note(&fake_input(KEY_PRESS_EVENT, 38));
+            assert!(!fakes.take(&device_event(KEY_PRESS_EVENT, 38)));
+        }
+
+        #[test]
+        fn wheel_buttons_decode_to_wheel_events() {
+            let keys = HashMap::new();
+            let decoded = |kind, detail| decode(&device_event(kind, detail), &keys).unwrap().0;
+            let wheel = |dx, dy| vec![InputEvent::MouseWheel { dx, dy }];
+            assert_eq!(decoded(BUTTON_PRESS_EVENT, 4), wheel(0, 1));
+            assert_eq!(decoded(BUTTON_PRESS_EVENT, 5), wheel(0, -1));
+            assert_eq!(decoded(BUTTON_PRESS_EVENT, 6), wheel(-1, 0));
+            assert_eq!(decoded(BUTTON_PRESS_EVENT, 7), wheel(1, 0));
+            // The release half of a detent adds nothing
+            assert!(decoded(BUTTON_RELEASE_EVENT, 4).is_empty());
+            assert_eq!(
+                decoded(BUTTON_PRESS_EVENT, 1),
+                [InputEvent::MousePress(MouseButton::Left)]
+            );
+        }
+
+        #[test]
+        fn physical_keys_past_the_last_keycode_have_none() {
+            assert_eq!(x11_keycode(PhysicalKey(30)), Some(38));
+            assert_eq!(physical(38), PhysicalKey(30));
+            assert_eq!(x11_keycode(PhysicalKey(247)), Some(255));
+            assert_eq!(x11_keycode(PhysicalKey(248)), None);
+            assert_eq!(x11_keycode(PhysicalKey(u16::MAX)), None);
         }
 
-        fn event_stream(&self) -> Box<dyn Stream<Item = InputEvent> + Send + Unpin> {
-            todo!("Implement event stream from X11")
+        #[test]
+        fn core_state_gives_side_agnostic_modifiers() {
+            let state = KeyButMask::CONTROL | KeyButMask::MOD1 | KeyButMask::BUTTON1;
+            assert_eq!(held_modifiers(state), ModifierSet::CTRL | ModifierSet::ALT);
+            let mut event = device_event(BUTTON_PRESS_EVENT, 1);
+            // The state field of a core input event
+            event[28..30].copy_from_slice(&u16::from(KeyButMask::SHIFT).to_ne_bytes());
+            let (_, state) = decode(&event, &HashMap::new()).unwrap();
+            assert_eq!(held_modifiers(state), ModifierSet::SHIFT);
+        }
+
+        #[test]
+        fn pending_fakes_are_bounded() {
+            let mut fakes = FakeInputs::new(Some(XTEST));
+            for _ in 0..MAX_PENDING_FAKES * 2 {
+                fakes.note(&fake_input(BUTTON_PRESS_EVENT, 1));
+            }
+            assert_eq!(fakes.pending.len(), MAX_PENDING_FAKES);
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod macos_impl {
+    use super::*;
+    use anyhow::anyhow;
+    use core_foundation::base::TCFType;
+    use core_foundation::mach_port::CFMachPortRef;
+    use core_foundation::runloop::{kCFRunLoopCommonModes, kCFRunLoopDefaultMode, CFRunLoop};
+    use core_graphics::event::{
+        CGEvent, CGEventFlags, CGEventTap, CGEventTapLocation, CGEventTapOptions,
+        CGEventTapPlacement, CGEventType, EventField,
+    };
+    use std::cell::Cell;
+    use std::rc::Rc;
+    use std::sync::{mpsc, Arc};
+    use std::thread::JoinHandle;
+    use tracing::warn;
+
+    #[link(name = "ApplicationServices", kind = "framework")]
+    extern "C" {
+        fn CGPreflightListenEventAccess() -> bool;
+        fn CGRequestListenEventAccess() -> bool;
+        fn CGEventTapEnable(tap: CFMachPortRef, enable: bool);
+    }
+
+    // How long the tap thread runs its loop between checks of `running`
+    const RUN_SLICE: Duration = Duration::from_millis(250);
+
+    // Device-dependent flag bits (NX_DEVICE*KEYMASK from IOKit), which say
+    // which side of each modifier is down
+    const DEVICE_MODIFIER_MASKS: [(u64, Modifier); 8] = [
+        (0x0001, Modifier::CtrlLeft),
+        (0x2000, Modifier::CtrlRight),
+        (0x0002, Modifier::ShiftLeft),
+        (0x0004, Modifier::ShiftRight),
+        (0x0020, Modifier::AltLeft),
+        (0x0040, Modifier::AltRight),
+        (0x0008, Modifier::MetaLeft),
+        (0x0010, Modifier::MetaRight),
+    ];
+
+    // CFRunLoopStop is documented as callable from any thread
+    struct RunLoop(CFRunLoop);
+    unsafe impl Send for RunLoop {}
+    unsafe impl Sync for RunLoop {}
+
+    /// Listen-only event tap on the session event stream, serviced by a
+    /// dedicated run loop thread
+    pub struct MacCapture {
+        options: CaptureOptions,
+        hotkeys: Vec<Hotkey>,
+        events: EventSink,
+        running: Arc<AtomicBool>,
+        run_loop: RunLoop,
+        tap_thread: Option<JoinHandle<()>>,
+    }
+
+    impl MacCapture {
+        /// Fails unless the process has Input Monitoring access, without
+        /// which the tap sees no keyboard events; macOS is asked to prompt
+        /// for it on the first attempt
+        pub fn new(options: CaptureOptions) -> Result<Self> {
+            if !unsafe { CGPreflightListenEventAccess() } {
+                unsafe { CGRequestListenEventAccess() };
+                return Err(CaptureError::permission_denied().into());
+            }
+
+            let events = EventSink::new(options);
+            let running = Arc::new(AtomicBool::new(true));
+            let (ready_tx, ready_rx) = mpsc::channel();
+            let sender = events.clone();
+            let flag = running.clone();
+            let tap_thread = std::thread::Builder::new()
+                .name("input-tap".into())
+                .spawn(move || tap_thread(sender, options, flag, ready_tx))?;
+
+            match ready_rx.recv() {
+                Ok(Ok(run_loop)) => Ok(Self {
+                    options,
+                    hotkeys: Vec::new(),
+                    events,
+                    running,
+                    run_loop,
+                    tap_thread: Some(tap_thread),
+                }),
+                Ok(Err(err)) => {
+                    let _ = tap_thread.join();
+                    Err(err)
+                }
+                Err(_) => Err(anyhow!("input tap thread exited during setup")),
+            }
+        }
+
+        pub fn options(&self) -> CaptureOptions {
+            self.options
+        }
+    }
+
+    impl InputCapture for MacCapture {
+        /// The tap sees every event, so hotkeys are only recorded here;
+        /// matching happens downstream on the event stream
+        fn register_hotkey(&mut self, hotkey: Hotkey) -> Result<()> {
+            if !self.hotkeys.contains(&hotkey) {
+                self.hotkeys.push(hotkey);
+            }
+            Ok(())
+        }
+
+        fn registered_hotkeys(&self) -> Vec<Hotkey> {
+            self.hotkeys.clone()
+        }
+
+        fn event_stream(&self) -> Box<dyn Stream<Item = CapturedEvent> + Send + Unpin> {
+            self.events.stream()
+        }
+
+        fn dropped_events(&self) -> u64 {
+            self.events.dropped()
+        }
+
+        fn pause(&mut self) -> Result<()> {
+            self.events.set_paused(true);
+            Ok(())
+        }
+
+        fn resume(&mut self) -> Result<()> {
+            self.events.set_paused(false);
+            Ok(())
+        }
+
+        fn stop(&mut self) -> Result<()> {
+            let Some(tap_thread) = self.tap_thread.take() else {
+                return Ok(());
+            };
+            self.running.store(false, Ordering::SeqCst);
+            self.run_loop.0.stop();
+            tap_thread
+                .join()
+                .map_err(|_| anyhow!("input tap thread panicked"))
+        }
+    }
+
+    impl Drop for MacCapture {
+        fn drop(&mut self) {
+            if let Err(err) = self.stop() {
+                warn!("stopping input capture: {err:#}");
+            }
+        }
+    }
+
+    /// Install the tap, then run this thread's loop until `running` clears;
+    /// the loop runs in slices so a stop before it starts is not missed
+    fn tap_thread(
+        events: EventSink,
+        options: CaptureOptions,
+        running: Arc<AtomicBool>,
+        ready: mpsc::Sender<Result<RunLoop>>,
+    ) {
+        // macOS disables taps that time out; the callback re-enables them
+        // through the port, which only exists once the tap does
+        let port: Rc<Cell<Option<CFMachPortRef>>> = Rc::default();
+        let callback_port = port.clone();
+        let callback = move |_proxy, kind: CGEventType, event: &CGEvent| -> Option<CGEvent> {
+            if let CGEventType::TapDisabledByTimeout | CGEventType::TapDisabledByUserInput = kind {
+                if let Some(port) = callback_port.get() {
+                    unsafe { CGEventTapEnable(port, true) };
+                }
+                return None;
+            }
+            let modifiers = held_modifiers(event.get_flags());
+            for decoded in decode(kind, event)
+                .into_iter()
+                .filter(|event| options.accepts(event))
+            {
+                events.send(CapturedEvent {
+                    event: decoded,
+                    modifiers,
+                    device_id: None,
+                    held_for: None,
+                });
+            }
+            // Listen-only taps pass the original event on
+            None
+        };
+
+        let tap = match CGEventTap::new(
+            CGEventTapLocation::Session,
+            CGEventTapPlacement::HeadInsertEventTap,
+            CGEventTapOptions::ListenOnly,
+            event_kinds(options),
+            callback,
+        ) {
+            Ok(tap) => tap,
+            Err(()) => {
+                // Creation fails when access was revoked after the check
+                let _ = ready.send(Err(CaptureError::permission_denied().into()));
+                return;
+            }
+        };
+        let source = match tap
+            .mach_port
+            .create_runloop_source(0)
+            .map_err(|()| anyhow!("failed to create a run loop source for the event tap"))
+        {
+            Ok(source) => source,
+            Err(err) => {
+                let _ = ready.send(Err(err));
+                return;
+            }
+        };
+        port.set(Some(tap.mach_port.as_concrete_TypeRef()));
+
+        let run_loop = CFRunLoop::get_current();
+        unsafe { run_loop.add_source(&source, kCFRunLoopCommonModes) };
+        tap.enable();
+        let _ = ready.send(Ok(RunLoop(run_loop.clone())));
+
+        while running.load(Ordering::SeqCst) {
+            CFRunLoop::run_in_mode(unsafe { kCFRunLoopDefaultMode }, RUN_SLICE, false);
+        }
+
+        unsafe { CGEventTapEnable(tap.mach_port.as_concrete_TypeRef(), false) };
+        unsafe { run_loop.remove_source(&source, kCFRunLoopCommonModes) };
+    }
+
+    fn event_kinds(options: CaptureOptions) -> Vec<CGEventType> {
+        let mut kinds = vec![
+            CGEventType::KeyDown,
+            CGEventType::KeyUp,
+            CGEventType::FlagsChanged,
+            CGEventType::LeftMouseDown,
+            CGEventType::LeftMouseUp,
+            CGEventType::RightMouseDown,
+            CGEventType::RightMouseUp,
+            CGEventType::OtherMouseDown,
+            CGEventType::OtherMouseUp,
+            CGEventType::ScrollWheel,
+        ];
+        if options.mouse_move {
+            kinds.extend([
+                CGEventType::MouseMoved,
+                CGEventType::LeftMouseDragged,
+                CGEventType::RightMouseDragged,
+                CGEventType::OtherMouseDragged,
+            ]);
+        }
+        kinds
+    }
+
+    /// Input events for one tapped event; key events are followed by their
+    /// physical counterpart
+    fn decode(kind: CGEventType, event: &CGEvent) -> Vec<InputEvent> {
+        let button = || match event.get_integer_value_field(EventField::MOUSE_EVENT_BUTTON_NUMBER) {
+            2 => Some(MouseButton::Middle),
+            3 => Some(MouseButton::Button4),
+            4 => Some(MouseButton::Button5),
+            _ => None,
+        };
+        match kind {
+            CGEventType::KeyDown => key_events(event, true),
+            CGEventType::KeyUp => key_events(event, false),
+            CGEventType::FlagsChanged => {
+                // Modifier keys only report the new flags, so whether this
+                // was a press follows from their own flag
+                let Some(key) = key_code(event) else {
+                    return Vec::new();
+                };
+                let flag = match key {
+                    Key::Shift | Key::ShiftRight => CGEventFlags::CGEventFlagShift,
+                    Key::Ctrl | Key::CtrlRight => CGEventFlags::CGEventFlagControl,
+                    Key::Alt | Key::AltGr => CGEventFlags::CGEventFlagAlternate,
+                    Key::Meta | Key::MetaRight => CGEventFlags::CGEventFlagCommand,
+                    Key::CapsLock => CGEventFlags::CGEventFlagAlphaShift,
+                    _ => return Vec::new(),
+                };
+                key_events(event, event.get_flags().contains(flag))
+            }
+            CGEventType::MouseMoved
+            | CGEventType::LeftMouseDragged
+            | CGEventType::RightMouseDragged
+            | CGEventType::OtherMouseDragged => {
+                let at = event.location();
+                vec![InputEvent::MouseMove {
+                    x: at.x as i32,
+                    y: at.y as i32,
+                }]
+            }
+            CGEventType::LeftMouseDown => vec![InputEvent::MousePress(MouseButton::Left)],
+            CGEventType::LeftMouseUp => vec![InputEvent::MouseRelease(MouseButton::Left)],
+            CGEventType::RightMouseDown => vec![InputEvent::MousePress(MouseButton::Right)],
+            CGEventType::RightMouseUp => vec![InputEvent::MouseRelease(MouseButton::Right)],
+            CGEventType::OtherMouseDown => {
+                button().map(InputEvent::MousePress).into_iter().collect()
+            }
+            CGEventType::OtherMouseUp => {
+                button().map(InputEvent::MouseRelease).into_iter().collect()
+            }
+            CGEventType::ScrollWheel => {
+                // Line deltas; axis 1 is positive up, axis 2 positive left
+                let dy = event.get_integer_value_field(EventField::SCROLL_WHEEL_EVENT_DELTA_AXIS_1);
+                let dx =
+                    -event.get_integer_value_field(EventField::SCROLL_WHEEL_EVENT_DELTA_AXIS_2);
+                if dx == 0 && dy == 0 {
+                    return Vec::new();
+                }
+                vec![InputEvent::MouseWheel {
+                    dx: dx as i32,
+                    dy: dy as i32,
+                }]
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    fn held_modifiers(flags: CGEventFlags) -> ModifierSet {
+        let mut modifiers = ModifierSet::empty();
+        modifiers.set(
+            ModifierSet::CTRL,
+            flags.contains(CGEventFlags::CGEventFlagControl),
+        );
+        modifiers.set(
+            ModifierSet::SHIFT,
+            flags.contains(CGEventFlags::CGEventFlagShift),
+        );
+        modifiers.set(
+            ModifierSet::ALT,
+            flags.contains(CGEventFlags::CGEventFlagAlternate),
+        );
+        modifiers.set(
+            ModifierSet::META,
+            flags.contains(CGEventFlags::CGEventFlagCommand),
+        );
+        for (mask, modifier) in DEVICE_MODIFIER_MASKS {
+            if flags.bits() & mask != 0 {
+                modifiers |= modifier.into();
+            }
+        }
+        modifiers
+    }
+
+    fn key_code(event: &CGEvent) -> Option<Key> {
+        let code = event.get_integer_value_field(EventField::KEYBOARD_EVENT_KEYCODE);
+        Key::from_mac_key_code(u16::try_from(code).ok()?)
+    }
+
+    /// Virtual key codes are not evdev codes, so only keys `Key` names get
+    /// a physical event
+    fn key_events(event: &CGEvent, pressed: bool) -> Vec<InputEvent> {
+        let Some(key) = key_code(event) else {
+            return Vec::new();
+        };
+        if pressed {
+            vec![
+                InputEvent::KeyPress(key),
+                InputEvent::PhysicalKeyPress(key.physical()),
+            ]
+        } else {
+            vec![
+                InputEvent::KeyRelease(key),
+                InputEvent::PhysicalKeyRelease(key.physical()),
+            ]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio_stream::StreamExt;
+
+    fn captured(event: InputEvent) -> CapturedEvent {
+        CapturedEvent {
+            event,
+            modifiers: ModifierSet::empty(),
+            device_id: None,
+            held_for: None,
+        }
+    }
+
+    fn from_device(event: InputEvent, device: &str) -> CapturedEvent {
+        CapturedEvent {
+            device_id: Some(DeviceId(device.into())),
+            ..captured(event)
+        }
+    }
+
+    #[tokio::test]
+    async fn filters_pass_only_matching_events() {
+        let mixed = vec![
+            captured(InputEvent::KeyPress(Key::A)),
+            captured(InputEvent::MouseMove { x: 1, y: 2 }),
+            captured(InputEvent::KeyRelease(Key::A)),
+            from_device(InputEvent::MousePress(MouseButton::Left), "Mouse"),
+            from_device(InputEvent::KeyPress(Key::B), "Macro Pad"),
+        ];
+        let presses: Vec<InputEvent> = tokio_stream::iter(mixed.clone())
+            .filtered(|event| matches!(event, InputEvent::KeyPress(_)))
+            .map(|captured| captured.event)
+            .collect()
+            .await;
+        assert_eq!(
+            presses,
+            [InputEvent::KeyPress(Key::A), InputEvent::KeyPress(Key::B)]
+        );
+
+        let pad: Vec<InputEvent> = tokio_stream::iter(mixed)
+            .only_device(DeviceId("Macro Pad".into()))
+            .map(|captured| captured.event)
+            .collect()
+            .await;
+        assert_eq!(pad, [InputEvent::KeyPress(Key::B)]);
+    }
+
+    const KEYS: [Key; 5] = [Key::A, Key::B, Key::C, Key::D, Key::E];
+
+    fn sink_with(backlog: usize, overflow: Overflow) -> EventSink {
+        EventSink::new(CaptureOptions::default().with_backlog(backlog, overflow))
+    }
+
+    /// Presses `stream` yields until `count` arrive or it goes quiet
+    async fn read_presses(
+        stream: &mut (impl Stream<Item = CapturedEvent> + Unpin),
+        count: usize,
+    ) -> Vec<Key> {
+        let mut keys = Vec::new();
+        while keys.len() < count {
+            let next = tokio::time::timeout(Duration::from_millis(100), stream.next());
+            match next.await {
+                Ok(Some(CapturedEvent {
+                    event: InputEvent::KeyPress(key),
+                    ..
+                })) => keys.push(key),
+                _ => break,
+            }
+        }
+        keys
+    }
+
+    #[tokio::test]
+    async fn overflow_drops_the_oldest_or_newest_events() {
+        let sink = sink_with(2, Overflow::DropOldest);
+        let mut stream = sink.stream();
+        for key in KEYS {
+            sink.send(captured(InputEvent::KeyPress(key)));
+        }
+        // The lag is found, and counted, as the stream reads
+        assert_eq!(read_presses(&mut stream, 5).await, [Key::D, Key::E]);
+        assert_eq!(sink.dropped(), 3);
+
+        let sink = sink_with(2, Overflow::DropNewest);
+        let mut stream = sink.stream();
+        for key in KEYS {
+            sink.send(captured(InputEvent::KeyPress(key)));
+        }
+        assert_eq!(sink.dropped(), 3);
+        assert_eq!(read_presses(&mut stream, 5).await, [Key::A, Key::B]);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn overflow_block_holds_the_sender_until_the_stream_reads() {
+        let sink = sink_with(2, Overflow::Block);
+        let mut stream = sink.stream();
+        let sender = {
+            let sink = sink.clone();
+            std::thread::spawn(move || {
+                for key in KEYS {
+                    sink.send(captured(InputEvent::KeyPress(key)));
+                }
+            })
+        };
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(!sender.is_finished());
+        assert!(sink.is_full());
+        assert_eq!(read_presses(&mut stream, 5).await, KEYS);
+        sender.join().unwrap();
+        assert_eq!(sink.dropped(), 0);
+    }
+
+    #[tokio::test]
+    async fn paused_sinks_discard_events_until_resumed() {
+        let sink = sink_with(2, Overflow::Block);
+        let mut stream = sink.stream();
+        sink.send(captured(InputEvent::KeyPress(Key::A)));
+        sink.set_paused(true);
+        // Paused, a full Block sink doesn't hold the sender back either
+        for key in KEYS {
+            sink.send(captured(InputEvent::KeyPress(key)));
+        }
+        sink.send(captured(InputEvent::KeyRelease(Key::A)));
+        sink.set_paused(false);
+        sink.send(captured(InputEvent::KeyPress(Key::F)));
+        let events: Vec<InputEvent> = [stream.next().await, stream.next().await]
+            .into_iter()
+            .map(|captured| captured.unwrap().event)
+            .collect();
+        assert_eq!(
+            events,
+            [InputEvent::KeyPress(Key::A), InputEvent::KeyPress(Key::F)]
+        );
+        assert_eq!(sink.dropped(), 0);
+    }
+
+    #[tokio::test]
+    async fn releases_report_how_long_their_press_was_held() {
+        let sink = sink_with(16, Overflow::DropOldest);
+        let mut stream = sink.stream();
+        sink.send(captured(InputEvent::KeyPress(Key::A)));
+        std::thread::sleep(Duration::from_millis(200));
+        sink.send(captured(InputEvent::KeyRelease(Key::A)));
+        // Pressed before capture started
+        sink.send(captured(InputEvent::MouseRelease(MouseButton::Left)));
+
+        assert_eq!(stream.next().await.unwrap().held_for, None);
+        let held = stream.next().await.unwrap().held_for.unwrap();
+        assert!(
+            (Duration::from_millis(200)..Duration::from_millis(400)).contains(&held),
+            "{held:?}"
+        );
+        assert_eq!(stream.next().await.unwrap().held_for, None);
+    }
+
+    #[tokio::test]
+    async fn auto_repeat_presses_are_dropped_unless_asked_for() {
+        let (press, release) = (InputEvent::KeyPress(Key::A), InputEvent::KeyRelease(Key::A));
+        // Held through three repeats, then tapped again
+        let held = [
+            press.clone(),
+            press.clone(),
+            press.clone(),
+            press.clone(),
+            release.clone(),
+            press.clone(),
+        ];
+        for (options, expected) in [
+            (
+                CaptureOptions::default(),
+                vec![press.clone(), release.clone(), press.clone()],
+            ),
+            (CaptureOptions::default().with_key_repeat(), held.to_vec()),
+        ] {
+            let sink = EventSink::new(options);
+            let stream = sink.stream();
+            for event in &held {
+                sink.send(captured(event.clone()));
+            }
+            drop(sink);
+            let events: Vec<InputEvent> = stream.map(|captured| captured.event).collect().await;
+            assert_eq!(events, expected);
+        }
+    }
+
+    #[test]
+    fn modifier_sets_insert_and_combine() {
+        let mut set = ModifierSet::empty();
+        set.insert(Modifier::ShiftLeft.into());
+        assert!(set.contains(ModifierSet::SHIFT | ModifierSet::SHIFT_LEFT));
+        assert!(!set.contains(ModifierSet::SHIFT_RIGHT));
+        set.insert(Modifier::Ctrl.into());
+        assert_eq!(
+            set.modifiers().collect::<Vec<_>>(),
+            [Modifier::Ctrl, Modifier::Shift]
+        );
+
+        let other: ModifierSet = [Modifier::Ctrl, Modifier::Alt].into_iter().collect();
+        assert_eq!(set & other, ModifierSet::CTRL);
+        assert_eq!(
+            (set | other).modifiers().collect::<Vec<_>>(),
+            [Modifier::Ctrl, Modifier::Shift, Modifier::Alt]
+        );
+        // Taking a modifier away clears both of its sides
+        assert_eq!(set.without(Modifier::ShiftRight), ModifierSet::CTRL);
+    }
+
+    #[test]
+    fn held_sets_match_hotkey_modifiers_by_side() {
+        let left_shift = ModifierSet::from(Modifier::ShiftLeft);
+        assert!(left_shift.matches(&[Modifier::Shift]));
+        assert!(left_shift.matches(&[Modifier::ShiftLeft]));
+        assert!(!left_shift.matches(&[Modifier::ShiftRight]));
+        assert!(!left_shift.matches(&[]));
+        assert!(!left_shift.matches(&[Modifier::Shift, Modifier::Ctrl]));
+        // Backends that can't tell sides only satisfy side-agnostic hotkeys
+        assert!(ModifierSet::SHIFT.matches(&[Modifier::Shift]));
+        assert!(!ModifierSet::SHIFT.matches(&[Modifier::ShiftLeft]));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn tracking_keeps_a_modifier_while_either_side_is_down() {
+        let mut set = ModifierSet::empty();
+        set.track(&InputEvent::KeyPress(Key::Shift));
+        set.track(&InputEvent::KeyPress(Key::ShiftRight));
+        set.track(&InputEvent::KeyRelease(Key::Shift));
+        assert_eq!(set, ModifierSet::SHIFT | ModifierSet::SHIFT_RIGHT);
+        set.track(&InputEvent::KeyPress(Key::A));
+        set.track(&InputEvent::KeyRelease(Key::ShiftRight));
+        assert_eq!(set, ModifierSet::empty());
+    }
+
+    /// Backend taking each hotkey once, like one whose grabs conflict
+    #[derive(Default)]
+    struct OwnOnce {
+        hotkeys: Vec<Hotkey>,
+        attempts: usize,
+    }
+
+    impl InputCapture for OwnOnce {
+        fn register_hotkey(&mut self, hotkey: Hotkey) -> Result<()> {
+            self.attempts += 1;
+            if self.hotkeys.contains(&hotkey) {
+                return Err(CaptureError::HotkeyAlreadyOwned(hotkey).into());
+            }
+            self.hotkeys.push(hotkey);
+            Ok(())
+        }
+
+        fn registered_hotkeys(&self) -> Vec<Hotkey> {
+            self.hotkeys.clone()
+        }
+
+        fn event_stream(&self) -> Box<dyn Stream<Item = CapturedEvent> + Send + Unpin> {
+            Box::new(tokio_stream::empty())
+        }
+
+        fn dropped_events(&self) -> u64 {
+            0
+        }
+
+        fn pause(&mut self) -> Result<()> {
+            Ok(())
+        }
+
+        fn resume(&mut self) -> Result<()> {
+            Ok(())
         }
 
         fn stop(&mut self) -> Result<()> {
-            todo!("Close X11 connection")
+            Ok(())
         }
     }
+
+    #[test]
+    fn batch_registration_stops_at_the_failing_hotkey() {
+        let batch = [
+            Hotkey::key(Key::F1),
+            Hotkey::key(Key::F2),
+            Hotkey::key(Key::F1),
+            Hotkey::key(Key::F3),
+        ];
+        let mut capture = OwnOnce::default();
+        let error = capture.register_hotkeys(&batch).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            format!("failed to register hotkey {} (2 of 4 registered)", batch[2])
+        );
+        assert!(matches!(
+            error.downcast_ref(),
+            Some(CaptureError::HotkeyAlreadyOwned(hotkey)) if *hotkey == batch[2]
+        ));
+        // The ones before it stay registered; F3 is never attempted
+        assert_eq!(capture.registered_hotkeys(), batch[..2]);
+        assert_eq!(capture.attempts, 3);
+
+        let mut capture = OwnOnce::default();
+        capture.register_hotkeys(&batch[..2]).unwrap();
+        assert_eq!(capture.registered_hotkeys(), batch[..2]);
+    }
 }
@@ -0,0 +1,215 @@
+// Deciding, inside a hook callback, whether the OS event it was handed
+// should be consumed instead of passed on to the focused application.
+
+use crate::{check_suppressible, Hotkey, InputEvent, Key, Modifier, ModifierSet, Trigger};
+use anyhow::Result;
+
+/// Suppressed hotkeys, consulted synchronously for every hooked event
+#[derive(Debug, Default)]
+pub(crate) struct Suppressor {
+    hotkeys: Vec<Hotkey>,
+    // Keys consumed outright, which count as a modifier while down
+    remaps: Vec<(Key, Modifier)>,
+    // Triggers whose press was consumed, so their repeats and release are too
+    down: Vec<Trigger>,
+}
+
+impl Suppressor {
+    /// Held keys are not tracked in the hook, and multi-press or timed
+    /// triggers are only known once their first press has gone through, so
+    /// those are rejected
+    pub(crate) fn add(&mut self, hotkey: Hotkey) -> Result<()> {
+        check_suppressible(&hotkey)?;
+        if !self.hotkeys.contains(&hotkey) {
+            self.hotkeys.push(hotkey);
+        }
+        Ok(())
+    }
+
+    /// Consume every press, repeat and release of `key`, and count it as
+    /// `modifier` when matching hotkeys while it is down
+    pub(crate) fn add_modifier_remap(&mut self, key: Key, modifier: Modifier) {
+        self.remaps.retain(|&(remapped, _)| remapped != key);
+        self.remaps.push((key, modifier));
+    }
+
+    /// Forget which presses were consumed, for when their releases won't
+    /// be seen
+    pub(crate) fn release_all(&mut self) {
+        self.down.clear();
+    }
+
+    /// Whether to consume the OS event that decoded to `events`, given the
+    /// modifiers held before it
+    pub(crate) fn should_suppress(
+        &mut self,
+        events: &[InputEvent],
+        modifiers: ModifierSet,
+    ) -> bool {
+        // Every event is checked, so `down` stays in step
+        let mut suppress = false;
+        for event in events {
+            suppress |= self.check(event, modifiers);
+        }
+        suppress
+    }
+
+    fn check(&mut self, event: &InputEvent, modifiers: ModifierSet) -> bool {
+        let (trigger, pressed) = match *event {
+            InputEvent::KeyPress(key) => (Trigger::Key(key), true),
+            InputEvent::KeyRelease(key) => (Trigger::Key(key), false),
+            InputEvent::PhysicalKeyPress(key) => (Trigger::PhysicalKey(key), true),
+            InputEvent::PhysicalKeyRelease(key) => (Trigger::PhysicalKey(key), false),
+            InputEvent::MousePress(button) => (Trigger::MouseButton(button), true),
+            InputEvent::MouseRelease(button) => (Trigger::MouseButton(button), false),
+            _ => return false,
+        };
+        if !pressed {
+            // Released modifiers don't matter; the press decided
+            return match self.down.iter().position(|down| *down == trigger) {
+                Some(index) => {
+                    self.down.swap_remove(index);
+                    true
+                }
+                None => false,
+            };
+        }
+        if self.down.contains(&trigger) {
+            return true;
+        }
+        if let Trigger::Key(key) = trigger {
+            if self.remaps.iter().any(|&(remapped, _)| remapped == key) {
+                self.down.push(trigger);
+                return true;
+            }
+        }
+        let modifiers = self.with_remaps(modifiers);
+        let matched = self
+            .hotkeys
+            .iter()
+            .any(|hotkey| hotkey.trigger == trigger && modifiers.matches(&hotkey.modifiers));
+        if matched {
+            self.down.push(trigger);
+        }
+        matched
+    }
+
+    // Remapped keys only count while their press was consumed
+    fn with_remaps(&self, modifiers: ModifierSet) -> ModifierSet {
+        self.remaps
+            .iter()
+            .filter(|&&(key, _)| self.down.contains(&Trigger::Key(key)))
+            .fold(modifiers, |set, &(_, modifier)| set | modifier.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ctrl_a() -> Suppressor {
+        let mut suppressor = Suppressor::default();
+        suppressor
+            .add(Hotkey::combo(&[Modifier::Ctrl], Trigger::Key(Key::A)))
+            .unwrap();
+        suppressor
+    }
+
+    fn consumed(suppressor: &mut Suppressor, event: InputEvent, modifiers: ModifierSet) -> bool {
+        suppressor.should_suppress(&[event], modifiers)
+    }
+
+    #[test]
+    fn only_registered_hotkeys_are_consumed() {
+        let mut suppressor = ctrl_a();
+        let none = ModifierSet::empty();
+        let ctrl = ModifierSet::CTRL;
+        assert!(!consumed(
+            &mut suppressor,
+            InputEvent::KeyPress(Key::A),
+            none
+        ));
+        assert!(!consumed(
+            &mut suppressor,
+            InputEvent::KeyRelease(Key::A),
+            none
+        ));
+        assert!(!consumed(
+            &mut suppressor,
+            InputEvent::KeyPress(Key::B),
+            ctrl
+        ));
+        assert!(consumed(
+            &mut suppressor,
+            InputEvent::KeyPress(Key::A),
+            ctrl
+        ));
+        // Repeats and the release go with the press, whatever is held by then
+        assert!(consumed(
+            &mut suppressor,
+            InputEvent::KeyPress(Key::A),
+            none
+        ));
+        assert!(consumed(
+            &mut suppressor,
+            InputEvent::KeyRelease(Key::A),
+            none
+        ));
+    }
+
+    #[test]
+    fn remapped_key_is_consumed_and_counts_as_its_modifier() {
+        let mut suppressor = ctrl_a();
+        suppressor.add_modifier_remap(Key::CapsLock, Modifier::Ctrl);
+        let none = ModifierSet::empty();
+        assert!(consumed(
+            &mut suppressor,
+            InputEvent::KeyPress(Key::CapsLock),
+            none
+        ));
+        assert!(consumed(
+            &mut suppressor,
+            InputEvent::KeyPress(Key::CapsLock),
+            none
+        ));
+        assert!(consumed(
+            &mut suppressor,
+            InputEvent::KeyPress(Key::A),
+            none
+        ));
+        assert!(consumed(
+            &mut suppressor,
+            InputEvent::KeyRelease(Key::A),
+            none
+        ));
+        assert!(consumed(
+            &mut suppressor,
+            InputEvent::KeyRelease(Key::CapsLock),
+            none
+        ));
+        // Released, it no longer stands in for Ctrl
+        assert!(!consumed(
+            &mut suppressor,
+            InputEvent::KeyPress(Key::A),
+            none
+        ));
+    }
+
+    #[test]
+    fn release_all_forgets_a_held_remap() {
+        let mut suppressor = ctrl_a();
+        suppressor.add_modifier_remap(Key::CapsLock, Modifier::Ctrl);
+        let none = ModifierSet::empty();
+        assert!(consumed(
+            &mut suppressor,
+            InputEvent::KeyPress(Key::CapsLock),
+            none
+        ));
+        suppressor.release_all();
+        assert!(!consumed(
+            &mut suppressor,
+            InputEvent::KeyPress(Key::A),
+            none
+        ));
+    }
+}
@@ -1,6 +1,7 @@
 // Command palette UI stub
 // TODO: Implement using egui or iced
 
+#[derive(Default)]
 pub struct CommandPalette {
     // Future: list of available commands, search, etc.
 }